@@ -0,0 +1,4 @@
+(module
+  (func $main (export "main")
+    f32.const 1
+    drop))
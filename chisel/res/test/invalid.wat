@@ -0,0 +1,3 @@
+(module
+  (func $main (export "main") (result i32)
+    i64.const 1))
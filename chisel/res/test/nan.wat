@@ -0,0 +1,4 @@
+(module
+  (func $main (export "main") (param f32) (result f32)
+    local.get 0
+    f32.sqrt))
@@ -0,0 +1 @@
+(module (table 1 externref))
@@ -0,0 +1,5 @@
+(module
+  (func $main (export "main") (param f32) (result i32)
+    local.get 0
+    i32.trunc_sat_f32_s)
+  (export "foo" (func $main)))
@@ -3,31 +3,34 @@
 //! from the relevant options passed in the CLI.
 //! Like config-driven mode, it then passes the config to the driver, executes, and writes
 //! output to the specified file (or stdout, if no file is specified).
+//! The exit code is EXIT_FAILED if any module failed under the "error" failure policy.
+//! A machine-readable report of the results may be written alongside the output; it can only go to
+//! stdout if the output module does not.
+
+use log::{debug, error, info, log_enabled, warn, Level};
 
 use crate::config::ChiselConfig;
 use crate::config::FromArgs;
 use crate::driver::{ChiselDriver, DriverState};
-use crate::fail;
 use crate::logger;
 use crate::options::ChiselFlags;
+use crate::report;
+use crate::{fail, EXIT_FAILED, EXIT_SUCCESS};
 
 pub fn chisel_oneliner(flags: ChiselFlags) -> i32 {
-    let log_level = match flags.value_of("util.debugging") {
-        Some("true") => 1i32,
-        Some("false") => 0i32,
-        _ => panic!("util.debugging must be set 'true' or 'false'"),
-    };
-    logger::set_global_log_level(log_level);
+    if let Err(e) = logger::init_from_flags(&flags) {
+        fail(1, &format!("bad logging options: {}", e));
+    }
 
-    chisel_debug!(1, "Running chisel in oneliner (unix-style) mode");
+    debug!("Running chisel in oneliner (unix-style) mode");
 
     // If no modules were passed, just exit.
     match flags.value_of("oneliner.modules") {
         Some(module_list) => {
-            chisel_debug!(1, "Modules passed:\n\t{}", module_list);
+            debug!("Modules passed:\n\t{}", module_list);
 
             let options_list = if let Some(opts) = flags.value_of("oneliner.modules.options") {
-                chisel_debug!(1, "Module options passed:\n\t{}", opts);
+                debug!("Module options passed:\n\t{}", opts);
                 opts
             } else {
                 ""
@@ -59,9 +62,15 @@ pub fn chisel_oneliner(flags: ChiselFlags) -> i32 {
                 Err(e) => fail(1, &format!("Failed to load configuration: {}", e)),
             };
 
-            chisel_debug!(1, "{}", chisel_config);
+            debug!("{}", chisel_config);
 
             let mut driver = ChiselDriver::new(chisel_config);
+            driver.set_diff(flags.value_eq("output.diff", "true"));
+            // Translators only list their changes if they are shown or reported.
+            driver.set_changes(
+                flags.value_eq("output.diff", "true") || flags.value_of("report.format").is_some(),
+            );
+            driver.set_paranoid(flags.value_eq("run.paranoid", "true"));
 
             loop {
                 match driver.fire() {
@@ -74,37 +83,63 @@ pub fn chisel_oneliner(flags: ChiselFlags) -> i32 {
             }
 
             let mut results = driver.take_result();
-            // wish list: write yaml-encoded results to stdout
-            chisel_debug!(1, "Module execution completed successfully");
-            eprintln!("{}", &results);
+            debug!("Module execution completed successfully");
+            if log_enabled!(Level::Info) {
+                eprintln!("{}", &results);
+            }
+
+            let report_format = flags.value_of("report.format");
+            let report_path = flags.value_of("report.path");
 
             // Get ruleset
-            let results = results.rulesets_mut();
+            let result = results
+                .rulesets_mut()
+                .last_mut()
+                .expect("One ruleset was executed");
+            if report_format.is_some()
+                && report_path.is_none()
+                && result.mutated()
+                && result.output_path().as_path() == std::path::Path::new("/dev/stdout")
+            {
+                fail(
+                    1,
+                    "cannot write both the report and the output to stdout; use --output or --report-file",
+                );
+            }
+
+            result.set_force(flags.binary_allowed(result.output_path()));
             let io_result = match flags.value_of("output.mode") {
-                Some("bin") => {
-                    let mut result = results.pop().expect("One ruleset was executed");
-                    result.write("bin")
+                Some(mode) => result.write(mode),
+                None => panic!("Output mode is always set"),
+            };
+
+            match io_result {
+                Ok(true) => info!("Successfully wrote output to file."),
+                Ok(false) if result.withheld() => {
+                    warn!("A module failed; output not written.")
                 }
-                Some("wat") => {
-                    let mut result = results.pop().expect("One ruleset was executed");
-                    result.write("wat")
+                Ok(false) => info!("No changes to write."),
+                Err(ref e) => {
+                    error!("failed to write output to file: {}", e);
+                    result.set_error(format!("failed to write output: {}", e));
                 }
-                Some("hex") => {
-                    let mut result = results.pop().expect("One ruleset was executed");
-                    result.write("hex")
+            }
+
+            if let Some(format) = report_format {
+                if let Err(e) = report::write_report(&results, format, report_path) {
+                    fail(1, &format!("failed to write report: {}", e));
                 }
-                _ => panic!("CLI parser ensures value can only be one of the above"),
-            };
+            }
 
-            match io_result {
-                Ok(true) => eprintln!("Successfully wrote output to file."),
-                Ok(false) => eprintln!("No changes to write."),
-                Err(e) => fail(
-                    1,
-                    &format!("failed to write output to file: {}", e.description()),
-                ),
+            if io_result.is_err() {
+                fail(1, "failed to write output");
+            }
+
+            if results.passed() {
+                EXIT_SUCCESS
+            } else {
+                EXIT_FAILED
             }
-            0
         }
         None => fail(1, "no modules specified"),
     }
@@ -2,98 +2,233 @@
 //! The main entry point is chisel_run, which consumes the ChiselFlags generated by the caller.
 //! chisel_run finds the configuration file provided and produces a ChiselConfig, which is passed
 //! to the instantiated ChiselDriver and executed. Afterwards, results are written to the file
-//! specified in the configuration.
+//! specified in the configuration, and a report is written if one was requested.
+//! The exit code is EXIT_ERROR if any ruleset could not be executed, EXIT_FAILED if any module
+//! failed under the "error" failure policy, and EXIT_SUCCESS otherwise.
+//! In watch mode, chisel_run never returns. It polls the configuration file and the input files of
+//! each ruleset, reloading the configuration or re-executing the affected rulesets when they change.
 
-use std::error::Error;
-use std::fs::read_to_string;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{metadata, read_to_string};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
 
-use serde_yaml::Value;
+use log::{debug, error, info, log_enabled, trace, warn, Level};
 
-use crate::config::ChiselConfig;
-use crate::config::FromYaml;
+use crate::config::{ChiselConfig, Ruleset};
 use crate::driver::ChiselDriver;
 use crate::driver::DriverState;
-use crate::fail;
 use crate::logger;
 use crate::options::ChiselFlags;
+use crate::preprocess::Preprocessor;
+use crate::report;
+use crate::{fail, EXIT_ERROR, EXIT_FAILED, EXIT_SUCCESS};
 
 /// Execute chisel in config-driven mode.
 pub fn chisel_run(flags: ChiselFlags) -> i32 {
-    let log_level = match flags.value_of("util.debugging") {
-        Some("true") => 1i32,
-        Some("false") => 0i32,
-        _ => panic!("util.debugging must be set 'true' or 'false'"),
-    };
-    logger::set_global_log_level(log_level);
+    if let Err(e) = logger::init_from_flags(&flags) {
+        fail(1, &format!("bad logging options: {}", e));
+    }
 
-    chisel_debug!(1, "Running chisel in config-driven mode");
-    chisel_debug!(1, "Flags set:");
-    flags
-        .iter()
-        .for_each(|(k, v)| chisel_debug!(1, "\t{}: {}", k, v));
+    debug!("Running chisel in config-driven mode");
+    trace!("Flags set:");
+    flags.iter().for_each(|(k, v)| trace!("\t{}: {}", k, v));
 
     let config_path = flags
         .value_of("run.config.path")
         .expect("'run.config.path' is not set!");
 
-    chisel_debug!(1, "Resolving config at {}...", config_path);
+    let chisel_config = match load_config(&flags, config_path) {
+        Ok(config) => config,
+        Err(e) => fail(1, &e),
+    };
+
+    if flags.value_eq("run.watch", "true") {
+        watch(&flags, config_path, chisel_config)
+    } else {
+        match execute(&flags, chisel_config) {
+            Ok(code) => code,
+            Err(e) => fail(1, &e),
+        }
+    }
+}
+
+/// Interval between polls of the watched files in watch mode.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Modification times of the input files of each ruleset, by ruleset name.
+type Snapshot = HashMap<String, BTreeMap<PathBuf, Option<SystemTime>>>;
+
+/// Executes the configuration, then re-executes rulesets whenever their input files change, and
+/// the whole configuration whenever the configuration file changes. Errors which would end a
+/// single run are logged instead. Never returns.
+fn watch(flags: &ChiselFlags, config_path: &str, mut config: ChiselConfig) -> ! {
+    if let Err(e) = execute(flags, config.clone()) {
+        error!("{}; waiting for changes", e);
+    }
+
+    let mut config_modified = modified(Path::new(config_path));
+    let mut inputs = snapshot(&config);
+    info!("Watching for changes...");
+
+    loop {
+        sleep(WATCH_INTERVAL);
+
+        let rerun = if modified(Path::new(config_path)) != config_modified {
+            config_modified = modified(Path::new(config_path));
+            match load_config(flags, config_path) {
+                Ok(reloaded) => {
+                    info!("Configuration changed; re-running all rulesets");
+                    config = reloaded;
+                    Some(config.clone())
+                }
+                Err(e) => {
+                    error!("{}; waiting for changes", e);
+                    None
+                }
+            }
+        } else {
+            let current = snapshot(&config);
+            let mut affected = config.clone();
+            affected
+                .rulesets_mut()
+                .retain(|(name, _)| current.get(name) != inputs.get(name));
+            if affected.rulesets().is_empty() {
+                None
+            } else {
+                let names: Vec<&str> = affected
+                    .rulesets()
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+                info!("Input changed; re-running {}", names.join(", "));
+                Some(affected)
+            }
+        };
+
+        if let Some(rerun) = rerun {
+            if let Err(e) = execute(flags, rerun) {
+                error!("{}; waiting for changes", e);
+            }
+            // Snapshot after execution, so that outputs written over inputs are not picked up as
+            // changes.
+            inputs = snapshot(&config);
+            info!("Watching for changes...");
+        }
+    }
+}
+
+/// Returns the modification time of a file, or None if it cannot be read.
+fn modified(path: &Path) -> Option<SystemTime> {
+    metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Records the modification times of the input files of every ruleset. Glob patterns are expanded,
+/// so that added and removed files count as changes. Stdin cannot be watched and is left out.
+fn snapshot(config: &ChiselConfig) -> Snapshot {
+    config
+        .rulesets()
+        .iter()
+        .map(|(name, ruleset)| (name.clone(), input_files(ruleset)))
+        .collect()
+}
+
+fn input_files(ruleset: &Ruleset) -> BTreeMap<PathBuf, Option<SystemTime>> {
+    let pattern = match ruleset.options().get("file") {
+        Some(pattern) if pattern != "-" => pattern,
+        _ => return BTreeMap::new(),
+    };
+
+    match glob::glob(pattern) {
+        Ok(paths) => paths
+            .filter_map(|path| path.ok())
+            .map(|path| {
+                let modified = modified(&path);
+                (path, modified)
+            })
+            .collect(),
+        // An invalid pattern is reported by the driver; watch the path as written.
+        Err(_) => {
+            let path = PathBuf::from(pattern);
+            let modified = modified(&path);
+            vec![(path, modified)].into_iter().collect()
+        }
+    }
+}
+
+/// Loads, preprocesses and validates the configuration file.
+fn load_config(flags: &ChiselFlags, config_path: &str) -> Result<ChiselConfig, String> {
+    debug!("Resolving config at {}...", config_path);
 
     let path_resolved = match std::fs::canonicalize(config_path) {
         Ok(p) => p,
-        Err(e) => fail(
-            1,
-            &format!(
+        Err(e) => {
+            return Err(format!(
                 "could not resolve config path '{}': {}",
-                config_path,
-                e.description()
-            ),
-        ),
+                config_path, e
+            ))
+        }
     };
 
-    chisel_debug!(
-        1,
+    debug!(
         "Successfully resolved config path: {}",
         path_resolved.to_str().expect("Path is not valid unicode")
     );
-    chisel_debug!(1, "Loading configuration...");
+    debug!("Loading configuration...");
 
     let config = match read_to_string(path_resolved) {
         Ok(conf) => {
-            chisel_debug!(1, "Successfully loaded configuration");
+            debug!("Successfully loaded configuration");
             conf
         }
-        Err(e) => fail(
-            1,
-            &format!("failed to load configuration: {}", e.description()),
-        ),
+        Err(e) => return Err(format!("failed to load configuration: {}", e)),
     };
 
-    let yaml_parsed = serde_yaml::from_str::<Value>(&config).unwrap_or_else(|e| {
-        fail(
-            1,
-            &format!("failed to parse configuration: {}", e.description()),
-        )
-    });
+    // Resolve inheritance, profiles and variables, then validate the YAML configuration against
+    // the module schema.
+    let mut preprocessor = Preprocessor::default();
+    if let Some(profile) = flags.value_of("run.profile") {
+        debug!("Using profile {}", profile);
+        preprocessor.set_profile(profile);
+    }
+    flags
+        .iter()
+        .filter(|(k, _)| k.starts_with("run.set."))
+        .for_each(|(k, v)| preprocessor.set_variable(&k["run.set.".len()..], v));
 
-    // Validate basic properties of the YAML configuration.
-    let chisel_config = match ChiselConfig::from_yaml(&yaml_parsed) {
+    match ChiselConfig::from_str_with(&config, &preprocessor) {
         Ok(config) => {
-            chisel_debug!(1, "Successfully processed configuration");
-            config
+            debug!("Successfully processed configuration");
+            Ok(config)
         }
-        Err(e) => fail(1, &format!("bad configuration: {}", e)),
-    };
+        Err(e) => Err(format!("bad configuration: {}", e)),
+    }
+}
 
+/// Executes the rulesets of the configuration, writes their output and the report, and returns
+/// the exit code. Returns an error if a ruleset failed to execute under `--norecover`, or if the
+/// report could not be written.
+fn execute(flags: &ChiselFlags, chisel_config: ChiselConfig) -> Result<i32, String> {
     let mut driver = ChiselDriver::new(chisel_config);
+    driver.set_diff(flags.value_eq("output.diff", "true"));
+    // Translators only list their changes if they are shown or reported.
+    driver.set_changes(
+        flags.value_eq("output.diff", "true") || flags.value_of("report.format").is_some(),
+    );
+    driver.set_paranoid(flags.value_eq("run.paranoid", "true"));
+    if let Some(jobs) = flags.value_of("run.jobs") {
+        driver.set_jobs(jobs.parse().expect("CLI parser ensures a positive integer"));
+    }
 
     loop {
         match driver.fire() {
             DriverState::Error(err, _) => {
                 if flags.value_eq("util.norecover", "true") {
-                    fail(1, &format!("runtime error: {}", err));
+                    return Err(format!("runtime error: {}", err));
                 } else {
                     // Forget the current ruleset and keep going
-                    eprintln!("runtime error {}; skipping ruleset", err);
+                    warn!("runtime error {}; skipping ruleset", err);
                     continue;
                 }
             }
@@ -106,39 +241,94 @@ pub fn chisel_run(flags: ChiselFlags) -> i32 {
 
     // After execution, get results and write output.
     let mut results = driver.take_result();
-    chisel_debug!(1, "Module execution completed successfully");
-    eprintln!("{}", &results);
+    debug!("Module execution completed successfully");
+    if log_enabled!(Level::Info) {
+        eprintln!("{}", &results);
+    }
     results
         .rulesets_mut()
         .iter_mut()
         .map(|ruleset| {
             let ruleset_name = ruleset.name().to_string();
+            ruleset.set_force(flags.binary_allowed(ruleset.output_path()));
             let write_result = match flags.value_of("output.mode") {
-                Some("bin") => ruleset.write("bin"),
-                Some("wat") => ruleset.write("wat"),
-                Some("hex") => ruleset.write("hex"),
-                _ => panic!("CLI parser ensures value can only be one of the above"),
+                Some(mode) => ruleset.write(mode),
+                None => panic!("Output mode is always set"),
             };
-            (ruleset_name, write_result)
+            if let Err(e) = &write_result {
+                ruleset.set_error(format!("failed to write output: {}", e));
+            }
+            (ruleset_name, write_result, ruleset.withheld())
         })
-        .filter_map(|(name, result)| {
+        .filter_map(|(name, result, withheld)| {
             if result.is_err() {
                 Some((name, result.unwrap_err()))
             } else {
                 if result.expect("Errors sorted out already") {
-                    eprintln!("Ruleset {}: wrote output to file successfully.", name);
+                    info!("Ruleset {}: wrote output to file successfully.", name);
+                } else if withheld {
+                    warn!("Ruleset {}: a module failed; output not written.", name);
                 } else {
-                    eprintln!("Ruleset {}: no changes made to be written.", name);
+                    info!("Ruleset {}: no changes made to be written.", name);
                 }
                 None
             }
         })
         .for_each(|(name, error)| {
-            eprintln!(
+            error!(
                 "failed to write output from ruleset {} to file: {}",
-                name,
-                error.description()
+                name, error
             )
         });
-    0
+
+    if let Some(format) = flags.value_of("report.format") {
+        if let Err(e) = report::write_report(&results, format, flags.value_of("report.path")) {
+            return Err(format!("failed to write report: {}", e));
+        }
+    }
+
+    if results.has_errors() {
+        Ok(EXIT_ERROR)
+    } else if !results.passed() {
+        Ok(EXIT_FAILED)
+    } else {
+        Ok(EXIT_SUCCESS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_inputs() {
+        let config = "\
+single:
+  file: \"./res/test/empty.wasm\"
+  verifyimports:
+    preset: \"ewasm\"
+pattern:
+  file: \"./res/test/*.wasm\"
+  verifyimports:
+    preset: \"ewasm\"
+missing:
+  file: \"./res/test/missing.wasm\"
+  verifyimports:
+    preset: \"ewasm\"
+stdin:
+  file: \"-\"
+  verifyimports:
+    preset: \"ewasm\"
+"
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let snapshot = snapshot(&config);
+        assert_eq!(snapshot.len(), 4);
+        assert!(snapshot["single"][Path::new("res/test/empty.wasm")].is_some());
+        assert_eq!(snapshot["pattern"].len(), 1);
+        assert!(snapshot["pattern"][Path::new("res/test/empty.wasm")].is_some());
+        assert!(snapshot["missing"].is_empty());
+        assert!(snapshot["stdin"].is_empty());
+    }
 }
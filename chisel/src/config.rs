@@ -6,17 +6,29 @@
 //! A ChiselConfig contains a list of ruleset configurations and their names.
 //! A ruleset configuration contains a set of top-level options and a set of module configurations to execute.
 //! The aforementioned traits are used to generate ChiselConfigs.
+//! YAML configurations are validated strictly against the schema in the schema module. Errors
+//! name the ruleset and key path at which they occurred, and when parsed from source through
+//! FromStr, the line and column of the offending key. Inheritance, profiles and variables are
+//! resolved beforehand by the preprocess module.
 
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::fmt::{self, Display};
+use std::str::FromStr;
 
+use log::debug;
+use serde_derive::Serialize;
 use serde_yaml::Value;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+use crate::preprocess::Preprocessor;
+use crate::schema;
 
 /// Main trait for generating a configuration usable by the driver from deserialized YAML.
 pub trait FromYaml {
     // yaml: top-level yaml element generated by parser
-    fn from_yaml(yaml: &Value) -> Result<Self, String>
+    fn from_yaml(yaml: &Value) -> Result<Self, ConfigError>
     where
         Self: Sized;
 }
@@ -34,11 +46,11 @@ pub trait FromArgs {
 /// A list of rulesets for a single chisel run.
 /// Left-hand value: name of ruleset
 /// Right-hand value: ruleset configuration
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ChiselConfig(VecDeque<(String, Ruleset)>);
 
 /// A single ruleset, containing options and a set of module configurations.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Ruleset {
     /// Top-level ruleset configurations, such as the input/output file paths.
     options: HashMap<String, String>,
@@ -49,9 +61,33 @@ pub struct Ruleset {
 }
 
 /// A key-value module configuration.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ModuleConfig(HashMap<String, String>);
 
+/// How the failure of a module affects the outcome of a run. A module fails when a validator
+/// reports the module invalid, a creator fails, or any module returns an error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// The failure fails the run. This is the default.
+    #[default]
+    Error,
+    /// The failure is reported as a warning, but does not fail the run.
+    Warn,
+    /// The failure is reported, but otherwise ignored.
+    Ignore,
+}
+
+/// An error in a YAML configuration.
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    /// Keys leading to the offending value. The first key is the ruleset name.
+    path: Vec<String>,
+    message: String,
+    /// Line and column of the offending key in the source, if known.
+    location: Option<(usize, usize)>,
+}
+
 impl ChiselConfig {
     pub fn rulesets(&self) -> &VecDeque<(String, Ruleset)> {
         &self.0
@@ -84,6 +120,31 @@ impl ModuleConfig {
     pub fn options(&self) -> &HashMap<String, String> {
         &self.0
     }
+
+    /// Removes the failure policy from the module options and returns it, so that the remaining
+    /// options can be passed to the module itself.
+    pub fn take_policy(&mut self) -> Result<FailurePolicy, String> {
+        match self.0.remove(schema::ON_FAIL) {
+            Some(policy) => policy.parse(),
+            None => Ok(FailurePolicy::default()),
+        }
+    }
+}
+
+impl FromStr for FailurePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(FailurePolicy::Error),
+            "warn" => Ok(FailurePolicy::Warn),
+            "ignore" => Ok(FailurePolicy::Ignore),
+            other => Err(format!(
+                "expected one of 'error', 'warn' or 'ignore', found '{}'",
+                other
+            )),
+        }
+    }
 }
 
 impl FromArgs for ChiselConfig {
@@ -166,12 +227,9 @@ impl FromArgs for Ruleset {
                 let keyval_pair: Vec<&str> = keyval_pair.split('=').collect();
 
                 if keyval_pair.len() == 2 {
-                    chisel_debug!(
-                        1,
+                    debug!(
                         "Found option for module {}: {} = {}",
-                        name,
-                        &keyval_pair[0],
-                        &keyval_pair[1]
+                        name, &keyval_pair[0], &keyval_pair[1]
                     );
                     options_final.insert(keyval_pair[0].to_string(), keyval_pair[1].to_string());
                 } else {
@@ -196,141 +254,464 @@ impl FromArgs for Ruleset {
     }
 }
 
-impl FromYaml for ChiselConfig {
-    fn from_yaml(yaml: &Value) -> Result<Self, String>
-    where
-        Self: Sized,
-    {
-        // Ensure that the first value in the config is a mapping representing rulesets.
-        if !yaml.is_mapping() {
-            return Err("Top-level value is not a mapping".to_string());
-        }
+impl ChiselConfig {
+    /// Parses a YAML configuration, resolving inheritance, profiles and variables with the given
+    /// preprocessor. Errors carry the line and column of the offending key.
+    pub fn from_str_with(source: &str, preprocessor: &Preprocessor) -> Result<Self, ConfigError> {
+        let yaml = serde_yaml::from_str::<Value>(source)
+            .map_err(|e| ConfigError::new(format!("failed to parse configuration: {}", e)))?;
 
-        let rulesets = yaml.as_mapping().expect("Already validated");
+        ChiselConfig::from_yaml_with(&yaml, preprocessor).map_err(|e| {
+            let location = KeyLocations::scan(source).find(&e.path);
+            e.with_location(location)
+        })
+    }
 
-        // Ensure that all rulesets in the top-level YAML map are string-map pairs.
+    /// Generates a configuration from deserialized YAML, resolving inheritance, profiles and
+    /// variables with the given preprocessor.
+    pub fn from_yaml_with(yaml: &Value, preprocessor: &Preprocessor) -> Result<Self, ConfigError> {
+        // Ensure that the first value in the config is a mapping representing rulesets.
+        let rulesets = match yaml {
+            Value::Mapping(rulesets) => rulesets,
+            other => {
+                return Err(ConfigError::new(format!(
+                    "expected top-level value to be a mapping of rulesets, found {}",
+                    schema::type_name(other)
+                )))
+            }
+        };
+
+        // The preprocessor ensures that all rulesets in the top-level YAML map are string-map
+        // pairs.
         // Valid example:
         // ruleset:
         //   option: value
         //
         // Invalid example:
         // ruleset: "something"
-        if rulesets
-            .iter()
-            .any(|(key, val)| !key.is_string() || !val.is_mapping())
-        {
-            return Err(
-                "Malformed ruleset; expected all rulesets to be string-map pairs".to_string(),
-            );
+        let mut ret = VecDeque::new();
+        for (name, val) in preprocessor.apply(rulesets)? {
+            let ruleset = Ruleset::from_yaml(&val).map_err(|e| e.within(&name))?;
+            ret.push_back((name, ruleset));
         }
 
-        let rulesets: VecDeque<(String, Ruleset)> = rulesets
-            .iter()
-            .map(|(key, val)| {
-                (
-                    key.as_str().expect("Already validated").to_string(),
-                    Ruleset::from_yaml(val).expect("No failure cases yet"),
-                )
-            })
-            .collect();
+        Ok(ChiselConfig(ret))
+    }
+}
+
+impl FromYaml for ChiselConfig {
+    fn from_yaml(yaml: &Value) -> Result<Self, ConfigError>
+    where
+        Self: Sized,
+    {
+        ChiselConfig::from_yaml_with(yaml, &Preprocessor::default())
+    }
+}
+
+impl FromStr for ChiselConfig {
+    type Err = ConfigError;
 
-        Ok(ChiselConfig(rulesets))
+    /// Parses a YAML configuration, attaching the line and column of the offending key to any
+    /// error.
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        ChiselConfig::from_str_with(source, &Preprocessor::default())
     }
 }
 
 impl FromYaml for Ruleset {
-    fn from_yaml(yaml: &Value) -> Result<Self, String>
+    fn from_yaml(yaml: &Value) -> Result<Self, ConfigError>
     where
         Self: Sized,
     {
         let mut options: HashMap<String, String> = HashMap::new();
         let mut modules: VecDeque<(String, ModuleConfig)> = VecDeque::new();
+        // Whether the modules were given as a "modules" sequence rather than as ruleset keys.
+        let mut module_list = false;
+
+        let ruleset = match yaml {
+            Value::Mapping(ruleset) => ruleset,
+            other => {
+                return Err(ConfigError::new(format!(
+                    "expected ruleset to be a mapping of options and modules, found {}",
+                    schema::type_name(other)
+                )))
+            }
+        };
 
-        let ruleset = yaml.as_mapping().expect("Already validated");
         for (key, val) in ruleset.iter() {
-            match (key, val) {
-                // If string-string pair, treat it as an option.
-                (Value::String(k), Value::String(v)) => {
-                    options.insert(k.to_string(), v.to_string());
+            let key = match key {
+                Value::String(key) => key,
+                other => {
+                    return Err(ConfigError::new(format!(
+                        "expected key to be a string, found {}",
+                        schema::type_name(other)
+                    )))
                 }
-                // If string-map pair or a string-null pair (no special options), treat it as a module configuration.
-                (Value::String(k), v @ Value::Mapping(_)) | (Value::String(k), v @ Value::Null) => {
-                    modules.push_back((
-                        k.to_string(),
-                        ModuleConfig::from_yaml(v).expect("No failure cases yet"),
-                    ));
+            };
+
+            if key == schema::MODULE_LIST {
+                // Ordered list of modules, which may contain the same module more than once.
+                if !modules.is_empty() {
+                    return Err(mixed_module_forms().within(key));
+                }
+                modules = module_list_from_yaml(val).map_err(|e| e.within(key))?;
+                module_list = true;
+            } else if let Some(ty) = schema::ruleset_option(key) {
+                // Top-level options, such as the input and output paths.
+                schema::check_value(ty, val).map_err(|e| e.within(key))?;
+                options.insert(
+                    key.to_string(),
+                    option_to_string(val).map_err(|e| e.within(key))?,
+                );
+            } else {
+                // Anything else must be a module configuration. A null value means the module
+                // takes no options.
+                schema::check_module(key, val).map_err(|e| e.within(key))?;
+                if module_list {
+                    return Err(mixed_module_forms().within(key));
                 }
-                // ignore others for now
-                (_, _) => (),
+                modules.push_back((
+                    key.to_string(),
+                    ModuleConfig::from_yaml(val).map_err(|e| e.within(key))?,
+                ));
             }
         }
         Ok(Ruleset { options, modules })
     }
 }
 
+fn mixed_module_forms() -> ConfigError {
+    ConfigError::new(format!(
+        "modules must be given either as ruleset keys or in '{}', not both",
+        schema::MODULE_LIST
+    ))
+}
+
+/// Reads the sequence form of a module list, in which each element is either a module name or a
+/// mapping of a single module name to its options:
+///
+/// ```yaml
+/// modules:
+///   - remapimports:
+///       preset: "ewasm"
+///   - verifyimports:
+///       preset: "ewasm"
+///   - snip
+///   - verifyimports:
+///       preset: "ewasm"
+/// ```
+///
+/// Modules are executed in the order in which they are listed.
+fn module_list_from_yaml(yaml: &Value) -> Result<VecDeque<(String, ModuleConfig)>, ConfigError> {
+    let elements = match yaml {
+        Value::Sequence(elements) => elements,
+        other => {
+            return Err(ConfigError::new(format!(
+                "expected a sequence of modules, found {}",
+                schema::type_name(other)
+            )))
+        }
+    };
+
+    let mut modules = VecDeque::new();
+    for (idx, element) in elements.iter().enumerate() {
+        let (name, config) = match element {
+            Value::String(name) => (name, &Value::Null),
+            Value::Mapping(mapping) if mapping.len() == 1 => {
+                match mapping.iter().next().expect("Length checked") {
+                    (Value::String(name), config) => (name, config),
+                    (other, _) => {
+                        return Err(ConfigError::new(format!(
+                            "expected module name to be a string, found {}",
+                            schema::type_name(other)
+                        ))
+                        .within(&format!("[{}]", idx)))
+                    }
+                }
+            }
+            Value::Mapping(_) => {
+                return Err(ConfigError::new(
+                    "expected a mapping of exactly one module name to its options",
+                )
+                .within(&format!("[{}]", idx)))
+            }
+            other => {
+                return Err(ConfigError::new(format!(
+                    "expected a module name or a mapping of a module name to its options, found {}",
+                    schema::type_name(other)
+                ))
+                .within(&format!("[{}]", idx)))
+            }
+        };
+
+        schema::check_module(name, config)
+            .map_err(|e| e.within(name).within(&format!("[{}]", idx)))?;
+        let config = ModuleConfig::from_yaml(config)
+            .map_err(|e| e.within(name).within(&format!("[{}]", idx)))?;
+        modules.push_back((name.to_string(), config));
+    }
+
+    Ok(modules)
+}
+
 impl FromYaml for ModuleConfig {
-    fn from_yaml(yaml: &Value) -> Result<Self, String>
+    fn from_yaml(yaml: &Value) -> Result<Self, ConfigError>
     where
         Self: Sized,
     {
-        if yaml == &Value::Null {
-            Ok(ModuleConfig(HashMap::new()))
-        } else {
-            let options = yaml.as_mapping().expect("Already validated");
-            let options: HashMap<String, String> = options
-                .iter()
-                .filter_map(|(key, val)| {
-                    match (key, val) {
-                        (Value::String(k), Value::String(v)) => {
-                            Some((k.to_string(), v.to_string()))
-                        }
-                        (Value::String(k), Value::Bool(v)) => Some((
-                            k.to_string(),
-                            if *v {
-                                "true".to_string()
-                            } else {
-                                "false".to_string()
-                            },
-                        )),
-                        (Value::String(k), Value::Number(n)) => {
-                            // Do not support floating point right now
-                            if n.is_u64() || n.is_i64() {
-                                Some((k.to_string(), n.to_string()))
-                            } else {
-                                None
-                            }
-                        }
-                        (Value::String(k), Value::Sequence(v)) => {
-                            // Ignore non-string/int/bools
-                            // This will produce a comma-separated list of sequence elements.
-                            Some((
-                                k.to_string(),
-                                v.iter()
-                                    .filter_map(|elem| match elem {
-                                        Value::String(s) => Some(s.to_string()),
-                                        Value::Bool(v) => Some(if *v {
-                                            "true".to_string()
-                                        } else {
-                                            "false".to_string()
-                                        }),
-                                        Value::Number(n) => {
-                                            if n.is_u64() || n.is_i64() {
-                                                Some(n.to_string())
-                                            } else {
-                                                None
-                                            }
-                                        }
-                                        _ => None,
-                                    })
-                                    .fold(String::new(), |acc, elem| acc + &elem),
-                            ))
-                        }
-                        _ => None,
+        let options = match yaml {
+            Value::Null => return Ok(ModuleConfig(HashMap::new())),
+            Value::Mapping(options) => options,
+            other => {
+                return Err(ConfigError::new(format!(
+                    "expected a mapping of module options, found {}",
+                    schema::type_name(other)
+                )))
+            }
+        };
+
+        let mut ret = HashMap::new();
+        for (key, val) in options.iter() {
+            let key = match key {
+                Value::String(key) => key,
+                other => {
+                    return Err(ConfigError::new(format!(
+                        "expected option name to be a string, found {}",
+                        schema::type_name(other)
+                    )))
+                }
+            };
+            ret.insert(
+                key.to_string(),
+                option_to_string(val).map_err(|e| e.within(key))?,
+            );
+        }
+
+        Ok(ModuleConfig(ret))
+    }
+}
+
+/// Converts an option value to its string representation.
+/// Sequences produce a comma-separated list of their elements. Floats and nested mappings are not
+/// supported.
+fn option_to_string(value: &Value) -> Result<String, ConfigError> {
+    match value {
+        Value::String(s) => Ok(s.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Number(n) if n.is_u64() || n.is_i64() => Ok(n.to_string()),
+        Value::Sequence(elements) => {
+            let mut list = Vec::new();
+            for (idx, element) in elements.iter().enumerate() {
+                match element {
+                    Value::Sequence(_) => {
+                        return Err(ConfigError::new("nested sequences are not supported")
+                            .within(&format!("[{}]", idx)))
                     }
-                })
-                .collect();
+                    element => list.push(
+                        option_to_string(element).map_err(|e| e.within(&format!("[{}]", idx)))?,
+                    ),
+                }
+            }
+            Ok(list.join(","))
+        }
+        other => Err(ConfigError::new(format!(
+            "unsupported value of type {}",
+            schema::type_name(other)
+        ))),
+    }
+}
+
+impl ConfigError {
+    pub fn new<T: Into<String>>(message: T) -> Self {
+        ConfigError {
+            path: Vec::new(),
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    /// Prepends a key to the path of the error. Used while the error propagates outward.
+    pub fn within(mut self, key: &str) -> Self {
+        self.path.insert(0, key.to_string());
+        self
+    }
+
+    pub fn with_location(mut self, location: Option<(usize, usize)>) -> Self {
+        self.location = location;
+        self
+    }
+
+    /// The name of the ruleset in which the error occurred, if any.
+    pub fn ruleset(&self) -> Option<&str> {
+        self.path.first().map(|s| s.as_str())
+    }
+
+    #[cfg(test)]
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    #[cfg(test)]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Line and column (both starting at 1) of the offending key, if known.
+    #[cfg(test)]
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.location
+    }
+}
 
-            Ok(ModuleConfig(options))
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ruleset) = self.ruleset() {
+            write!(f, "in ruleset '{}'", ruleset)?;
+            if self.path.len() > 1 {
+                // Sequence indices are written as "key[0]", other keys are separated by dots.
+                let key = self.path[1..].iter().fold(String::new(), |mut acc, key| {
+                    if !acc.is_empty() && !key.starts_with('[') {
+                        acc.push('.');
+                    }
+                    acc.push_str(key);
+                    acc
+                });
+                write!(f, " at '{}'", key)?;
+            }
+            if let Some((line, column)) = self.location {
+                write!(f, " (line {}, column {})", line, column)?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Positions of the keys and sequence elements in a YAML document, indexed by their key path.
+/// serde_yaml only reports positions for syntax errors, and its `Value` keeps none, so the
+/// document is scanned again with yaml-rust, the parser serde_yaml itself is built on. The scan
+/// only happens once a configuration error needs a location.
+struct KeyLocations(HashMap<Vec<String>, (usize, usize)>);
+
+impl KeyLocations {
+    fn scan(source: &str) -> Self {
+        let mut collector = LocationCollector {
+            stack: Vec::new(),
+            path: Vec::new(),
+            skip: 0,
+            locations: HashMap::new(),
+        };
+        // Syntax errors are already reported by serde_yaml, so a failed scan just leaves us with
+        // fewer locations.
+        let _ = Parser::new(source.chars()).load(&mut collector, false);
+        KeyLocations(collector.locations)
+    }
+
+    /// Finds the location of a path, falling back to its closest parent that has one.
+    fn find(&self, path: &[String]) -> Option<(usize, usize)> {
+        (0..=path.len())
+            .rev()
+            .find_map(|len| self.0.get(&path[..len]).cloned())
+    }
+}
+
+/// A container being read by LocationCollector.
+enum Frame {
+    /// A mapping, holding the key whose value is being read, if any.
+    Mapping(Option<String>),
+    /// A sequence, holding the index of the element being read.
+    Sequence(usize),
+}
+
+/// Receives YAML parser events and records the position of every key.
+struct LocationCollector {
+    stack: Vec<Frame>,
+    path: Vec<String>,
+    /// Depth of a complex (non-scalar) mapping key being skipped.
+    skip: usize,
+    locations: HashMap<Vec<String>, (usize, usize)>,
+}
+
+impl LocationCollector {
+    /// Path component of the node being read in the current container.
+    fn component(&self) -> Option<String> {
+        match self.stack.last() {
+            Some(Frame::Mapping(Some(key))) => Some(key.to_string()),
+            Some(Frame::Sequence(idx)) => Some(format!("[{}]", idx)),
+            _ => None,
+        }
+    }
+
+    /// Records the position of the node being read, unless a key has already been recorded there.
+    fn record(&mut self, component: &str, mark: Marker) {
+        let mut path = self.path.clone();
+        path.push(component.to_string());
+        self.locations
+            .entry(path)
+            .or_insert((mark.line(), mark.col() + 1));
+    }
+
+    /// Moves on to the next key or element after a node was read.
+    fn advance(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Mapping(key)) => *key = None,
+            Some(Frame::Sequence(idx)) => *idx += 1,
+            None => (),
+        }
+    }
+}
+
+impl MarkedEventReceiver for LocationCollector {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        if self.skip > 0 {
+            match event {
+                Event::MappingStart(_) | Event::SequenceStart(_) => self.skip += 1,
+                Event::MappingEnd | Event::SequenceEnd => self.skip -= 1,
+                _ => (),
+            }
+            return;
+        }
+
+        let awaiting_key = matches!(self.stack.last(), Some(Frame::Mapping(None)));
+
+        match event {
+            Event::Scalar(ref value, ..) if awaiting_key => {
+                self.record(value, mark);
+                if let Some(frame) = self.stack.last_mut() {
+                    *frame = Frame::Mapping(Some(value.clone()));
+                }
+            }
+            Event::MappingStart(_) | Event::SequenceStart(_) if awaiting_key => {
+                // Complex keys never name anything chisel knows about, so skip over them.
+                if let Some(frame) = self.stack.last_mut() {
+                    *frame = Frame::Mapping(Some("?".to_string()));
+                }
+                self.skip = 1;
+            }
+            Event::Scalar(..) | Event::Alias(_) => {
+                if let Some(component) = self.component() {
+                    self.record(&component, mark);
+                }
+                self.advance();
+            }
+            Event::MappingStart(_) | Event::SequenceStart(_) => {
+                if let Some(component) = self.component() {
+                    self.record(&component, mark);
+                    self.path.push(component);
+                }
+                self.stack.push(match event {
+                    Event::MappingStart(_) => Frame::Mapping(None),
+                    _ => Frame::Sequence(0),
+                });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                if !self.stack.is_empty() {
+                    self.path.pop();
+                    self.advance();
+                }
+            }
+            _ => (),
         }
     }
 }
@@ -398,7 +779,7 @@ mod tests {
     #[test]
     fn from_yaml() {
         let yaml = {
-            // test:
+            // verifyimports:
             //   preset: "ewasm"
             let mut test_module = Mapping::new();
             test_module.insert(
@@ -415,7 +796,7 @@ mod tests {
                 Value::String("file".to_string()),
                 Value::String("test.yaml".to_string()),
             );
-            test_config.insert(Value::String("test".to_string()), test_module);
+            test_config.insert(Value::String("verifyimports".to_string()), test_module);
             let test_config = Value::Mapping(test_config);
 
             let mut ruleset = Mapping::new();
@@ -567,4 +948,205 @@ mod tests {
         let config = ChiselConfig::from_yaml(&yaml);
         assert!(config.is_err());
     }
+
+    #[test]
+    fn yaml_from_str() {
+        let config = "\
+ewasm:
+  file: \"test.wasm\"
+  remapimports:
+    preset: [\"ewasm\", \"debug\"]
+  checkstartfunc:
+    require_start: false
+  checkfloat:
+";
+        let config = config.parse::<ChiselConfig>().expect("Should be valid");
+        let ruleset = &config.rulesets()[0].1;
+        assert_eq!(ruleset.modules().len(), 3);
+        assert_eq!(
+            ruleset.modules()[0].1.options().get("preset"),
+            Some(&"ewasm,debug".to_string())
+        );
+        assert_eq!(
+            ruleset.modules()[1].1.options().get("require_start"),
+            Some(&"false".to_string())
+        );
+        assert_eq!(ruleset.modules()[2].1.options().len(), 0);
+    }
+
+    #[test]
+    fn yaml_unknown_module() {
+        let config = "\
+ewasm:
+  file: \"test.wasm\"
+  verifyimport:
+    preset: \"ewasm\"
+";
+        let err = config.parse::<ChiselConfig>().unwrap_err();
+        assert_eq!(err.ruleset(), Some("ewasm"));
+        assert_eq!(
+            err.path(),
+            &["ewasm".to_string(), "verifyimport".to_string()]
+        );
+        assert_eq!(err.location(), Some((3, 3)));
+        assert_eq!(
+            err.to_string(),
+            "in ruleset 'ewasm' at 'verifyimport' (line 3, column 3): \
+             unknown module or option 'verifyimport'; did you mean 'verifyimports'?"
+        );
+    }
+
+    #[test]
+    fn yaml_unknown_option() {
+        let config = "\
+ewasm:
+  file: \"test.wasm\"
+  verifyimports:
+    prest: \"ewasm\"
+";
+        let err = config.parse::<ChiselConfig>().unwrap_err();
+        assert_eq!(err.location(), Some((4, 5)));
+        assert_eq!(
+            err.to_string(),
+            "in ruleset 'ewasm' at 'verifyimports.prest' (line 4, column 5): \
+             unknown option for 'verifyimports' 'prest'; did you mean 'preset'?"
+        );
+    }
+
+    #[test]
+    fn yaml_wrong_types() {
+        // Float.
+        let err = "a:\n  file: 1.5\n".parse::<ChiselConfig>().unwrap_err();
+        assert_eq!(err.message(), "expected string, found float");
+        assert_eq!(err.location(), Some((2, 3)));
+
+        // Nested mapping.
+        let err = "a:\n  trimexports:\n    preset:\n      foo: bar\n"
+            .parse::<ChiselConfig>()
+            .unwrap_err();
+        assert_eq!(err.message(), "expected string, found mapping");
+        assert_eq!(err.location(), Some((3, 5)));
+
+        // Wrong type within a sequence.
+        let err = "a:\n  remapimports:\n    preset:\n      - ewasm\n      - 3\n"
+            .parse::<ChiselConfig>()
+            .unwrap_err();
+        assert_eq!(
+            err.path(),
+            &[
+                "a".to_string(),
+                "remapimports".to_string(),
+                "preset".to_string(),
+                "[1]".to_string()
+            ]
+        );
+        assert_eq!(err.location(), Some((5, 9)));
+        assert!(err
+            .to_string()
+            .starts_with("in ruleset 'a' at 'remapimports.preset[1]'"));
+
+        // Boolean option given a string.
+        let err = "a:\n  checkstartfunc:\n    require_start: yes please\n"
+            .parse::<ChiselConfig>()
+            .unwrap_err();
+        assert_eq!(err.message(), "expected boolean, found string");
+    }
+
+    #[test]
+    fn yaml_syntax_error() {
+        let err = "a:\n  file: [\n".parse::<ChiselConfig>().unwrap_err();
+        assert!(err.message().starts_with("failed to parse configuration"));
+        assert_eq!(err.ruleset(), None);
+    }
+
+    #[test]
+    fn yaml_module_list() {
+        let config = "\
+ewasm:
+  file: \"test.wasm\"
+  modules:
+    - remapimports:
+        preset: \"ewasm\"
+    - verifyimports:
+        preset: \"ewasm\"
+    - snip
+    - dropsection:
+        custom_by_name: \"producers\"
+    - dropsection:
+        custom_by_index: 2
+    - verifyimports:
+        preset: \"ewasm\"
+";
+        let config = config.parse::<ChiselConfig>().expect("Should be valid");
+        let ruleset = &config.rulesets()[0].1;
+        assert_eq!(
+            ruleset.options().get("file"),
+            Some(&"test.wasm".to_string())
+        );
+
+        let names: Vec<&str> = ruleset
+            .modules()
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "remapimports",
+                "verifyimports",
+                "snip",
+                "dropsection",
+                "dropsection",
+                "verifyimports"
+            ]
+        );
+        assert_eq!(
+            ruleset.modules()[3].1.options().get("custom_by_name"),
+            Some(&"producers".to_string())
+        );
+        assert_eq!(
+            ruleset.modules()[4].1.options().get("custom_by_index"),
+            Some(&"2".to_string())
+        );
+    }
+
+    #[test]
+    fn yaml_module_list_errors() {
+        // Unknown option within a list element.
+        let err = "a:\n  modules:\n    - snip\n    - dropsection:\n        custom_by_nam: x\n"
+            .parse::<ChiselConfig>()
+            .unwrap_err();
+        assert_eq!(err.location(), Some((5, 9)));
+        assert!(err
+            .to_string()
+            .starts_with("in ruleset 'a' at 'modules[1].dropsection.custom_by_nam'"));
+
+        // More than one module in an element.
+        let err = "a:\n  modules:\n    - snip:\n      repack:\n"
+            .parse::<ChiselConfig>()
+            .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "expected a mapping of exactly one module name to its options"
+        );
+
+        // Not a sequence.
+        let err = "a:\n  modules:\n    snip:\n"
+            .parse::<ChiselConfig>()
+            .unwrap_err();
+        assert_eq!(
+            err.message(),
+            "expected a sequence of modules, found mapping"
+        );
+
+        // Both forms in one ruleset.
+        let err = "a:\n  snip:\n  modules:\n    - repack\n"
+            .parse::<ChiselConfig>()
+            .unwrap_err();
+        assert_eq!(err.location(), Some((3, 3)));
+        let err = "a:\n  modules:\n    - repack\n  snip:\n"
+            .parse::<ChiselConfig>()
+            .unwrap_err();
+        assert_eq!(err.location(), Some((4, 3)));
+    }
 }
@@ -5,30 +5,70 @@
 //! If it enters an error state, it is up to the caller to handle it. If called again, the ruleset
 //! in which the error occurred is dropped.
 //! Upon completed execution, the driver returns a ChiselResult structure.
+//! A ruleset whose input file is a glob pattern is expanded into one execution per matched file,
+//! and its output path may be a template such as "out/{stem}.chisel.wasm".
+//! An input file of "-" reads the module from stdin. Inputs may be binary, text, hex or JSON.
 
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt::{self, Display};
 use std::fs::{canonicalize, read};
-use std::path::PathBuf;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
+#[cfg(feature = "binaryen")]
+use libchisel::batch::BinaryenTranslator;
 #[cfg(feature = "binaryen")]
 use libchisel::binaryenopt::BinaryenOptimiser;
 use libchisel::{
-    checkfloat::CheckFloat, checkstartfunc::CheckStartFunc, deployer::Deployer,
-    dropsection::DropSection, remapimports::RemapImports, remapstart::RemapStart, repack::Repack,
-    snip::Snip, trimexports::TrimExports, trimstartfunc::TrimStartFunc,
-    verifyexports::VerifyExports, verifyimports::VerifyImports, ChiselModule, Module, ModulePreset,
-    ModuleTranslator, ModuleValidator,
+    batch::{self, WalrusTranslator},
+    canonicalizenan::CanonicalizeNan,
+    checkfloat::CheckFloat,
+    checkinstructions::CheckInstructions,
+    checklimits::CheckLimits,
+    checksize::CheckSize,
+    checkstartfunc::CheckStartFunc,
+    deployer::Deployer,
+    diff::Changes,
+    dropsection::DropSection,
+    proposals,
+    remapimports::RemapImports,
+    remapstart::RemapStart,
+    repack::Repack,
+    snip::Snip,
+    softfloat::SoftFloat,
+    trimexports::TrimExports,
+    trimstartfunc::TrimStartFunc,
+    validate::Validate,
+    verifyexports::VerifyExports,
+    verifyimports::VerifyImports,
+    ChiselModule, Module, ModuleError, ModulePreset, ModuleTranslator, ModuleValidator,
 };
+use log::debug;
 
-use crate::config::{ChiselConfig, ModuleConfig};
-use crate::result::{ChiselResult, ModuleResult, RulesetResult};
+use crate::config::{ChiselConfig, FailurePolicy, ModuleConfig, Ruleset};
+use crate::input;
+use crate::result::{error_chain, ChiselResult, ModuleResult, RulesetResult};
 
 /// State machine implementing the main chisel execution loop. Consumes ChiselConfig and returns
 /// ChiselResult, with an intermediate state returned to allow error handling.
 pub struct ChiselDriver {
     config: ChiselConfig,
     state: DriverState,
+    /// Rulesets expanded from the configuration, one per input file, awaiting execution.
+    expanded: VecDeque<(String, Ruleset)>,
+    /// Maximum number of rulesets executed in parallel.
+    jobs: usize,
+    /// Outcomes of rulesets executed in parallel, in configuration order, awaiting collection.
+    executed: VecDeque<(RulesetResult, Result<(), DriverError>)>,
+    /// If set, the differences between the input and output module of each ruleset are recorded.
+    diff: bool,
+    /// If set, translators list the changes they make to the module.
+    changes: bool,
+    /// If set, the module is validated after every translator or creator which changed it.
+    paranoid: bool,
 }
 
 /// The state of the chisel driver.
@@ -50,9 +90,15 @@ pub enum DriverError {
     /// A canonicalized path was generated unsuccessfully. Left-hand is the config object name,
     /// right-hand is the invalid path.
     PathResolution(String, String),
+    /// A glob pattern matched no files. Left-hand is the config object name, right-hand is the
+    /// pattern.
+    NoMatches(String, String),
     /// An internal error occurred. Field 0 is the config object, during the execution of which the error occurred.
     /// Field 1 is an additional informational message. Field 2 is the error generated.
-    Internal(String, String, Box<dyn Error>),
+    Internal(String, String, Box<dyn Error + Send + Sync>),
+    /// A module produced an invalid module. Left-hand is the module name, right-hand is the
+    /// validation error.
+    InvalidOutput(String, String),
 }
 
 impl ChiselDriver {
@@ -60,9 +106,38 @@ impl ChiselDriver {
         ChiselDriver {
             config,
             state: DriverState::Ready,
+            expanded: VecDeque::new(),
+            jobs: 1,
+            executed: VecDeque::new(),
+            diff: false,
+            changes: false,
+            paranoid: false,
         }
     }
 
+    /// Sets the maximum number of rulesets executed in parallel. Results are collected in
+    /// configuration order regardless.
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs.max(1);
+    }
+
+    /// Enables recording the differences between the input and output module of each ruleset.
+    pub fn set_diff(&mut self, diff: bool) {
+        self.diff = diff;
+    }
+
+    /// Enables listing the changes each translator makes to the module, along with their size
+    /// delta where the translator measures it.
+    pub fn set_changes(&mut self, changes: bool) {
+        self.changes = changes;
+    }
+
+    /// Validates the module after every translator or creator which changed it, and stops the
+    /// ruleset with an error if it is invalid.
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.paranoid = paranoid;
+    }
+
     pub fn take_result(self) -> ChiselResult {
         match self.state {
             DriverState::Ready => {
@@ -80,150 +155,465 @@ impl ChiselDriver {
             DriverState::Done(_) => panic!("fire() called on a completed driver"),
         };
 
+        // Execute all remaining rulesets up front when running in parallel. Their outcomes are
+        // then collected in order below, as if they had been executed one at a time.
+        if self.jobs > 1 && self.executed.is_empty() {
+            self.execute_parallel();
+        }
+
         // Consume the rulesets in the configuration and execute each one.
-        while let Some((name, mut ruleset)) = self.config.rulesets_mut().pop_front() {
-            let mut ruleset_result = RulesetResult::new(name.clone());
+        loop {
+            if let Some((ruleset_result, outcome)) = self.executed.pop_front() {
+                results.rulesets_mut().push(ruleset_result);
+                if let Err(e) = outcome {
+                    self.state = DriverState::Error(e, results);
+                    return &self.state;
+                }
+                continue;
+            }
 
-            // Load binary.
-            chisel_debug!(1, "Running ruleset {}", name);
-            chisel_debug!(1, "Looking for binary path...");
-            let binary_path = if let Some(binary_path) = ruleset.options().get(&"file".to_string())
-            {
-                chisel_debug!(1, "Found binary path: {}", &binary_path);
-                chisel_debug!(1, "Attempting to resolve path...");
-
-                match canonicalize(binary_path) {
-                    Ok(path_resolved) => {
-                        chisel_debug!(1, "Successfully resolved binary path");
-                        path_resolved
+            let (name, ruleset) = if let Some(next) = self.expanded.pop_front() {
+                next
+            } else if let Some((name, ruleset)) = self.config.rulesets_mut().pop_front() {
+                // Expand glob patterns into one ruleset per matched file.
+                match expand_ruleset(name.clone(), ruleset) {
+                    Ok(expanded) => {
+                        self.expanded.extend(expanded);
+                        continue;
                     }
-                    Err(_) => {
-                        chisel_debug!(1, "Failed to resolve binary path");
-                        self.state = DriverState::Error(
-                            DriverError::PathResolution(name.clone(), binary_path.clone()),
-                            results,
-                        );
+                    Err(e) => {
+                        let mut ruleset_result = RulesetResult::new(name);
+                        ruleset_result.set_error(e.to_string());
+                        results.rulesets_mut().push(ruleset_result);
+                        self.state = DriverState::Error(e, results);
                         return &self.state;
                     }
                 }
             } else {
-                self.state = DriverState::Error(
-                    DriverError::MissingRequiredField(name.clone(), "file".to_string()),
-                    results,
-                );
-                return &self.state;
+                break;
             };
 
-            // Look for output path and set.
-            let output_path =
-                if let Some(output_path) = ruleset.options().get(&"output".to_string()) {
-                    chisel_debug!(1, "Found output path: {}", &output_path);
-                    PathBuf::from(output_path)
-                } else {
-                    chisel_debug!(1, "No output path found.");
-                    binary_path.clone()
-                };
-            ruleset_result.set_output_path(output_path);
-
-            // Load the wasm binary into a buffer before deserialization.
-            chisel_debug!(1, "Deserializing module from file");
-            let wasm_raw = match read(binary_path) {
-                Ok(ret) => ret,
-                Err(e) => {
-                    chisel_debug!(1, "Failed to load Wasm binary");
-                    self.state = DriverState::Error(
-                        DriverError::Internal(
-                            name.clone(),
-                            "Failed to load file".to_string(),
-                            e.into(),
-                        ),
-                        results,
-                    );
-                    return &self.state;
+            // A failed ruleset is kept in the results along with its error, so that reports
+            // cover every ruleset.
+            let mut ruleset_result = RulesetResult::new(name.clone());
+            let outcome = Self::execute_ruleset(
+                name,
+                ruleset,
+                self.diff,
+                self.changes,
+                self.paranoid,
+                &mut ruleset_result,
+            );
+            if let Err(e) = outcome {
+                ruleset_result.set_error(e.to_string());
+                results.rulesets_mut().push(ruleset_result);
+                self.state = DriverState::Error(e, results);
+                return &self.state;
+            }
+            results.rulesets_mut().push(ruleset_result);
+        }
+        self.state = DriverState::Done(results);
+        &self.state
+    }
+
+    /// Executes all remaining rulesets on up to `jobs` threads, and queues their outcomes in
+    /// configuration order. Rulesets which fail to expand are queued with their error.
+    fn execute_parallel(&mut self) {
+        let mut outcomes: Vec<Option<(RulesetResult, Result<(), DriverError>)>> = Vec::new();
+        let mut queue = VecDeque::new();
+        loop {
+            let (name, ruleset) = if let Some(next) = self.expanded.pop_front() {
+                next
+            } else if let Some((name, ruleset)) = self.config.rulesets_mut().pop_front() {
+                match expand_ruleset(name.clone(), ruleset) {
+                    Ok(expanded) => {
+                        self.expanded.extend(expanded);
+                        continue;
+                    }
+                    Err(e) => {
+                        let mut ruleset_result = RulesetResult::new(name);
+                        ruleset_result.set_error(e.to_string());
+                        outcomes.push(Some((ruleset_result, Err(e))));
+                        continue;
+                    }
                 }
+            } else {
+                break;
             };
+            queue.push_back((outcomes.len(), name, ruleset));
+            outcomes.push(None);
+        }
 
-            // Try parsing as Wasm text (Wat) first. Note: this function passes through binaries.
-            let wasm_raw = match wat::parse_bytes(&wasm_raw) {
-                Ok(ret) => ret,
-                Err(e) => {
-                    chisel_debug!(1, "Failed to parse input as text");
-                    self.state = DriverState::Error(
-                        DriverError::Internal(
-                            name.clone(),
-                            "Failed to parse input as text".to_string(),
-                            e.into(),
-                        ),
-                        results,
+        debug!(
+            "Executing {} rulesets on {} threads",
+            queue.len(),
+            self.jobs
+        );
+        let threads = self.jobs.min(queue.len());
+        let queue = Arc::new(Mutex::new(queue));
+        let (sender, receiver) = mpsc::channel();
+        let diff = self.diff;
+        let changes = self.changes;
+        let paranoid = self.paranoid;
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                let sender = sender.clone();
+                thread::spawn(move || loop {
+                    let next = queue.lock().expect("Queue lock poisoned").pop_front();
+                    let (idx, name, ruleset) = match next {
+                        Some(next) => next,
+                        None => break,
+                    };
+                    let mut ruleset_result = RulesetResult::new(name.clone());
+                    let outcome = Self::execute_ruleset(
+                        name,
+                        ruleset,
+                        diff,
+                        changes,
+                        paranoid,
+                        &mut ruleset_result,
                     );
-                    return &self.state;
+                    if sender.send((idx, ruleset_result, outcome)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        for (idx, mut ruleset_result, outcome) in receiver {
+            if let Err(e) = &outcome {
+                ruleset_result.set_error(e.to_string());
+            }
+            outcomes[idx] = Some((ruleset_result, outcome));
+        }
+        for worker in workers {
+            if let Err(panic) = worker.join() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+
+        self.executed = outcomes
+            .into_iter()
+            .map(|outcome| outcome.expect("Every ruleset was executed"))
+            .collect();
+    }
+
+    /// Executes a single ruleset, recording module results in `ruleset_result`. If `diff` is set,
+    /// the differences between the input and the mutated module are recorded as well. If
+    /// `changes` is set, translators list the changes they make. If `paranoid` is set, the module
+    /// is validated after every module which changed it.
+    fn execute_ruleset(
+        name: String,
+        mut ruleset: Ruleset,
+        diff: bool,
+        changes: bool,
+        paranoid: bool,
+        ruleset_result: &mut RulesetResult,
+    ) -> Result<(), DriverError> {
+        // Load binary.
+        debug!("Running ruleset {}", name);
+        debug!("Looking for binary path...");
+        let binary_path = if let Some(binary_path) = ruleset.options().get(&"file".to_string()) {
+            debug!("Found binary path: {}", &binary_path);
+            ruleset_result.set_input_path(PathBuf::from(binary_path));
+            debug!("Attempting to resolve path...");
+
+            match canonicalize(binary_path) {
+                _ if binary_path == STDIN => {
+                    debug!("Reading binary from stdin");
+                    None
                 }
-            };
+                Ok(path_resolved) => {
+                    debug!("Successfully resolved binary path");
+                    Some(path_resolved)
+                }
+                Err(_) => {
+                    debug!("Failed to resolve binary path");
+                    return Err(DriverError::PathResolution(name, binary_path.clone()));
+                }
+            }
+        } else {
+            return Err(DriverError::MissingRequiredField(name, "file".to_string()));
+        };
 
-            // Deserialize the Wasm binary and parse its names section.
-            let mut wasm = match Module::from_bytes(wasm_raw) {
-                Ok(wasm) => {
-                    chisel_debug!(1, "Successfully deserialized Wasm module");
-                    // TODO: Make this error recoverable
-                    wasm.parse_names().expect("names parsing failed")
+        // Look for output path and set.
+        let output_path = if let Some(output_path) = ruleset.options().get(&"output".to_string()) {
+            debug!("Found output path: {}", &output_path);
+            let input_path = ruleset
+                .options()
+                .get(&"file".to_string())
+                .expect("Input path was found");
+            match render_output_path(output_path, Path::new(input_path), &name) {
+                Ok(rendered) => PathBuf::from(rendered),
+                Err(_) => {
+                    debug!("Invalid output path template: {}", &output_path);
+                    return Err(DriverError::InvalidField(name, "output".to_string()));
                 }
-                Err(e) => {
-                    chisel_debug!(1, "Failed to deserialize Wasm module");
-                    self.state = DriverState::Error(
-                        DriverError::Internal(
-                            name.clone(),
-                            "Deserialization failure".to_string(),
-                            e.into(),
-                        ),
-                        results,
-                    );
-                    return &self.state;
+            }
+        } else {
+            debug!("No output path found.");
+            match &binary_path {
+                Some(binary_path) => binary_path.clone(),
+                None => PathBuf::from("/dev/stdout"),
+            }
+        };
+        ruleset_result.set_output_path(output_path);
+
+        // Rulesets are transactional unless disabled.
+        match ruleset.options().get("transactional").map(|t| t.as_str()) {
+            None | Some("true") => (),
+            Some("false") => ruleset_result.set_transactional(false),
+            Some(_) => return Err(DriverError::InvalidField(name, "transactional".to_string())),
+        }
+
+        // Load the wasm binary into a buffer before deserialization.
+        debug!("Deserializing module from file");
+        let wasm_raw = match &binary_path {
+            Some(binary_path) => read(binary_path),
+            None => {
+                let mut buf = Vec::new();
+                io::stdin().read_to_end(&mut buf).map(|_| buf)
+            }
+        };
+        let wasm_raw = match wasm_raw {
+            Ok(ret) => ret,
+            Err(e) => {
+                debug!("Failed to load Wasm binary");
+                return Err(DriverError::Internal(
+                    name,
+                    "Failed to load file".to_string(),
+                    e.into(),
+                ));
+            }
+        };
+
+        // Detect the input format, and decode text, hex and JSON inputs to binary.
+        let wasm_raw = match input::decode(wasm_raw) {
+            Ok((format, ret)) => {
+                debug!("Detected {} input", format);
+                ret
+            }
+            Err(e) => {
+                debug!("Failed to decode input");
+                return Err(DriverError::Internal(
+                    name,
+                    "Failed to decode input".to_string(),
+                    e,
+                ));
+            }
+        };
+
+        // Deserialize the Wasm binary and parse its names section.
+        let mut wasm = match proposals::from_bytes(&wasm_raw) {
+            Ok(wasm) => {
+                debug!("Successfully deserialized Wasm module");
+                for proposal in proposals::detect(&wasm) {
+                    debug!("Module uses {}", proposal);
                 }
-            };
+                // TODO: Make this error recoverable
+                wasm.parse_names().expect("names parsing failed")
+            }
+            Err(e) => {
+                debug!("Failed to deserialize Wasm module");
+                return Err(DriverError::Internal(
+                    name,
+                    "Deserialization failure".to_string(),
+                    e.into(),
+                ));
+            }
+        };
 
-            // Consume modules in ruleset and execute.
-            while let Some((name, module)) = ruleset.modules_mut().pop_front() {
-                chisel_debug!(1, "Executing module {}", &name);
+        let input = if diff { Some(wasm.clone()) } else { None };
 
-                let module_result = match self.execute_module(name, module, &mut wasm) {
-                    Ok(result) => result,
-                    Err(error_state) => {
-                        self.state = DriverState::Error(error_state, results);
-                        return &self.state;
-                    }
-                };
-
-                // If the module was a translator or creator, we set the output in the result.
-                match module_result {
-                    ModuleResult::Creator(_, ref result)
-                    | ModuleResult::Translator(_, ref result) => {
-                        if let Ok(true) = result {
-                            chisel_debug!(1, "Module mutated or created.");
-                            ruleset_result.set_output_module(wasm.clone()); //TODO: Refactor to only set this at the end and save some expensive copies
+        // Consume modules in ruleset and execute. All modules work on the same module, which is
+        // only moved into the result once all of them have been executed.
+        while let Some((name, mut module)) = ruleset.modules_mut().pop_front() {
+            debug!("Executing module {}", &name);
+
+            // The failure policy applies to the result, and is not passed to the module.
+            let policy = match module.take_policy() {
+                Ok(policy) => policy,
+                Err(_) => return Err(DriverError::InvalidField(name, "on_fail".to_string())),
+            };
+
+            // Consecutive modules operating on the same foreign IR share a single conversion.
+            // Paranoid validation needs the module after each of them, so that an invalid module
+            // is blamed on the module which produced it, and is not batched.
+            if let Some(ir) = module_ir(&name).filter(|_| !paranoid) {
+                let mut batch = vec![(name, policy, module)];
+                while ruleset
+                    .modules()
+                    .front()
+                    .is_some_and(|(next, _)| module_ir(next) == Some(ir))
+                {
+                    let (name, mut module) =
+                        ruleset.modules_mut().pop_front().expect("Checked above");
+                    let policy = match module.take_policy() {
+                        Ok(policy) => policy,
+                        Err(_) => {
+                            return Err(DriverError::InvalidField(name, "on_fail".to_string()))
                         }
-                    }
-                    ModuleResult::Validator(_, _) => (),
+                    };
+                    batch.push((name, policy, module));
                 }
-                ruleset_result.results_mut().push(module_result);
+                Self::execute_batch(ir, batch, &mut wasm, changes, ruleset_result)?;
+                continue;
+            }
+
+            let mut notes = vec![];
+            let module_result = Self::execute_module(name, module, &mut wasm, changes, &mut notes)?;
+            let changed = match &module_result {
+                ModuleResult::Creator(name, Ok(true))
+                | ModuleResult::Translator(name, Ok(true), _) => Some(name.clone()),
+                _ => None,
+            };
+            ruleset_result.push_result_with_notes(module_result, policy, notes);
+            if let (true, Some(name)) = (paranoid, changed) {
+                check_output(&name, &wasm)?;
             }
-            results.rulesets_mut().push(ruleset_result);
         }
-        self.state = DriverState::Done(results);
-        &self.state
+
+        // If a translator or creator changed the module, it is the output of the ruleset.
+        if ruleset_result.mutated() {
+            debug!("Module mutated or created.");
+            if let Some(input) = input {
+                ruleset_result.set_diff(libchisel::diff::diff(&input, &wasm));
+            }
+            ruleset_result.set_output_module(wasm);
+        }
+        Ok(())
     }
 
+    /// Executes consecutive modules which operate on the same foreign IR, converting the module
+    /// to and from the IR only once. If `changes` is set, each module lists the changes it made.
+    fn execute_batch(
+        ir: Ir,
+        batch: Vec<(String, FailurePolicy, ModuleConfig)>,
+        wasm: &mut Module,
+        changes: bool,
+        ruleset_result: &mut RulesetResult,
+    ) -> Result<(), DriverError> {
+        debug!(
+            "Executing {} modules on a single {:?} conversion",
+            batch.len(),
+            ir
+        );
+        let results = match ir {
+            Ir::Walrus => {
+                let translators = batch
+                    .iter()
+                    .map(|(name, _, module)| walrus_translator(name, module))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let translators: Vec<&dyn WalrusTranslator> =
+                    translators.iter().map(|t| t.as_ref()).collect();
+                batch::translate_walrus(wasm, &translators, changes)
+            }
+            #[cfg(feature = "binaryen")]
+            Ir::Binaryen => {
+                let translators = batch
+                    .iter()
+                    .map(|(name, _, module)| binaryen_translator(name, module))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let translators: Vec<&dyn BinaryenTranslator> =
+                    translators.iter().map(|t| t.as_ref()).collect();
+                batch::translate_binaryen(wasm, &translators, changes)
+            }
+        };
+
+        for ((name, policy, _), result) in batch.into_iter().zip(results) {
+            ruleset_result.push_result(batch_result(name, result, changes), policy);
+        }
+        Ok(())
+    }
+
+    /// Executes a module on `wasm`. Notes on the result, such as the locations a validator
+    /// objected to, are appended to `notes`. If `changes` is set, a translator lists the changes
+    /// it made.
     pub fn execute_module(
-        &mut self,
         name: String,
         module: ModuleConfig,
         wasm: &mut Module,
+        changes: bool,
+        notes: &mut Vec<String>,
     ) -> Result<ModuleResult, DriverError> {
         let result = match name.as_str() {
-            "checkfloat" => {
-                let checkfloat = CheckFloat::with_defaults().expect("Should not fail");
-                let module_result = checkfloat.validate(wasm);
-                ModuleResult::Validator(name, module_result)
+            "canonicalizenan" => {
+                let canonicalizenan = CanonicalizeNan::with_defaults().expect("Should not fail");
+                let instrumented = canonicalizenan.instrument(wasm);
+                notes.extend(instrumented.iter().map(|function| function.to_string()));
+                ModuleResult::Translator(name, Ok(!instrumented.is_empty()), None)
             }
+            "checkfloat" => match CheckFloat::with_config(module.options()) {
+                Ok(checkfloat) => {
+                    let module_result = checkfloat.find(wasm).map(|uses| {
+                        notes.extend(uses.iter().map(|u| u.to_string()));
+                        uses.is_empty()
+                    });
+                    ModuleResult::Validator(name, module_result)
+                }
+                Err(_) => {
+                    debug!("checkfloat given invalid value for 'reachable_only'");
+                    return Err(DriverError::InvalidField(
+                        name,
+                        "reachable_only".to_string(),
+                    ));
+                }
+            },
+            "checkinstructions" => match CheckInstructions::with_config(module.options()) {
+                Ok(checkinstructions) => {
+                    let module_result = checkinstructions.find(wasm).map(|violations| {
+                        notes.extend(violations.iter().map(|v| v.to_string()));
+                        violations.is_empty()
+                    });
+                    ModuleResult::Validator(name, module_result)
+                }
+                Err(e) => {
+                    debug!("checkinstructions given invalid configuration: {}", e);
+                    return Err(DriverError::InvalidField(
+                        name,
+                        invalid_option(&e, "allow|deny"),
+                    ));
+                }
+            },
+            "checklimits" => match CheckLimits::with_config(module.options()) {
+                Ok(checklimits) => {
+                    let module_result = checklimits.find(wasm).map(|violations| {
+                        notes.extend(violations.iter().map(|v| v.to_string()));
+                        violations.is_empty()
+                    });
+                    ModuleResult::Validator(name, module_result)
+                }
+                Err(e) => {
+                    debug!("checklimits given invalid configuration: {}", e);
+                    // Either a limit is not a number, or no limit is given.
+                    return Err(match e {
+                        ModuleError::InvalidConfig(key, _)
+                            if module.options().contains_key(&key) =>
+                        {
+                            DriverError::InvalidField(name, key)
+                        }
+                        _ => DriverError::MissingRequiredField(name, "max_*".to_string()),
+                    });
+                }
+            },
+            "checksize" => match CheckSize::with_config(module.options()) {
+                Ok(checksize) => {
+                    let module_result = checksize.find(wasm).map(|oversize| {
+                        notes.extend(oversize.iter().map(|part| part.to_string()));
+                        oversize.is_empty()
+                    });
+                    ModuleResult::Validator(name, module_result)
+                }
+                Err(e) => {
+                    debug!("checksize given invalid configuration: {}", e);
+                    return Err(DriverError::InvalidField(
+                        name,
+                        invalid_option(&e, "max_size|preset"),
+                    ));
+                }
+            },
             "checkstartfunc" => {
                 if let Some(require_start) = module.options().get("require_start") {
                     let require_start = match require_start.as_str() {
@@ -240,7 +630,7 @@ impl ChiselDriver {
                     let module_result = checkstartfunc.validate(wasm);
                     ModuleResult::Validator(name, module_result)
                 } else {
-                    chisel_debug!(1, "checkstartfunc missing field 'require_start'");
+                    debug!("checkstartfunc missing field 'require_start'");
                     return Err(DriverError::MissingRequiredField(
                         name,
                         "require_start".to_string(),
@@ -259,17 +649,17 @@ impl ChiselDriver {
                                     false
                                 };
 
-                                ModuleResult::Translator(name, Ok(did_mutate))
+                                ModuleResult::Translator(name, Ok(did_mutate), None)
                             }
-                            Err(e) => ModuleResult::Translator(name, Err(e)),
+                            Err(e) => ModuleResult::Translator(name, Err(e), None),
                         },
                         Err(_) => {
-                            chisel_debug!(1, "deployer given invalid preset");
+                            debug!("deployer given invalid preset");
                             return Err(DriverError::InvalidField(name, "preset".to_string()));
                         }
                     }
                 } else {
-                    chisel_debug!(1, "deployer missing field 'preset'");
+                    debug!("deployer missing field 'preset'");
                     return Err(DriverError::MissingRequiredField(
                         name,
                         "preset".to_string(),
@@ -278,20 +668,29 @@ impl ChiselDriver {
             }
             "dropnames" => {
                 let dropsection = DropSection::NamesSection;
-                ModuleResult::Translator(name, dropsection.translate_inplace(wasm))
+                translate(name, &dropsection, wasm, changes)
             }
+            "dropsection" => match DropSection::with_config(module.options()) {
+                Ok(dropsection) => translate(name, &dropsection, wasm, changes),
+                Err(e) => {
+                    debug!("dropsection given invalid configuration: {}", e);
+                    return Err(DriverError::InvalidField(
+                        name,
+                        invalid_option(&e, "names|custom_by_name|custom_by_index|unknown_by_index"),
+                    ));
+                }
+            },
             "remapimports" => {
                 if let Some(preset) = module.options().get("preset") {
                     let remapimports = RemapImports::with_preset(preset.as_str());
                     if let Ok(remapimports) = remapimports {
-                        let module_result = remapimports.translate_inplace(wasm);
-                        ModuleResult::Translator(name, module_result)
+                        translate(name, &remapimports, wasm, changes)
                     } else {
-                        chisel_debug!(1, "remapimports given invalid preset");
+                        debug!("remapimports given invalid preset");
                         return Err(DriverError::InvalidField(name, "preset".to_string()));
                     }
                 } else {
-                    chisel_debug!(1, "remapimports missing field 'preset'");
+                    debug!("remapimports missing field 'preset'");
                     return Err(DriverError::MissingRequiredField(
                         name,
                         "preset".to_string(),
@@ -302,56 +701,39 @@ impl ChiselDriver {
                 // NOTE: preset "ewasm" maps to the default and only mode. Fixing
                 // later.
                 let remapstart = RemapStart::with_preset("ewasm").expect("Should not fail");
-                let module_result = remapstart.translate_inplace(wasm);
-                ModuleResult::Translator(name, module_result)
+                translate(name, &remapstart, wasm, changes)
             }
             "repack" => {
                 let repack = Repack::with_defaults().expect("Should not fail");
-                let module_result = repack.translate(wasm).expect("No failure cases");
-
-                let did_mutate = if let Some(new_wasm) = module_result {
-                    *wasm = new_wasm;
-                    true
-                } else {
-                    false
-                };
-
-                ModuleResult::Translator(name, Ok(did_mutate))
+                translate(name, &repack, wasm, changes)
             }
             "snip" => {
-                let snip = Snip::with_defaults().expect("Should not fail");
-                let module_result = match snip.translate(wasm) {
-                    Ok(result) => result,
-                    Err(e) => {
-                        return Err(DriverError::Internal(
-                            "snip".to_string(),
-                            "Chisel module failed".to_string(),
-                            e.into(),
-                        ))
-                    }
-                };
-
-                let did_mutate = if let Some(new_wasm) = module_result {
-                    *wasm = new_wasm;
-                    true
-                } else {
-                    false
-                };
-
-                ModuleResult::Translator(name, Ok(did_mutate))
+                let snip = walrus_translator(&name, &module)?;
+                let module_result =
+                    batch::translate_walrus(wasm, &[snip.as_ref()], changes).remove(0);
+                batch_result(name, module_result, changes)
             }
+            "softfloat" => match SoftFloat::with_config(module.options()) {
+                Ok(softfloat) => translate(name, &softfloat, wasm, changes),
+                Err(e) => {
+                    debug!("softfloat given invalid configuration: {}", e);
+                    return Err(DriverError::InvalidField(
+                        name,
+                        invalid_option(&e, "mode|namespace"),
+                    ));
+                }
+            },
             "trimexports" => {
                 if let Some(preset) = module.options().get("preset") {
                     let trimexports = TrimExports::with_preset(preset.as_str());
                     if let Ok(trimexports) = trimexports {
-                        let module_result = trimexports.translate_inplace(wasm);
-                        ModuleResult::Translator(name, module_result)
+                        translate(name, &trimexports, wasm, changes)
                     } else {
-                        chisel_debug!(1, "trimexports given invalid preset");
+                        debug!("trimexports given invalid preset");
                         return Err(DriverError::InvalidField(name, "preset".to_string()));
                     }
                 } else {
-                    chisel_debug!(1, "remapimports missing field 'preset'");
+                    debug!("remapimports missing field 'preset'");
                     return Err(DriverError::MissingRequiredField(
                         name,
                         "preset".to_string(),
@@ -362,9 +744,21 @@ impl ChiselDriver {
                 // NOTE: preset "ewasm" maps to the default and only mode. Fixing
                 // later.
                 let trimstartfunc = TrimStartFunc::with_preset("ewasm").expect("Should not fail");
-                let module_result = trimstartfunc.translate_inplace(wasm);
-                ModuleResult::Translator(name, module_result)
+                translate(name, &trimstartfunc, wasm, changes)
             }
+            "validate" => match Validate::with_config(module.options()) {
+                Ok(validate) => {
+                    let module_result = validate.find(wasm).map(|invalid| {
+                        notes.extend(invalid.iter().map(|invalid| invalid.to_string()));
+                        invalid.is_none()
+                    });
+                    ModuleResult::Validator(name, module_result)
+                }
+                Err(e) => {
+                    debug!("validate given invalid features: {}", e);
+                    return Err(DriverError::InvalidField(name, "features".to_string()));
+                }
+            },
             "verifyexports" => {
                 if let Some(preset) = module.options().get("preset") {
                     let verifyexports = VerifyExports::with_preset(preset.as_str());
@@ -372,11 +766,11 @@ impl ChiselDriver {
                         let module_result = verifyexports.validate(wasm);
                         ModuleResult::Validator(name, module_result)
                     } else {
-                        chisel_debug!(1, "verifyexports given invalid preset");
+                        debug!("verifyexports given invalid preset");
                         return Err(DriverError::InvalidField(name, "preset".to_string()));
                     }
                 } else {
-                    chisel_debug!(1, "verifyexports missing field 'preset'");
+                    debug!("verifyexports missing field 'preset'");
                     return Err(DriverError::MissingRequiredField(
                         name,
                         "preset".to_string(),
@@ -390,11 +784,11 @@ impl ChiselDriver {
                         let module_result = verifyimports.validate(&wasm);
                         ModuleResult::Validator(name, module_result)
                     } else {
-                        chisel_debug!(1, "verifyimports given invalid preset");
+                        debug!("verifyimports given invalid preset");
                         return Err(DriverError::InvalidField(name, "preset".to_string()));
                     }
                 } else {
-                    chisel_debug!(1, "verifyimports missing field 'preset'");
+                    debug!("verifyimports missing field 'preset'");
                     return Err(DriverError::MissingRequiredField(
                         name,
                         "preset".to_string(),
@@ -403,33 +797,10 @@ impl ChiselDriver {
             }
             #[cfg(feature = "binaryen")]
             "binaryenopt" => {
-                if let Some(preset) = module.options().get("preset") {
-                    let binaryenopt = BinaryenOptimiser::with_preset(preset.as_str());
-                    if let Ok(binaryenopt) = binaryenopt {
-                        match binaryenopt.translate(wasm) {
-                            Ok(new_wasm) => {
-                                let did_mutate = if let Some(new_wasm) = new_wasm {
-                                    *wasm = new_wasm;
-                                    true
-                                } else {
-                                    false
-                                };
-
-                                ModuleResult::Translator(name, Ok(did_mutate))
-                            }
-                            Err(e) => ModuleResult::Translator(name, Err(e)),
-                        }
-                    } else {
-                        chisel_debug!(1, "binaryenopt given invalid preset");
-                        return Err(DriverError::InvalidField(name, "preset".to_string()));
-                    }
-                } else {
-                    chisel_debug!(1, "binaryenopt missing field 'preset'");
-                    return Err(DriverError::MissingRequiredField(
-                        name,
-                        "preset".to_string(),
-                    ));
-                }
+                let binaryenopt = binaryen_translator(&name, &module)?;
+                let module_result =
+                    batch::translate_binaryen(wasm, &[binaryenopt.as_ref()], changes).remove(0);
+                batch_result(name, module_result, changes)
             }
             _ => {
                 return Err(DriverError::ModuleNotFound(name.clone()));
@@ -439,6 +810,239 @@ impl ChiselDriver {
     }
 }
 
+/// Runs `translator` on `wasm`. The translator is only asked for its changes if `changes` is set.
+fn translate(
+    name: String,
+    translator: &dyn ModuleTranslator,
+    wasm: &mut Module,
+    changes: bool,
+) -> ModuleResult {
+    if !changes {
+        return ModuleResult::Translator(name, translator.translate_inplace(wasm), None);
+    }
+    match translator.translate_with_changes(wasm) {
+        Ok(changes) => ModuleResult::Translator(name, Ok(changes.is_some()), changes),
+        Err(e) => ModuleResult::Translator(name, Err(e), None),
+    }
+}
+
+/// Converts the result of a translator run in a batch to a module result, keeping its changes
+/// only if `changes` is set.
+fn batch_result(
+    name: String,
+    result: Result<Option<Changes>, ModuleError>,
+    changes: bool,
+) -> ModuleResult {
+    match result {
+        Ok(summary) => {
+            ModuleResult::Translator(name, Ok(summary.is_some()), summary.filter(|_| changes))
+        }
+        Err(e) => ModuleResult::Translator(name, Err(e), None),
+    }
+}
+
+/// Foreign IRs on which some modules operate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Ir {
+    Walrus,
+    #[cfg(feature = "binaryen")]
+    Binaryen,
+}
+
+/// Returns the foreign IR the named module operates on, if any.
+fn module_ir(name: &str) -> Option<Ir> {
+    match name {
+        "snip" => Some(Ir::Walrus),
+        #[cfg(feature = "binaryen")]
+        "binaryenopt" => Some(Ir::Binaryen),
+        _ => None,
+    }
+}
+
+/// Instantiates a module operating on the walrus IR.
+fn walrus_translator(
+    name: &str,
+    module: &ModuleConfig,
+) -> Result<Box<dyn WalrusTranslator>, DriverError> {
+    match name {
+        "snip" => Ok(Box::new(
+            Snip::with_config(module.options()).expect("Should not fail"),
+        )),
+        _ => Err(DriverError::ModuleNotFound(name.to_string())),
+    }
+}
+
+/// Instantiates a module operating on the binaryen IR.
+#[cfg(feature = "binaryen")]
+fn binaryen_translator(
+    name: &str,
+    module: &ModuleConfig,
+) -> Result<Box<dyn BinaryenTranslator>, DriverError> {
+    match name {
+        "binaryenopt" => match module.options().get("preset") {
+            Some(preset) => match BinaryenOptimiser::with_preset(preset.as_str()) {
+                Ok(binaryenopt) => Ok(Box::new(binaryenopt)),
+                Err(_) => {
+                    debug!("binaryenopt given invalid preset");
+                    Err(DriverError::InvalidField(
+                        name.to_string(),
+                        "preset".to_string(),
+                    ))
+                }
+            },
+            None => {
+                debug!("binaryenopt missing field 'preset'");
+                Err(DriverError::MissingRequiredField(
+                    name.to_string(),
+                    "preset".to_string(),
+                ))
+            }
+        },
+        _ => Err(DriverError::ModuleNotFound(name.to_string())),
+    }
+}
+
+/// Input path which reads the module from stdin.
+const STDIN: &str = "-";
+
+/// Returns true if the path contains glob metacharacters.
+fn is_glob(path: &str) -> bool {
+    path.contains(&['*', '?', '['][..])
+}
+
+/// Expands a ruleset whose input file is a glob pattern into one ruleset per matched file, in
+/// lexicographic order. Other rulesets are returned as they are.
+fn expand_ruleset(name: String, ruleset: Ruleset) -> Result<Vec<(String, Ruleset)>, DriverError> {
+    let pattern = match ruleset.options().get("file") {
+        Some(pattern) if is_glob(pattern) => pattern.clone(),
+        _ => return Ok(vec![(name, ruleset)]),
+    };
+
+    debug!("Expanding input pattern {}", &pattern);
+    let paths = match glob::glob(&pattern) {
+        Ok(paths) => paths,
+        Err(e) => {
+            return Err(DriverError::Internal(
+                name,
+                format!("Invalid glob pattern '{}'", pattern),
+                e.into(),
+            ))
+        }
+    };
+
+    let mut ret = Vec::new();
+    for path in paths {
+        let path = match path {
+            Ok(path) => path,
+            Err(e) => {
+                return Err(DriverError::Internal(
+                    name,
+                    "Failed to read matched path".to_string(),
+                    e.into(),
+                ))
+            }
+        };
+        let path = match path.to_str() {
+            Some(path) => path.to_string(),
+            None => {
+                return Err(DriverError::PathResolution(
+                    name,
+                    path.display().to_string(),
+                ))
+            }
+        };
+
+        let mut expanded = ruleset.clone();
+        expanded.options_mut().insert("file".to_string(), path);
+        ret.push((name.clone(), expanded));
+    }
+
+    if ret.is_empty() {
+        return Err(DriverError::NoMatches(name, pattern));
+    }
+
+    // Several inputs written to a single fixed output path would overwrite each other.
+    if let Some(output) = ruleset.options().get("output") {
+        if ret.len() > 1 && !output.contains('{') {
+            debug!("Output path must be a template when several files match");
+            return Err(DriverError::InvalidField(name, "output".to_string()));
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Renders an output path template for an input file. The following placeholders are supported:
+/// {name}: the file name of the input, e.g. "token.wasm"
+/// {stem}: the file name without its extension, e.g. "token"
+/// {ext}: the extension of the input, e.g. "wasm"
+/// {dir}: the directory containing the input
+/// {ruleset}: the name of the ruleset
+/// Literal braces are written as "{{" and "}}".
+fn render_output_path(template: &str, input: &Path, ruleset: &str) -> Result<String, String> {
+    let component =
+        |s: Option<&std::ffi::OsStr>| s.and_then(|s| s.to_str()).unwrap_or_default().to_string();
+
+    let mut ret = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                ret.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                ret.push('}');
+            }
+            '{' => {
+                let placeholder: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                ret.push_str(&match placeholder.as_str() {
+                    "name" => component(input.file_name()),
+                    "stem" => component(input.file_stem()),
+                    "ext" => component(input.extension()),
+                    "dir" => match input.parent() {
+                        Some(dir) if dir != Path::new("") => component(Some(dir.as_os_str())),
+                        _ => ".".to_string(),
+                    },
+                    "ruleset" => ruleset.to_string(),
+                    other => return Err(format!("unknown placeholder '{{{}}}'", other)),
+                });
+            }
+            c => ret.push(c),
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Returns the option a module configuration error is about, or `fallback` if it is not known.
+fn invalid_option(error: &ModuleError, fallback: &str) -> String {
+    match error {
+        ModuleError::InvalidConfig(option, _) => option.clone(),
+        _ => fallback.to_string(),
+    }
+}
+
+/// Validates the module produced by the named modules. Returns an error naming them if it is
+/// invalid.
+fn check_output(name: &str, wasm: &Module) -> Result<(), DriverError> {
+    debug!("Validating the module produced by {}", name);
+    let validate = Validate::with_defaults().expect("Should not fail");
+    match validate.find(wasm) {
+        Ok(None) => Ok(()),
+        Ok(Some(invalid)) => Err(DriverError::InvalidOutput(
+            name.to_string(),
+            invalid.to_string(),
+        )),
+        Err(e) => Err(DriverError::Internal(
+            name.to_string(),
+            "Failed to validate the module".to_string(),
+            e.into(),
+        )),
+    }
+}
+
 // Error.description() is deprecated for displaying errors now.
 impl Display for DriverError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -453,8 +1057,24 @@ impl Display for DriverError {
             DriverError::PathResolution(object, path) => {
                 write!(f, "in '{}': failed to resolve path '{}'", object, path)
             }
+            DriverError::NoMatches(object, pattern) => {
+                write!(f, "in '{}': no files match '{}'", object, pattern)
+            }
             DriverError::Internal(object, info, err) => {
-                write!(f, "in '{}': {}; {}", object, info, err)
+                write!(
+                    f,
+                    "in '{}': {}; {}",
+                    object,
+                    info,
+                    error_chain(err.as_ref())
+                )
+            }
+            DriverError::InvalidOutput(object, invalid) => {
+                write!(
+                    f,
+                    "in '{}': produced an invalid module: {}",
+                    object, invalid
+                )
             }
         }
     }
@@ -465,7 +1085,7 @@ mod tests {
     use std::panic::catch_unwind;
 
     use super::*;
-    use crate::config::{ChiselConfig, FromArgs};
+    use crate::config::{ChiselConfig, FailurePolicy, FromArgs};
 
     #[test]
     fn take_result_ready() {
@@ -536,4 +1156,609 @@ mod tests {
 
         assert!(is_correct, "Module result incorrect");
     }
+
+    #[test]
+    fn unsupported_proposal() {
+        let mut config = ChiselConfig::from_args("verifyimports", "verifyimports.preset=ewasm")
+            .expect("Cannot fail");
+
+        config.rulesets_mut()[0]
+            .1
+            .options_mut()
+            .insert("file".to_string(), "./res/test/reftypes.wat".to_string());
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Error(DriverError::Internal(_, message, err), _) => {
+                assert_eq!(message, "Deserialization failure");
+                let chain = error_chain(err.as_ref());
+                assert!(chain.contains("reference types"), "{}", chain)
+            }
+            _ => panic!("Must be a deserialization error"),
+        }
+    }
+
+    #[test]
+    fn saturating_conversions_kept() {
+        let dir = std::env::temp_dir().join(format!("chisel-saturating-{}", std::process::id()));
+        let config = format!(
+            "\
+test:
+  file: \"./res/test/saturating.wat\"
+  output: \"{}/saturating.wasm\"
+  modules:
+    - trimexports:
+        preset: ewasm
+    - validate
+",
+            dir.display()
+        )
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config);
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+        let mut result = driver.take_result();
+        let written = result.rulesets_mut()[0].write("bin");
+        let output = std::fs::read(dir.join("saturating.wasm"));
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(written.ok(), Some(true));
+        let text = wasmprinter::print_bytes(output.unwrap()).unwrap();
+        assert!(text.contains("i32.trunc_sat_f32_s"), "{}", text);
+        assert!(!text.contains("foo"), "{}", text);
+    }
+
+    #[test]
+    fn validator_notes_recorded() {
+        let mut config = ChiselConfig::from_args("checkfloat", "checkfloat.reachable_only=true")
+            .expect("Cannot fail");
+
+        config.rulesets_mut()[0]
+            .1
+            .options_mut()
+            .insert("file".to_string(), "./res/test/float.wat".to_string());
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        let ruleset = &result.rulesets()[0];
+        assert!(!ruleset.passed());
+        assert_eq!(
+            ruleset.notes(0),
+            &["function 0 ($main), instruction 0: f32.const 1065353216".to_string()]
+        );
+    }
+
+    #[test]
+    fn denied_instructions_recorded() {
+        let config = "\
+test:
+  file: \"./res/test/float.wat\"
+  checkinstructions:
+    deny: [\"float\", \"drop\"]
+"
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        let ruleset = &result.rulesets()[0];
+        assert!(!ruleset.passed());
+        assert_eq!(
+            ruleset.notes(0),
+            &[
+                "function 0 ($main), instruction 0: f32.const".to_string(),
+                "function 0 ($main), instruction 1: drop".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_limit() {
+        let mut config = ChiselConfig::from_args("checklimits", "checklimits.max_locals=many")
+            .expect("Cannot fail");
+
+        config.rulesets_mut()[0]
+            .1
+            .options_mut()
+            .insert("file".to_string(), "./res/test/float.wat".to_string());
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Error(DriverError::InvalidField(_, field), _) => {
+                assert_eq!(field, "max_locals")
+            }
+            _ => panic!("Must be an error"),
+        }
+    }
+
+    #[test]
+    fn oversize_breakdown_recorded() {
+        let mut config =
+            ChiselConfig::from_args("checksize", "checksize.max_size=16").expect("Cannot fail");
+
+        config.rulesets_mut()[0]
+            .1
+            .options_mut()
+            .insert("file".to_string(), "./res/test/float.wat".to_string());
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        let ruleset = &result.rulesets()[0];
+        assert!(!ruleset.passed());
+        assert_eq!(
+            ruleset.notes(0)[0],
+            "module: 56 bytes, limit 16, 40 bytes over"
+        );
+        assert_eq!(
+            ruleset.notes(0).last().unwrap(),
+            "function 0 ($main): 8 bytes"
+        );
+    }
+
+    #[test]
+    fn validation_error_recorded() {
+        let mut config = ChiselConfig::from_args("validate", "").expect("Cannot fail");
+
+        config.rulesets_mut()[0]
+            .1
+            .options_mut()
+            .insert("file".to_string(), "./res/test/invalid.wat".to_string());
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        let ruleset = &result.rulesets()[0];
+        assert!(!ruleset.passed());
+        assert_eq!(
+            ruleset.notes(0),
+            &[
+                "function 0 ($main), offset 0x24: type mismatch: expected i32, found i64"
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn translator_notes_recorded() {
+        let mut config = ChiselConfig::from_args("canonicalizenan", "").expect("Cannot fail");
+
+        config.rulesets_mut()[0]
+            .1
+            .options_mut()
+            .insert("file".to_string(), "./res/test/nan.wat".to_string());
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        let ruleset = &result.rulesets()[0];
+        assert!(ruleset.passed());
+        assert_eq!(
+            ruleset.notes(0),
+            &["function 0 ($main): 1 NaN canonicalization".to_string()]
+        );
+    }
+
+    #[test]
+    fn softfloat_removes_floats() {
+        let config = "\
+test:
+  file: \"./res/test/float.wat\"
+  modules:
+    - softfloat:
+        mode: \"import\"
+    - checkfloat
+"
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        let ruleset = &result.rulesets()[0];
+        assert!(ruleset.passed());
+        assert!(ruleset.notes(1).is_empty());
+    }
+
+    #[test]
+    fn execute_module_list_in_order() {
+        let config = "\
+test:
+  file: \"./res/test/empty.wasm\"
+  modules:
+    - verifyimports:
+        preset: \"ewasm\"
+    - dropsection:
+        custom_by_name: \"name\"
+    - verifyimports:
+        preset: \"ewasm\"
+"
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let mut result = driver.take_result();
+        let names: Vec<String> = result.rulesets_mut()[0]
+            .results_mut()
+            .iter()
+            .map(|module_result| match module_result {
+                ModuleResult::Creator(name, _)
+                | ModuleResult::Translator(name, _, _)
+                | ModuleResult::Validator(name, _) => name.clone(),
+            })
+            .collect();
+
+        assert_eq!(names, vec!["verifyimports", "dropsection", "verifyimports"]);
+    }
+
+    #[test]
+    fn batched_modules_in_order() {
+        let config = "\
+test:
+  file: \"./res/test/empty.wasm\"
+  modules:
+    - snip:
+    - snip:
+        on_fail: ignore
+    - verifyimports:
+        preset: \"ewasm\"
+    - snip:
+"
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let mut result = driver.take_result();
+        let ruleset_result = &mut result.rulesets_mut()[0];
+        assert_eq!(ruleset_result.policy(1), FailurePolicy::Ignore);
+        let names: Vec<String> = ruleset_result
+            .results_mut()
+            .iter()
+            .map(|module_result| match module_result {
+                ModuleResult::Creator(name, _)
+                | ModuleResult::Translator(name, _, _)
+                | ModuleResult::Validator(name, _) => name.clone(),
+            })
+            .collect();
+
+        assert_eq!(names, vec!["snip", "snip", "verifyimports", "snip"]);
+        assert!(ruleset_result.mutated());
+    }
+
+    #[test]
+    fn output_path_template() {
+        let input = Path::new("target/release/token.wasm");
+        assert_eq!(
+            render_output_path("out/{stem}.chisel.{ext}", input, "contracts"),
+            Ok("out/token.chisel.wasm".to_string())
+        );
+        assert_eq!(
+            render_output_path("{dir}/{ruleset}-{name}", input, "contracts"),
+            Ok("target/release/contracts-token.wasm".to_string())
+        );
+        assert_eq!(
+            render_output_path("{dir}/{{stem}}", Path::new("token.wasm"), "contracts"),
+            Ok("./{stem}".to_string())
+        );
+        assert!(render_output_path("out/{foo}.wasm", input, "contracts").is_err());
+    }
+
+    #[test]
+    fn output_path_template_nested() {
+        let dir = std::env::temp_dir().join(format!("chisel-nested-{}", std::process::id()));
+        let config = format!(
+            "\
+test:
+  file: \"./res/test/empty.wasm\"
+  output: \"{}/out/{{stem}}.chisel.wasm\"
+  modules:
+    - deployer:
+        preset: memory
+",
+            dir.display()
+        )
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config);
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+        let mut result = driver.take_result();
+        let written = result.rulesets_mut()[0].write("bin");
+        let exists = dir.join("out/empty.chisel.wasm").is_file();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(written.ok(), Some(true));
+        assert!(exists);
+    }
+
+    #[test]
+    fn glob_expansion() {
+        let mut config = ChiselConfig::from_args("verifyimports", "verifyimports.preset=ewasm")
+            .expect("Cannot fail");
+
+        config.rulesets_mut()[0]
+            .1
+            .options_mut()
+            .insert("file".to_string(), "./res/test/*.wasm".to_string());
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        assert_eq!(result.rulesets().len(), 1);
+        assert_eq!(
+            result.rulesets()[0].input_path(),
+            &PathBuf::from("res/test/empty.wasm")
+        );
+    }
+
+    #[test]
+    fn glob_no_matches() {
+        let mut config = ChiselConfig::from_args("verifyimports", "verifyimports.preset=ewasm")
+            .expect("Cannot fail");
+
+        config.rulesets_mut()[0]
+            .1
+            .options_mut()
+            .insert("file".to_string(), "./res/test/*.nothing".to_string());
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Error(DriverError::NoMatches(_, _), _) => (),
+            _ => panic!("Must be error state"),
+        }
+    }
+
+    #[test]
+    fn glob_fixed_output_rejected() {
+        let mut config = ChiselConfig::from_args("verifyimports", "verifyimports.preset=ewasm")
+            .expect("Cannot fail");
+
+        let (name, mut ruleset) = config.rulesets_mut().pop_front().expect("Cannot fail");
+        ruleset
+            .options_mut()
+            .insert("file".to_string(), "./src/*.rs".to_string());
+        ruleset
+            .options_mut()
+            .insert("output".to_string(), "out.wasm".to_string());
+
+        match expand_ruleset(name, ruleset) {
+            Err(DriverError::InvalidField(_, field)) => assert_eq!(field, "output"),
+            _ => panic!("Must be an error"),
+        }
+    }
+
+    #[test]
+    fn failure_policy_recorded() {
+        let config = "\
+test:
+  file: \"./res/test/empty.wasm\"
+  modules:
+    - checkstartfunc:
+        require_start: true
+        on_fail: warn
+    - checkstartfunc:
+        require_start: true
+"
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config);
+
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        let ruleset_result = &result.rulesets()[0];
+        assert_eq!(ruleset_result.policy(0), FailurePolicy::Warn);
+        assert_eq!(ruleset_result.policy(1), FailurePolicy::Error);
+        assert!(ruleset_result.results().iter().all(|r| r.failed()));
+        assert!(!result.passed());
+    }
+
+    #[test]
+    fn translator_changes_recorded() {
+        let config = "\
+test:
+  file: \"./res/test/float.wat\"
+  modules:
+    - checkfloat:
+        on_fail: ignore
+    - dropnames
+    - dropnames
+"
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config.clone());
+        driver.set_changes(true);
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        let results = result.rulesets()[0].results();
+        assert!(results[0].changes().is_none());
+        let changes = results[1].changes().expect("Changes are recorded");
+        assert_eq!(
+            changes
+                .changes()
+                .iter()
+                .map(|change| change.to_string())
+                .collect::<Vec<String>>(),
+            vec!["- section custom \"name\""]
+        );
+        assert!(changes.size_delta() < Some(0));
+        // The names section is already gone.
+        assert!(results[2].changes().is_none());
+
+        // Nothing is listed unless requested.
+        let mut driver = ChiselDriver::new(config);
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+        let result = driver.take_result();
+        let results = result.rulesets()[0].results();
+        assert!(matches!(
+            results[1],
+            ModuleResult::Translator(_, Ok(true), None)
+        ));
+    }
+
+    #[test]
+    fn diff_recorded() {
+        let config = "\
+test:
+  file: \"./res/test/empty.wasm\"
+  modules:
+    - deployer:
+        preset: memory
+"
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config.clone());
+        driver.set_diff(true);
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+        let result = driver.take_result();
+        let diff = result.rulesets()[0].diff().expect("Diff is recorded");
+        assert!(!diff.is_empty());
+        assert!(diff.size_delta() > 0);
+
+        let mut driver = ChiselDriver::new(config);
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+        assert!(driver.take_result().rulesets()[0].diff().is_none());
+    }
+
+    #[test]
+    fn paranoid_validation() {
+        let config = "\
+valid:
+  file: \"./res/test/empty.wasm\"
+  deployer:
+    preset: memory
+invalid:
+  file: \"./res/test/invalid.wat\"
+  dropnames:
+"
+        .parse::<ChiselConfig>()
+        .expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config);
+        driver.set_paranoid(true);
+        match driver.fire() {
+            DriverState::Error(err, result) => {
+                assert_eq!(
+                    err.to_string(),
+                    "in 'dropnames': produced an invalid module: function 0, offset 0x24: \
+                     type mismatch: expected i32, found i64"
+                );
+                assert!(result.rulesets()[0].error().is_none());
+                assert!(result.rulesets()[1].error().is_some());
+            }
+            _ => panic!("Must be an error"),
+        }
+    }
+
+    #[test]
+    fn parallel_results_in_order() {
+        let mut config = String::new();
+        for idx in 0..8 {
+            config.push_str(&format!(
+                "r{}:\n  file: \"./res/test/empty.wasm\"\n  verifyimports:\n    preset: \"ewasm\"\n",
+                idx
+            ));
+        }
+        config.push_str("missing:\n  file: \"./res/test/missing.wasm\"\n  dropnames:\n");
+        config.push_str("last:\n  file: \"./res/test/empty.wasm\"\n  dropnames:\n");
+        let config = config.parse::<ChiselConfig>().expect("Should be valid");
+
+        let mut driver = ChiselDriver::new(config);
+        driver.set_jobs(4);
+
+        match driver.fire() {
+            DriverState::Error(DriverError::PathResolution(name, _), _) => {
+                assert_eq!(name, "missing")
+            }
+            _ => panic!("Must be error state"),
+        }
+        match driver.fire() {
+            DriverState::Done(_) => (),
+            _ => panic!("Must succeed"),
+        }
+
+        let result = driver.take_result();
+        let names: Vec<&str> = result.rulesets().iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            vec!["r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "missing", "last"]
+        );
+        assert!(result.rulesets()[8].error().is_some());
+    }
 }
@@ -0,0 +1,143 @@
+//! Input format detection.
+//! Chisel accepts Wasm modules as binaries, in the text format (WAT), or hex-encoded with or
+//! without a "0x" prefix. Hex may also be wrapped in JSON, either as a bare string, or as the
+//! "result" field of a JSON-RPC response such as the one returned by eth_getCode, or the "code"
+//! field written by the json output mode.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+
+/// Input formats recognized by decode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputFormat {
+    Binary,
+    Text,
+    Hex,
+    Json,
+}
+
+/// Detects the format of the input and decodes it to a Wasm binary.
+pub fn decode(raw: Vec<u8>) -> Result<(InputFormat, Vec<u8>), Box<dyn Error + Send + Sync>> {
+    if raw.starts_with(b"\0asm") {
+        return Ok((InputFormat::Binary, raw));
+    }
+
+    let trimmed = match std::str::from_utf8(&raw) {
+        Ok(text) => text.trim(),
+        // Not text, so let the deserializer report what is wrong with the binary.
+        Err(_) => return Ok((InputFormat::Binary, raw)),
+    };
+
+    if trimmed.starts_with('{') || trimmed.starts_with('"') {
+        let json: serde_json::Value = serde_json::from_str(trimmed)?;
+        let hex = match &json {
+            serde_json::Value::String(hex) => Some(hex.as_str()),
+            serde_json::Value::Object(object) => object
+                .get("result")
+                .or_else(|| object.get("code"))
+                .and_then(|hex| hex.as_str()),
+            _ => None,
+        };
+        return match hex {
+            Some(hex) => Ok((InputFormat::Json, decode_hex(hex)?)),
+            None => {
+                Err("expected a hex string, or an object with a 'result' or 'code' field".into())
+            }
+        };
+    }
+
+    if is_hex(trimmed) {
+        return Ok((InputFormat::Hex, decode_hex(trimmed)?));
+    }
+
+    Ok((InputFormat::Text, wat::parse_str(trimmed)?))
+}
+
+/// Returns true if the string is non-empty and consists of hex digits, optionally prefixed with
+/// "0x".
+fn is_hex(s: &str) -> bool {
+    let digits = strip_prefix(s);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn strip_prefix(s: &str) -> &str {
+    if s.starts_with("0x") || s.starts_with("0X") {
+        &s[2..]
+    } else {
+        s
+    }
+}
+
+/// Decodes a hex string, optionally prefixed with "0x".
+fn decode_hex(s: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(strip_prefix(s.trim()))
+}
+
+impl Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                InputFormat::Binary => "binary",
+                InputFormat::Text => "text",
+                InputFormat::Hex => "hex",
+                InputFormat::Json => "JSON",
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY: &[u8] = &[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn binary() {
+        assert_eq!(
+            decode(EMPTY.to_vec()).unwrap(),
+            (InputFormat::Binary, EMPTY.to_vec())
+        );
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            decode(b"(module)\n".to_vec()).unwrap(),
+            (InputFormat::Text, EMPTY.to_vec())
+        );
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(
+            decode(b"0061736d01000000\n".to_vec()).unwrap(),
+            (InputFormat::Hex, EMPTY.to_vec())
+        );
+        assert_eq!(
+            decode(b"0x0061736d01000000".to_vec()).unwrap(),
+            (InputFormat::Hex, EMPTY.to_vec())
+        );
+        assert!(decode(b"0x0061736d0100000".to_vec()).is_err());
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            decode(b"{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":\"0x0061736d01000000\"}".to_vec())
+                .unwrap(),
+            (InputFormat::Json, EMPTY.to_vec())
+        );
+        assert_eq!(
+            decode(b"{\"code\":\"0x0061736d01000000\",\"size\":8}".to_vec()).unwrap(),
+            (InputFormat::Json, EMPTY.to_vec())
+        );
+        assert_eq!(
+            decode(b"\"0061736d01000000\"".to_vec()).unwrap(),
+            (InputFormat::Json, EMPTY.to_vec())
+        );
+        assert!(decode(b"{\"error\":\"not found\"}".to_vec()).is_err());
+    }
+}
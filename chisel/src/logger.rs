@@ -1,31 +1,133 @@
-static mut LOG_LEVEL: i32 = 0;
+//! Leveled logging for chisel and libchisel.
+//! Both crates log through the `log` facade; this module provides the logger which writes the
+//! records to stderr, either as plain text or as one JSON object per line.
 
-#[macro_export]
-macro_rules! chisel_debug {
-    ($lvl:expr, $($arg:tt)*) => {
-        crate::logger::Logger::with_global_level().log($lvl, &format!($($arg)*));
-    }
+use std::str::FromStr;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Log line formats accepted on the command line.
+pub const FORMATS: &[&str] = &["text", "json"];
+
+/// Log levels accepted in the configuration, from least to most verbose.
+pub const LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Writes log records to stderr. Each record is written with a single call, so records from
+/// different threads are not interleaved.
+struct Logger {
+    format: LogFormat,
 }
 
-/// Simple logging utility struct.
-pub struct Logger(i32);
+static TEXT_LOGGER: Logger = Logger {
+    format: LogFormat::Text,
+};
+static JSON_LOGGER: Logger = Logger {
+    format: LogFormat::Json,
+};
 
 impl Logger {
-    pub fn with_global_level() -> Self {
-        unsafe { Logger(LOG_LEVEL) }
+    fn format(&self, record: &Record) -> String {
+        match self.format {
+            // Informational messages are the regular output of chisel and carry no prefix.
+            LogFormat::Text => match record.level() {
+                Level::Info => record.args().to_string(),
+                Level::Warn => format!("warning: {}", record.args()),
+                level => format!("{}: {}", level.to_string().to_lowercase(), record.args()),
+            },
+            LogFormat::Json => serde_json::json!({
+                "level": record.level().to_string().to_lowercase(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            })
+            .to_string(),
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", self.format(record));
+        }
     }
 
-    pub fn log<T: AsRef<str>>(&self, level: i32, message: T) {
-        if self.0 >= level {
-            eprintln!("{}", message.as_ref());
+    fn flush(&self) {}
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("expected one of 'text' or 'json', found '{}'", s)),
         }
     }
 }
 
-/// Set the global log level.
-// NOTE: Unsafe in a multithreaded context. Add mutex later when this is moved into the library.
-pub fn set_global_log_level(lvl: i32) {
-    unsafe {
-        LOG_LEVEL = lvl;
+/// Installs the logger with the given level and format. Only the first call installs a logger;
+/// later calls only change the level.
+pub fn init(level: LevelFilter, format: LogFormat) {
+    let logger = match format {
+        LogFormat::Text => &TEXT_LOGGER,
+        LogFormat::Json => &JSON_LOGGER,
+    };
+    let _ = log::set_logger(logger);
+    log::set_max_level(level);
+}
+
+/// Installs the logger configured by the "log.level" and "log.format" flags.
+pub fn init_from_flags(flags: &crate::options::ChiselFlags) -> Result<(), String> {
+    let level = flags.value_of("log.level").unwrap_or("info");
+    let level = LevelFilter::from_str(level)
+        .map_err(|_| format!("expected one of {}, found '{}'", LEVELS.join(", "), level))?;
+    let format = LogFormat::from_str(flags.value_of("log.format").unwrap_or("text"))?;
+    init(level, format);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use log::RecordBuilder;
+
+    use super::*;
+
+    #[test]
+    fn record_formats() {
+        let args = format_args!("Running ruleset {}", "ewasm");
+        let record = RecordBuilder::new()
+            .args(args)
+            .level(Level::Info)
+            .target("chisel::driver")
+            .build();
+
+        assert_eq!(TEXT_LOGGER.format(&record), "Running ruleset ewasm");
+
+        let line: serde_json::Value =
+            serde_json::from_str(&JSON_LOGGER.format(&record)).expect("Should be JSON");
+        assert_eq!(line["level"], "info");
+        assert_eq!(line["target"], "chisel::driver");
+        assert_eq!(line["message"], "Running ruleset ewasm");
+
+        let args = format_args!("Failed to decode input");
+        let record = RecordBuilder::new().args(args).level(Level::Debug).build();
+        assert_eq!(TEXT_LOGGER.format(&record), "debug: Failed to decode input");
+    }
+
+    #[test]
+    fn log_format_from_str() {
+        assert_eq!(LogFormat::from_str("json"), Ok(LogFormat::Json));
+        assert!(LogFormat::from_str("xml").is_err());
     }
 }
@@ -1,11 +1,14 @@
-#[macro_use]
-mod logger;
 mod cmd_oneliner;
 mod cmd_run;
 mod config;
 mod driver;
+mod input;
+mod logger;
 mod options;
+mod preprocess;
+mod report;
 mod result;
+mod schema;
 
 use std::process;
 
@@ -15,6 +18,13 @@ use cmd_oneliner::chisel_oneliner;
 use cmd_run::chisel_run;
 use options::ChiselFlags;
 
+/// Exit code when every ruleset passed.
+const EXIT_SUCCESS: i32 = 0;
+/// Exit code on errors, such as a bad configuration or a ruleset which could not be executed.
+const EXIT_ERROR: i32 = 1;
+/// Exit code when a module failed under the "error" failure policy, such as an invalid module.
+const EXIT_FAILED: i32 = 2;
+
 fn fail(code: i32, message: &str) -> ! {
     eprintln!("{}: {}", crate_name!(), message);
     process::exit(code);
@@ -35,9 +45,34 @@ pub fn main() {
             Arg::with_name("DEBUG_MESSAGES")
                 .short("d")
                 .long("debug")
-                .help("Enables debug messages")
+                .help("Enables debug messages. Equivalent to -v")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("VERBOSE")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .help("Increases the log level. May be repeated: -v for debug, -vv for trace messages")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("QUIET")
+                .short("q")
+                .long("quiet")
+                .help("Only prints errors")
+                .conflicts_with_all(&["VERBOSE", "DEBUG_MESSAGES"])
                 .global(true),
         )
+        .arg(
+            Arg::with_name("LOG_FORMAT")
+                .long("log-format")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .help("Selects the format of log messages")
+                .possible_values(logger::FORMATS)
+                .global(true)
+        )
         .arg(
             Arg::with_name("MODULES")
                 .short("m")
@@ -68,10 +103,47 @@ pub fn main() {
                 .long("output-mode")
                 .takes_value(true)
                 .help("Selects the type of output")
-                .possible_values(&["bin", "wat", "hex"])
+                .possible_values(&["bin", "wat", "hex", "hex0x", "base64", "rust", "c", "json"])
                 .global(true)
         )
-        .arg(Arg::with_name("FILE").help("File to chisel"))
+        .arg(
+            Arg::with_name("FORCE")
+                .short("f")
+                .long("force")
+                .help("Writes raw binary output to standard streams")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("DIFF")
+                .long("diff")
+                .help("Lists the imports, exports, sections and functions changed by each ruleset")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("PARANOID")
+                .long("paranoid")
+                .help("Validates the module after every module which changed it")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("REPORT")
+                .long("report")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .help("Writes a machine-readable report of the results")
+                .possible_values(report::FORMATS)
+                .global(true)
+        )
+        .arg(
+            Arg::with_name("REPORT_FILE")
+                .long("report-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("Writes the report to a file instead of stdout")
+                .requires("REPORT")
+                .global(true)
+        )
+        .arg(Arg::with_name("FILE").help("File to chisel, or '-' to read from stdin"))
         .subcommand(
             SubCommand::with_name("run")
                 .about("Runs chisel in config-driven mode.")
@@ -82,6 +154,48 @@ pub fn main() {
                         .help("Sets the configuration file in config-driven mode.")
                         .value_name("PATH")
                         .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("JOBS")
+                        .short("j")
+                        .long("jobs")
+                        .help("Runs up to N rulesets in parallel. Results are reported in configuration order.")
+                        .value_name("N")
+                        .takes_value(true)
+                        .validator(|value| match value.parse::<usize>() {
+                            Ok(jobs) if jobs > 0 => Ok(()),
+                            _ => Err("expected a positive integer".to_string()),
+                        }),
+                )
+                .arg(
+                    Arg::with_name("WATCH")
+                        .short("w")
+                        .long("watch")
+                        .help("Keeps running, and re-runs rulesets when their input files or the configuration change."),
+                )
+                .arg(
+                    Arg::with_name("PROFILE")
+                        .short("p")
+                        .long("profile")
+                        .help("Selects the profile to apply to each ruleset which defines it.")
+                        .value_name("NAME")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("SET")
+                        .long("set")
+                        .help("Sets a variable used for ${NAME} interpolation in the configuration,\noverriding the environment variable of the same name.")
+                        .value_name("NAME=VALUE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .validator(|value| {
+                            if value.contains('=') {
+                                Ok(())
+                            } else {
+                                Err("expected NAME=VALUE".to_string())
+                            }
+                        }),
                 ),
         )
         .after_help("chisel runs in two primary modes: unix-style and config-driven.\n\nunix-style is invoked without a subcommand. \
@@ -93,7 +207,7 @@ pub fn main() {
 
     let mut flags = ChiselFlags::default();
 
-    match cli_matches.subcommand() {
+    let code = match cli_matches.subcommand() {
         ("run", args) => {
             if let Some(opts) = args {
                 flags.apply(opts);
@@ -107,4 +221,5 @@ pub fn main() {
         }
         (_, _) => fail(1, "invalid subcommand"),
     };
+    process::exit(code);
 }
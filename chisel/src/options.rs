@@ -8,19 +8,37 @@
 //!
 //! Options:
 //! NO_RECOVER: Forces panic on recoverable errors.
-//! VERBOSE: Enables verbose debug logging.
+//! VERBOSE: Increases the log level from info to debug, or to trace if repeated.
+//! DEBUG_MESSAGES: Sets the log level to debug. Kept for compatibility with older versions.
+//! QUIET: Sets the log level to error, and disables the summary of results.
+//! LOG_FORMAT: Sets the format of log messages: text or json.
 //! CONFIG: Overrides the configuration file path in config-driven mode.
+//! JOBS: Sets the number of rulesets executed in parallel in config-driven mode.
+//! WATCH: Re-runs rulesets on changes to their inputs or the configuration in config-driven mode.
+//! PROFILE: Selects the configuration profile in config-driven mode.
+//! SET: Sets configuration variables in config-driven mode. Stored as "run.set.NAME".
 //! MODULES: A list of modules to invoke in oneliner mode.
 //! MODULE_OPTIONS: A list of options set for the modules being invoked in oneliner mode.
 //! FILE: Sets the input file path in oneliner mode.
 //! OUTPUT_PATH: Sets the path to write any mutated binaries in oneliner mode.
 //! OUTPUT_MODE: Sets the format in which to output mutated binaries.
-//!      - wasm: default binary mode. disallowed when writing to stdout.
+//!      - bin: default binary mode. disallowed when writing to a terminal without FORCE.
 //!      - hex: write the output in hex. recommended if writing to stdout.
 //!      - wat: write the output in disassembled (.wat) format.
+//!      - hex0x: write the output in hex, prefixed with "0x".
+//!      - base64: write the output in base64.
+//!      - rust: write the output as a Rust constant byte array.
+//!      - c: write the output as a C header declaring a byte array and its length.
+//!      - json: write a JSON object with the hex-encoded output and its size.
+//! FORCE: Allows writing binary output to standard streams. Implied if stdout is not a terminal.
+//! DIFF: Lists the changes between the input and output module of each mutating ruleset.
+//! PARANOID: Validates the module after every translator or creator which changed it.
+//! REPORT: Sets the format of the machine-readable report: json, yaml or junit.
+//! REPORT_FILE: Sets the path to write the report to. Defaults to stdout.
 
 use std::collections::HashMap;
 use std::ops::Deref;
+use std::path::Path;
 
 use clap::ArgMatches;
 
@@ -50,17 +68,58 @@ impl ChiselFlags {
         }
     }
 
+    /// Returns true if raw binary output may be written to the standard stream at `path`. This is
+    /// the case if FORCE is set, or if the stream is stdout and it is not a terminal.
+    pub fn binary_allowed(&self, path: &Path) -> bool {
+        self.value_eq("output.force", "true")
+            || (path == Path::new("/dev/stdout") && !atty::is(atty::Stream::Stdout))
+    }
+
     /// Apply all flags passed from CLI
     pub fn apply(&mut self, matches: &ArgMatches) {
         if matches.is_present("NO_RECOVER") {
             self.set("util.norecover", "true");
         }
-        if matches.is_present("VERBOSE") {
-            self.set("util.debugging", "true");
+        if matches.is_present("QUIET") {
+            self.set("log.level", "error");
+        } else {
+            let verbosity =
+                matches
+                    .occurrences_of("VERBOSE")
+                    .max(if matches.is_present("DEBUG_MESSAGES") {
+                        1
+                    } else {
+                        0
+                    });
+            match verbosity {
+                0 => (),
+                1 => self.set("log.level", "debug"),
+                _ => self.set("log.level", "trace"),
+            }
+        }
+        if let Some(value) = matches.value_of("LOG_FORMAT") {
+            self.set("log.format", value);
         }
         if let Some(value) = matches.value_of("CONFIG") {
             self.set("run.config.path", value);
         }
+        if let Some(value) = matches.value_of("JOBS") {
+            self.set("run.jobs", value);
+        }
+        if matches.is_present("WATCH") {
+            self.set("run.watch", "true");
+        }
+        if let Some(value) = matches.value_of("PROFILE") {
+            self.set("run.profile", value);
+        }
+        if let Some(values) = matches.values_of("SET") {
+            for value in values {
+                let mut pair = value.splitn(2, '=');
+                let name = pair.next().expect("splitn yields at least one element");
+                let value = pair.next().expect("CLI parser ensures an equals sign");
+                self.set(&format!("run.set.{}", name), value);
+            }
+        }
         if let Some(values) = matches.values_of("MODULES") {
             let values_collected = values.fold(String::new(), |mut acc, val| {
                 acc.push_str(&format!("{},", val));
@@ -83,12 +142,34 @@ impl ChiselFlags {
         }
         if let Some(value) = matches.value_of("OUTPUT_MODE") {
             match value {
-                val @ "bin" | val @ "wat" | val @ "hex" => {
+                val @ "bin"
+                | val @ "wat"
+                | val @ "hex"
+                | val @ "hex0x"
+                | val @ "base64"
+                | val @ "rust"
+                | val @ "c"
+                | val @ "json" => {
                     self.set("output.mode", val);
                 }
-                _ => panic!("CLI parser only accepts 'bin', 'wat', or 'hex'"),
+                _ => panic!("CLI parser only accepts the listed output modes"),
             }
         }
+        if matches.is_present("FORCE") {
+            self.set("output.force", "true");
+        }
+        if matches.is_present("DIFF") {
+            self.set("output.diff", "true");
+        }
+        if matches.is_present("PARANOID") {
+            self.set("run.paranoid", "true");
+        }
+        if let Some(value) = matches.value_of("REPORT") {
+            self.set("report.format", value);
+        }
+        if let Some(value) = matches.value_of("REPORT_FILE") {
+            self.set("report.path", value);
+        }
     }
 }
 
@@ -105,7 +186,8 @@ impl Default for ChiselFlags {
         let mut ret = ChiselFlags(HashMap::new());
 
         ret.set("util.norecover", "false");
-        ret.set("util.debugging", "false");
+        ret.set("log.level", "info");
+        ret.set("log.format", "text");
         ret.set("output.mode", "bin");
         ret.set("run.config.path", "./chisel.yml");
         ret
@@ -120,7 +202,8 @@ mod tests {
     fn default_options() {
         let options = ChiselFlags::default();
         assert!(options.value_eq("util.norecover", "false"));
-        assert!(options.value_eq("util.debugging", "false"));
+        assert!(options.value_eq("log.level", "info"));
+        assert!(options.value_eq("log.format", "text"));
         assert!(options.value_eq("output.mode", "bin"));
         assert!(options.value_eq("run.config.path", "./chisel.yml"));
     }
@@ -0,0 +1,459 @@
+//! Preprocessing of YAML configurations.
+//! Before rulesets are validated and converted into a ChiselConfig, the Preprocessor resolves the
+//! following on the raw YAML:
+//! - Inheritance: a ruleset may name a base ruleset with `extends`. Its keys are merged over the
+//!   keys of the base, and a `modules` list in the derived ruleset replaces the modules of the
+//!   base. Rulesets whose names begin with a dot are only used as bases and are not executed.
+//! - Profiles: a ruleset may define named sets of overrides under `profiles`. The selected
+//!   profile is merged over each ruleset in the inheritance chain.
+//! - Variables: `${NAME}` in any string value is replaced with a variable set on the command line,
+//!   or otherwise with the environment variable of that name. `${NAME:-default}` falls back to a
+//!   default value, and `$$` produces a literal dollar sign.
+
+use std::collections::HashMap;
+use std::env;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::config::ConfigError;
+use crate::schema;
+
+/// Resolves inheritance, profiles and variables in a configuration.
+#[derive(Default)]
+pub struct Preprocessor {
+    profile: Option<String>,
+    variables: HashMap<String, String>,
+}
+
+impl Preprocessor {
+    /// Selects the profile to apply to every ruleset that defines it.
+    pub fn set_profile(&mut self, profile: &str) {
+        self.profile = Some(profile.to_string());
+    }
+
+    /// Sets a variable, overriding any environment variable of the same name.
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    /// Resolves the rulesets in a top-level configuration mapping. Returns the rulesets to be
+    /// executed, in order, with inheritance, profiles and variables applied.
+    pub fn apply(&self, rulesets: &Mapping) -> Result<Vec<(String, Value)>, ConfigError> {
+        // Validate ruleset names and bodies before looking anything up by name.
+        let mut by_name: HashMap<&str, &Mapping> = HashMap::new();
+        let mut names = Vec::new();
+        for (key, val) in rulesets.iter() {
+            let name = match key {
+                Value::String(name) => name.as_str(),
+                other => {
+                    return Err(ConfigError::new(format!(
+                        "expected ruleset name to be a string, found {}",
+                        schema::type_name(other)
+                    )))
+                }
+            };
+            match val {
+                Value::Mapping(body) => {
+                    by_name.insert(name, body);
+                    names.push(name);
+                }
+                other => {
+                    return Err(ConfigError::new(format!(
+                        "expected ruleset to be a mapping of options and modules, found {}",
+                        schema::type_name(other)
+                    ))
+                    .within(name))
+                }
+            }
+        }
+
+        let mut profile_found = false;
+        let mut ret = Vec::new();
+        for name in names {
+            let (resolved, found) = self
+                .resolve(name, &by_name, &mut Vec::new())
+                .map_err(|e| e.within(name))?;
+            profile_found |= found;
+
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let resolved = self
+                .interpolate(&Value::Mapping(resolved))
+                .map_err(|e| e.within(name))?;
+            ret.push((name.to_string(), resolved));
+        }
+
+        match &self.profile {
+            Some(profile) if !profile_found => Err(ConfigError::new(format!(
+                "profile '{}' is not defined by any ruleset",
+                profile
+            ))),
+            _ => Ok(ret),
+        }
+    }
+
+    /// Resolves the inheritance chain of a ruleset, applying the selected profile at each level.
+    /// Returns the merged ruleset and whether the profile was defined anywhere in the chain.
+    fn resolve<'a>(
+        &self,
+        name: &'a str,
+        rulesets: &HashMap<&'a str, &'a Mapping>,
+        chain: &mut Vec<&'a str>,
+    ) -> Result<(Mapping, bool), ConfigError> {
+        if chain.contains(&name) {
+            chain.push(name);
+            return Err(ConfigError::new(format!(
+                "inheritance cycle: {}",
+                chain.join(" -> ")
+            )));
+        }
+        chain.push(name);
+
+        let body = rulesets[name];
+        let (own, mut found) = self.apply_profile(body)?;
+
+        let extends = match body.get(&Value::String(schema::EXTENDS.to_string())) {
+            None => return Ok((own, found)),
+            Some(Value::String(base)) => base.as_str(),
+            Some(other) => {
+                return Err(ConfigError::new(format!(
+                    "expected name of base ruleset, found {}",
+                    schema::type_name(other)
+                ))
+                .within(schema::EXTENDS))
+            }
+        };
+
+        let base = match rulesets.get_key_value(extends) {
+            Some((base, _)) => *base,
+            None => {
+                return Err(
+                    ConfigError::new(format!("base ruleset '{}' does not exist", extends))
+                        .within(schema::EXTENDS),
+                )
+            }
+        };
+
+        let (base, base_found) = self.resolve(base, rulesets, chain)?;
+        found |= base_found;
+        Ok((merge(&base, &own), found))
+    }
+
+    /// Merges the selected profile of a ruleset over it, if the ruleset defines that profile.
+    /// Returns the ruleset without its `extends` and `profiles` keys, and whether the profile
+    /// was found.
+    fn apply_profile(&self, body: &Mapping) -> Result<(Mapping, bool), ConfigError> {
+        let own: Mapping = body
+            .iter()
+            .filter(|(key, _)| {
+                key.as_str() != Some(schema::EXTENDS) && key.as_str() != Some(schema::PROFILES)
+            })
+            .map(|(key, val)| (key.clone(), val.clone()))
+            .collect();
+
+        let profiles = match body.get(&Value::String(schema::PROFILES.to_string())) {
+            None => return Ok((own, false)),
+            Some(Value::Mapping(profiles)) => profiles,
+            Some(other) => {
+                return Err(ConfigError::new(format!(
+                    "expected a mapping of profile names to overrides, found {}",
+                    schema::type_name(other)
+                ))
+                .within(schema::PROFILES))
+            }
+        };
+
+        let selected = match &self.profile {
+            Some(profile) => profile,
+            None => return Ok((own, false)),
+        };
+
+        match profiles.get(&Value::String(selected.to_string())) {
+            None => Ok((own, false)),
+            Some(Value::Mapping(overrides)) => {
+                if let Some(key) = overrides.iter().find_map(|(key, _)| match key.as_str() {
+                    Some(key) if key == schema::EXTENDS || key == schema::PROFILES => Some(key),
+                    _ => None,
+                }) {
+                    return Err(ConfigError::new("not allowed within a profile")
+                        .within(key)
+                        .within(selected)
+                        .within(schema::PROFILES));
+                }
+                Ok((merge(&own, overrides), true))
+            }
+            Some(Value::Null) => Ok((own, true)),
+            Some(other) => Err(ConfigError::new(format!(
+                "expected a mapping of overrides, found {}",
+                schema::type_name(other)
+            ))
+            .within(selected)
+            .within(schema::PROFILES)),
+        }
+    }
+
+    /// Replaces variables in every string value, recursively.
+    fn interpolate(&self, value: &Value) -> Result<Value, ConfigError> {
+        match value {
+            Value::String(s) => Ok(Value::String(self.interpolate_str(s)?)),
+            Value::Sequence(elements) => {
+                let mut ret = Vec::new();
+                for (idx, element) in elements.iter().enumerate() {
+                    ret.push(
+                        self.interpolate(element)
+                            .map_err(|e| e.within(&format!("[{}]", idx)))?,
+                    );
+                }
+                Ok(Value::Sequence(ret))
+            }
+            Value::Mapping(mapping) => {
+                let mut ret = Mapping::new();
+                for (key, val) in mapping.iter() {
+                    let val = match key {
+                        Value::String(k) => self.interpolate(val).map_err(|e| e.within(k))?,
+                        _ => self.interpolate(val)?,
+                    };
+                    ret.insert(key.clone(), val);
+                }
+                Ok(Value::Mapping(ret))
+            }
+            other => Ok(other.clone()),
+        }
+    }
+
+    fn interpolate_str(&self, s: &str) -> Result<String, ConfigError> {
+        let mut ret = String::new();
+        let mut rest = s;
+
+        while let Some(idx) = rest.find('$') {
+            ret.push_str(&rest[..idx]);
+            rest = &rest[idx..];
+
+            if rest.starts_with("$$") {
+                ret.push('$');
+                rest = &rest[2..];
+            } else if rest.starts_with("${") {
+                let end = rest
+                    .find('}')
+                    .ok_or_else(|| ConfigError::new(format!("unterminated variable in '{}'", s)))?;
+                let (name, default) = match rest[2..end].find(":-") {
+                    Some(split) => (&rest[2..2 + split], Some(&rest[2 + split + 2..end])),
+                    None => (&rest[2..end], None),
+                };
+
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    return Err(ConfigError::new(format!(
+                        "invalid variable name '{}'",
+                        name
+                    )));
+                }
+
+                match (self.variable(name), default) {
+                    (Some(value), _) => ret.push_str(&value),
+                    (None, Some(default)) => ret.push_str(default),
+                    (None, None) => {
+                        return Err(ConfigError::new(format!("variable '{}' is not set", name)))
+                    }
+                }
+                rest = &rest[end + 1..];
+            } else {
+                ret.push('$');
+                rest = &rest[1..];
+            }
+        }
+
+        ret.push_str(rest);
+        Ok(ret)
+    }
+
+    fn variable(&self, name: &str) -> Option<String> {
+        match self.variables.get(name) {
+            Some(value) => Some(value.to_string()),
+            None => env::var(name).ok(),
+        }
+    }
+}
+
+/// Merges the keys of a derived ruleset over those of its base. Keys keep the position they have
+/// in the base, and new keys are appended. A `modules` list in the derived ruleset replaces all
+/// modules of the base.
+fn merge(base: &Mapping, derived: &Mapping) -> Mapping {
+    let module_list = Value::String(schema::MODULE_LIST.to_string());
+    let replaces_modules = derived.contains_key(&module_list);
+
+    let mut ret = Mapping::new();
+    for (key, val) in base.iter() {
+        let is_module = match key.as_str() {
+            Some(key) => schema::ruleset_option(key).is_none(),
+            None => false,
+        };
+        if replaces_modules && is_module {
+            continue;
+        }
+        ret.insert(key.clone(), derived.get(key).unwrap_or(val).clone());
+    }
+    for (key, val) in derived.iter() {
+        if !ret.contains_key(key) {
+            ret.insert(key.clone(), val.clone());
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rulesets(source: &str) -> Mapping {
+        match serde_yaml::from_str::<Value>(source).expect("Should be valid YAML") {
+            Value::Mapping(mapping) => mapping,
+            _ => panic!("Should be a mapping"),
+        }
+    }
+
+    fn get<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+        value
+            .as_mapping()
+            .expect("Should be a mapping")
+            .get(&Value::String(key.to_string()))
+    }
+
+    const CONFIG: &str = "\
+.base:
+  output: \"out/${NAME}.wasm\"
+  remapimports:
+    preset: \"ewasm\"
+  verifyimports:
+    preset: \"ewasm\"
+  profiles:
+    release:
+      snip:
+child:
+  extends: \".base\"
+  file: \"${NAME}.wasm\"
+  verifyimports:
+    preset: \"eth2\"
+  profiles:
+    debug:
+      output: \"debug.wasm\"
+";
+
+    #[test]
+    fn inheritance() {
+        let mut preprocessor = Preprocessor::default();
+        preprocessor.set_variable("NAME", "token");
+
+        let resolved = preprocessor.apply(&rulesets(CONFIG)).unwrap();
+        // Hidden rulesets are not executed.
+        assert_eq!(resolved.len(), 1);
+        let (name, child) = &resolved[0];
+        assert_eq!(name, "child");
+
+        let keys: Vec<&str> = child
+            .as_mapping()
+            .unwrap()
+            .iter()
+            .map(|(key, _)| key.as_str().unwrap())
+            .collect();
+        assert_eq!(
+            keys,
+            vec!["output", "remapimports", "verifyimports", "file"]
+        );
+        assert_eq!(
+            get(child, "output"),
+            Some(&Value::String("out/token.wasm".to_string()))
+        );
+        assert_eq!(
+            get(get(child, "verifyimports").unwrap(), "preset"),
+            Some(&Value::String("eth2".to_string()))
+        );
+    }
+
+    #[test]
+    fn profiles() {
+        let mut preprocessor = Preprocessor::default();
+        preprocessor.set_variable("NAME", "token");
+        preprocessor.set_profile("release");
+        let resolved = preprocessor.apply(&rulesets(CONFIG)).unwrap();
+        assert!(get(&resolved[0].1, "snip").is_some());
+        assert_eq!(
+            get(&resolved[0].1, "output"),
+            Some(&Value::String("out/token.wasm".to_string()))
+        );
+
+        preprocessor.set_profile("debug");
+        let resolved = preprocessor.apply(&rulesets(CONFIG)).unwrap();
+        assert!(get(&resolved[0].1, "snip").is_none());
+        assert_eq!(
+            get(&resolved[0].1, "output"),
+            Some(&Value::String("debug.wasm".to_string()))
+        );
+
+        preprocessor.set_profile("relase");
+        let err = preprocessor.apply(&rulesets(CONFIG)).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "profile 'relase' is not defined by any ruleset"
+        );
+    }
+
+    #[test]
+    fn module_list_replaces_base_modules() {
+        let config = "\
+base:
+  file: \"a.wasm\"
+  remapimports:
+    preset: \"ewasm\"
+child:
+  extends: base
+  modules:
+    - snip
+";
+        let resolved = Preprocessor::default().apply(&rulesets(config)).unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert!(get(&resolved[1].1, "remapimports").is_none());
+        assert!(get(&resolved[1].1, "modules").is_some());
+        assert!(get(&resolved[1].1, "file").is_some());
+    }
+
+    #[test]
+    fn inheritance_errors() {
+        let err = Preprocessor::default()
+            .apply(&rulesets("a:\n  extends: b\nb:\n  extends: a\n"))
+            .unwrap_err();
+        assert_eq!(err.message(), "inheritance cycle: a -> b -> a");
+        assert_eq!(err.ruleset(), Some("a"));
+
+        let err = Preprocessor::default()
+            .apply(&rulesets("a:\n  extends: c\n"))
+            .unwrap_err();
+        assert_eq!(err.message(), "base ruleset 'c' does not exist");
+        assert_eq!(err.path(), &["a".to_string(), "extends".to_string()]);
+    }
+
+    #[test]
+    fn variables() {
+        let mut preprocessor = Preprocessor::default();
+        preprocessor.set_variable("A", "x");
+
+        assert_eq!(preprocessor.interpolate_str("${A}/${A}").unwrap(), "x/x");
+        assert_eq!(preprocessor.interpolate_str("$${A} $5").unwrap(), "${A} $5");
+        assert_eq!(
+            preprocessor
+                .interpolate_str("${CHISEL_TEST_UNSET_VARIABLE:-dflt}")
+                .unwrap(),
+            "dflt"
+        );
+        assert_eq!(
+            preprocessor
+                .interpolate_str("${CHISEL_TEST_UNSET_VARIABLE}")
+                .unwrap_err()
+                .message(),
+            "variable 'CHISEL_TEST_UNSET_VARIABLE' is not set"
+        );
+        assert!(preprocessor.interpolate_str("${A").is_err());
+        assert!(preprocessor.interpolate_str("${A B}").is_err());
+    }
+}
@@ -0,0 +1,376 @@
+//! Machine-readable chisel execution reports.
+//! This module renders a ChiselResult as JSON, YAML or JUnit XML, for consumption by CI systems
+//! and other tools which would otherwise have to parse the colored text output.
+//! The JSON and YAML reports share the same structure: a list of rulesets, each with its input
+//! and output paths, whether the module was mutated, written or withheld, whether it passed, the error
+//! which stopped the ruleset if any, and the result and failure policy of every module executed.
+//! Translators which changed the module also list their changes and the resulting size delta, if
+//! they were asked to.
+
+use std::error::Error;
+use std::fs::write;
+use std::io::{self, Write};
+
+use serde_derive::Serialize;
+
+use crate::config::FailurePolicy;
+use crate::result::{error_chain, ChiselResult, ModuleResult, RulesetResult};
+
+/// Report formats accepted on the command line.
+pub const FORMATS: &[&str] = &["json", "yaml", "junit"];
+
+#[derive(Serialize)]
+struct Report<'a> {
+    version: &'static str,
+    success: bool,
+    rulesets: Vec<RulesetReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct RulesetReport<'a> {
+    name: &'a str,
+    input: String,
+    output: String,
+    mutated: bool,
+    written: bool,
+    withheld: bool,
+    passed: bool,
+    error: Option<&'a str>,
+    modules: Vec<ModuleReport<'a>>,
+    /// Changes between the input and output module, if a diff was requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changes: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct ModuleReport<'a> {
+    name: &'a str,
+    kind: &'static str,
+    status: &'static str,
+    policy: FailurePolicy,
+    failed: bool,
+    error: Option<String>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    notes: &'a [String],
+    /// Changes made to the module, if the module is a translator which changed it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changes: Option<Vec<String>>,
+    /// Size delta of the module in bytes, if the translator measured it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size_delta: Option<isize>,
+}
+
+impl<'a> ModuleReport<'a> {
+    fn new(result: &'a ModuleResult, policy: FailurePolicy, notes: &'a [String]) -> Self {
+        let failed = result.failed();
+        let changes = result.changes();
+        let (name, kind, result, statuses) = match result {
+            ModuleResult::Creator(name, result) => (name, "creator", result, ("ok", "failed")),
+            ModuleResult::Translator(name, result, _) => {
+                (name, "translator", result, ("mutated", "unchanged"))
+            }
+            ModuleResult::Validator(name, result) => {
+                (name, "validator", result, ("valid", "invalid"))
+            }
+        };
+
+        ModuleReport {
+            name,
+            kind,
+            status: match result {
+                Ok(true) => statuses.0,
+                Ok(false) => statuses.1,
+                Err(_) => "error",
+            },
+            policy,
+            failed,
+            error: result.as_ref().err().map(|e| error_chain(e)),
+            notes,
+            changes: changes
+                .map(|changes| changes.changes().iter().map(|c| c.to_string()).collect()),
+            size_delta: changes.and_then(|changes| changes.size_delta()),
+        }
+    }
+
+    /// Returns the JUnit element and message describing the failure of the module, if it failed
+    /// the run, followed by the notes on the result, one per line. Failures of modules with a
+    /// policy other than "error" are not reported.
+    fn failure(&self) -> Option<(&'static str, String)> {
+        if !self.failed || self.policy != FailurePolicy::Error {
+            return None;
+        }
+        let (element, message) = match &self.error {
+            Some(error) => ("error", error.clone()),
+            None => ("failure", self.status.to_uppercase()),
+        };
+        let lines: Vec<&str> = std::iter::once(message.as_str())
+            .chain(self.notes.iter().map(|note| note.as_str()))
+            .collect();
+        Some((element, lines.join("\n")))
+    }
+}
+
+impl<'a> From<&'a RulesetResult> for RulesetReport<'a> {
+    fn from(result: &'a RulesetResult) -> Self {
+        RulesetReport {
+            name: result.name(),
+            input: result.input_path().display().to_string(),
+            output: result.output_path().display().to_string(),
+            mutated: result.mutated(),
+            written: result.written(),
+            withheld: result.withheld(),
+            passed: result.passed(),
+            error: result.error(),
+            modules: result
+                .results()
+                .iter()
+                .enumerate()
+                .map(|(idx, module)| {
+                    ModuleReport::new(module, result.policy(idx), result.notes(idx))
+                })
+                .collect(),
+            changes: result
+                .diff()
+                .map(|diff| diff.changes().iter().map(|c| c.to_string()).collect()),
+        }
+    }
+}
+
+impl<'a> From<&'a ChiselResult> for Report<'a> {
+    fn from(result: &'a ChiselResult) -> Self {
+        Report {
+            version: env!("CARGO_PKG_VERSION"),
+            success: result.passed(),
+            rulesets: result.rulesets().iter().map(RulesetReport::from).collect(),
+        }
+    }
+}
+
+/// Renders a report of the results in the given format.
+pub fn render(result: &ChiselResult, format: &str) -> Result<String, Box<dyn Error>> {
+    let report = Report::from(result);
+    match format {
+        "json" => Ok(serde_json::to_string_pretty(&report)? + "\n"),
+        "yaml" => Ok(serde_yaml::to_string(&report)? + "\n"),
+        "junit" => Ok(junit(&report)),
+        _ => Err("invalid report format".into()),
+    }
+}
+
+/// Renders a report of the results and writes it to the given path, or to stdout if no path is
+/// given.
+pub fn write_report(
+    result: &ChiselResult,
+    format: &str,
+    path: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let report = render(result, format)?;
+    match path {
+        Some(path) => write(path, report)?,
+        None => io::stdout().write_all(report.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Renders a report as JUnit XML. Each ruleset is a test suite and each module a test case.
+/// A ruleset which stopped with an error gets an additional test case carrying the error.
+fn junit(report: &Report) -> String {
+    let mut suites = String::new();
+    let (mut total_tests, mut total_failures, mut total_errors) = (0, 0, 0);
+
+    for ruleset in report.rulesets.iter() {
+        let suite_name = if ruleset.input.is_empty() {
+            ruleset.name.to_string()
+        } else {
+            format!("{} ({})", ruleset.name, ruleset.input)
+        };
+
+        let mut cases = String::new();
+        let (mut failures, mut errors) = (0, 0);
+        for module in ruleset.modules.iter() {
+            cases.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\"",
+                escape(ruleset.name),
+                escape(module.name)
+            ));
+            match module.failure() {
+                Some((element, message)) => {
+                    if element == "error" {
+                        errors += 1;
+                    } else {
+                        failures += 1;
+                    }
+                    cases.push_str(&format!(
+                        ">\n      <{} message=\"{}\"/>\n    </testcase>\n",
+                        element,
+                        escape(&message)
+                    ));
+                }
+                None => cases.push_str("/>\n"),
+            }
+        }
+        if let Some(error) = ruleset.error {
+            errors += 1;
+            cases.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"ruleset\">\n      <error message=\"{}\"/>\n    </testcase>\n",
+                escape(ruleset.name),
+                escape(error)
+            ));
+        }
+
+        let tests = ruleset.modules.len() + ruleset.error.map_or(0, |_| 1);
+        suites.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">\n{}  </testsuite>\n",
+            escape(&suite_name),
+            tests,
+            failures,
+            errors,
+            cases
+        ));
+        total_tests += tests;
+        total_failures += failures;
+        total_errors += errors;
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"chisel\" tests=\"{}\" failures=\"{}\" errors=\"{}\">\n{}</testsuites>\n",
+        total_tests, total_failures, total_errors, suites
+    )
+}
+
+/// Escapes a string for use in an XML attribute.
+fn escape(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            '\'' => ret.push_str("&apos;"),
+            '\n' => ret.push_str("&#10;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use libchisel::trimexports::TrimExports;
+    use libchisel::{Module, ModuleError, ModulePreset, ModuleTranslator};
+
+    use super::*;
+
+    fn sample() -> ChiselResult {
+        let mut result = ChiselResult::new();
+
+        let mut ruleset = RulesetResult::new("ewasm".to_string());
+        ruleset.set_input_path(PathBuf::from("in.wasm"));
+        ruleset.set_output_path(PathBuf::from("out.wasm"));
+        let mut module = Module::from_bytes(
+            wat::parse_str(r#"(module (func $main (export "main")) (export "foo" (func $main)))"#)
+                .unwrap(),
+        )
+        .unwrap();
+        let changes = TrimExports::with_preset("ewasm")
+            .unwrap()
+            .translate_with_changes(&mut module)
+            .unwrap();
+        assert!(changes.is_some());
+        ruleset.results_mut().push(ModuleResult::Translator(
+            "trimexports".to_string(),
+            Ok(true),
+            changes,
+        ));
+        ruleset.push_result_with_notes(
+            ModuleResult::Validator("verifyimports".to_string(), Ok(false)),
+            FailurePolicy::Error,
+            vec!["ethereum.foo: unknown import".to_string()],
+        );
+        ruleset.results_mut().push(ModuleResult::Validator(
+            "verifyexports".to_string(),
+            Err(ModuleError::Custom("bad <export>".to_string())),
+        ));
+        ruleset.push_result(
+            ModuleResult::Validator("checkfloat".to_string(), Ok(false)),
+            FailurePolicy::Warn,
+        );
+        result.rulesets_mut().push(ruleset);
+
+        let mut failed = RulesetResult::new("missing".to_string());
+        failed.set_error("in 'missing': failed to resolve path 'x.wasm'".to_string());
+        result.rulesets_mut().push(failed);
+
+        result
+    }
+
+    #[test]
+    fn json_report() {
+        let report = render(&sample(), "json").expect("Should render");
+        let report: serde_json::Value = serde_json::from_str(&report).expect("Should be JSON");
+
+        assert_eq!(report["success"], false);
+        assert_eq!(report["rulesets"][0]["name"], "ewasm");
+        assert_eq!(report["rulesets"][0]["input"], "in.wasm");
+        assert_eq!(report["rulesets"][0]["mutated"], true);
+        assert_eq!(report["rulesets"][0]["written"], false);
+        assert_eq!(report["rulesets"][0]["modules"][0]["status"], "mutated");
+        assert_eq!(report["rulesets"][0]["modules"][1]["status"], "invalid");
+        assert_eq!(
+            report["rulesets"][0]["modules"][1]["notes"][0],
+            "ethereum.foo: unknown import"
+        );
+        assert!(report["rulesets"][0]["modules"][0].get("notes").is_none());
+        assert_eq!(
+            report["rulesets"][0]["modules"][0]["changes"][0],
+            "- export foo"
+        );
+        assert!(report["rulesets"][0]["modules"][0]["size_delta"].as_i64() < Some(0));
+        assert!(report["rulesets"][0]["modules"][1].get("changes").is_none());
+        assert_eq!(report["rulesets"][0]["modules"][2]["kind"], "validator");
+        assert_eq!(report["rulesets"][0]["modules"][2]["error"], "bad <export>");
+        assert_eq!(report["rulesets"][0]["modules"][3]["failed"], true);
+        assert_eq!(report["rulesets"][0]["modules"][3]["policy"], "warn");
+        assert_eq!(report["rulesets"][0]["passed"], false);
+        assert_eq!(
+            report["rulesets"][1]["error"],
+            "in 'missing': failed to resolve path 'x.wasm'"
+        );
+    }
+
+    #[test]
+    fn yaml_report() {
+        let report = render(&sample(), "yaml").expect("Should render");
+        let report: serde_yaml::Value = serde_yaml::from_str(&report).expect("Should be YAML");
+
+        assert_eq!(
+            report["rulesets"][0]["modules"][0]["name"],
+            serde_yaml::Value::String("trimexports".to_string())
+        );
+        assert_eq!(report["success"], serde_yaml::Value::Bool(false));
+    }
+
+    #[test]
+    fn junit_report() {
+        let report = render(&sample(), "junit").expect("Should render");
+
+        assert!(report.starts_with("<?xml"));
+        assert!(
+            report.contains("<testsuites name=\"chisel\" tests=\"5\" failures=\"1\" errors=\"2\">")
+        );
+        assert!(report.contains(
+            "<testsuite name=\"ewasm (in.wasm)\" tests=\"4\" failures=\"1\" errors=\"1\">"
+        ));
+        assert!(report.contains("<failure message=\"INVALID&#10;ethereum.foo: unknown import\"/>"));
+        assert!(report.contains("<error message=\"bad &lt;export&gt;\"/>"));
+        assert!(report.contains("<testcase classname=\"missing\" name=\"ruleset\">"));
+    }
+
+    #[test]
+    fn invalid_format() {
+        assert!(render(&ChiselResult::new(), "xml").is_err());
+    }
+}
@@ -6,16 +6,21 @@
 //! ModuleResults. This makes it structurally identical to the ChiselConfig for the driver
 //! execution which produced it.
 //! RulesetResult also implements utilities for writing the resulting Wasm module to file, if the
-//! driver performed any transformations.
+//! driver performed any transformations, in binary, text or one of several encodings for embedding
+//! the module in other sources. Rulesets are transactional by default: if the ruleset did
+//! not pass, the output is withheld rather than written.
 
 use std::error::Error;
 use std::fmt::{self, Display};
-use std::fs::write;
-use std::path::PathBuf;
+use std::fs::{create_dir_all, write};
+use std::path::{Path, PathBuf};
 
 use ansi_term::Colour::{Green, Red, Yellow};
 
-use libchisel::{Module, ModuleError};
+use libchisel::diff::{Changes, ModuleDiff};
+use libchisel::{proposals, Module, ModuleError};
+
+use crate::config::FailurePolicy;
 
 #[derive(Clone)]
 /// Main result structure returned by ChiselDriver, containing a manifest of modules executed and
@@ -27,16 +32,30 @@ pub struct ChiselResult(Vec<RulesetResult>);
 pub struct RulesetResult {
     ruleset_name: String,
     results: Vec<ModuleResult>,
+    /// Failure policy of each module result, by index. Results without a policy use the default.
+    policies: Vec<FailurePolicy>,
+    /// Notes on each module result, by index, such as the locations a validator objected to.
+    notes: Vec<Vec<String>>,
+    input_path: PathBuf,
     output_path: PathBuf,
     output_module: Option<Module>,
+    /// If set, the output is only written if the ruleset passed.
+    transactional: bool,
+    /// If set, raw binary output may be written to the standard streams.
+    force: bool,
+    written: bool,
+    withheld: bool,
+    error: Option<String>,
+    /// Differences between the input and output modules, if requested and the module was mutated.
+    diff: Option<ModuleDiff>,
 }
 
 #[derive(Clone)]
 /// Individual module execution result. Left-hand field is the module name, and left-hand is the
-/// return value.
+/// return value. Translators also carry the changes they made, if these were requested.
 pub enum ModuleResult {
     Creator(String, Result<bool, ModuleError>),
-    Translator(String, Result<bool, ModuleError>),
+    Translator(String, Result<bool, ModuleError>, Option<Changes>),
     Validator(String, Result<bool, ModuleError>),
 }
 
@@ -52,6 +71,18 @@ impl ChiselResult {
     pub fn rulesets(&self) -> &Vec<RulesetResult> {
         &self.0
     }
+
+    /// Returns true if every ruleset passed.
+    pub fn passed(&self) -> bool {
+        self.0.iter().all(|ruleset_result| ruleset_result.passed())
+    }
+
+    /// Returns true if any ruleset stopped with an error.
+    pub fn has_errors(&self) -> bool {
+        self.0
+            .iter()
+            .any(|ruleset_result| ruleset_result.error().is_some())
+    }
 }
 
 impl RulesetResult {
@@ -59,8 +90,17 @@ impl RulesetResult {
         RulesetResult {
             ruleset_name: name,
             results: Vec::new(),
+            policies: Vec::new(),
+            notes: Vec::new(),
+            input_path: PathBuf::new(),
             output_path: PathBuf::new(),
             output_module: None,
+            transactional: true,
+            force: false,
+            written: false,
+            withheld: false,
+            error: None,
+            diff: None,
         }
     }
 
@@ -68,78 +108,335 @@ impl RulesetResult {
         self.ruleset_name.as_str()
     }
 
+    pub fn results(&self) -> &Vec<ModuleResult> {
+        &self.results
+    }
+
+    #[cfg(test)]
     pub fn results_mut(&mut self) -> &mut Vec<ModuleResult> {
         &mut self.results
     }
 
+    /// Records a module result along with the failure policy of the module.
+    pub fn push_result(&mut self, result: ModuleResult, policy: FailurePolicy) {
+        self.policies
+            .resize(self.results.len(), FailurePolicy::default());
+        self.policies.push(policy);
+        self.results.push(result);
+    }
+
+    /// Records a module result along with the failure policy of the module and notes on the
+    /// result.
+    pub fn push_result_with_notes(
+        &mut self,
+        result: ModuleResult,
+        policy: FailurePolicy,
+        notes: Vec<String>,
+    ) {
+        self.notes.resize(self.results.len(), Vec::new());
+        self.notes.push(notes);
+        self.push_result(result, policy);
+    }
+
+    /// Returns the notes on the module result at `idx`.
+    pub fn notes(&self, idx: usize) -> &[String] {
+        self.notes.get(idx).map_or(&[], |notes| notes.as_slice())
+    }
+
+    /// Returns the failure policy of the module result at `idx`.
+    pub fn policy(&self, idx: usize) -> FailurePolicy {
+        self.policies.get(idx).copied().unwrap_or_default()
+    }
+
+    /// Returns true if the ruleset completed, and every module which failed has a policy other
+    /// than FailurePolicy::Error.
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+            && self
+                .results
+                .iter()
+                .enumerate()
+                .all(|(idx, result)| !result.failed() || self.policy(idx) != FailurePolicy::Error)
+    }
+
+    pub fn input_path(&self) -> &PathBuf {
+        &self.input_path
+    }
+
+    pub fn set_input_path(&mut self, path: PathBuf) {
+        self.input_path = path;
+    }
+
+    pub fn output_path(&self) -> &PathBuf {
+        &self.output_path
+    }
+
     pub fn set_output_path(&mut self, path: PathBuf) {
         self.output_path = path;
     }
 
+    /// Returns true if any translator or creator in the ruleset produced a new module.
+    pub fn mutated(&self) -> bool {
+        self.results.iter().any(|result| {
+            matches!(
+                result,
+                ModuleResult::Creator(_, Ok(true)) | ModuleResult::Translator(_, Ok(true), _)
+            )
+        })
+    }
+
+    pub fn set_transactional(&mut self, transactional: bool) {
+        self.transactional = transactional;
+    }
+
+    /// Allows writing raw binary output to the standard streams.
+    pub fn set_force(&mut self, force: bool) {
+        self.force = force;
+    }
+
+    /// Returns true if the output module was withheld because the ruleset did not pass.
+    pub fn withheld(&self) -> bool {
+        self.withheld
+    }
+
+    /// Returns true if the output module was written.
+    pub fn written(&self) -> bool {
+        self.written
+    }
+
+    /// Returns the error which stopped the execution of the ruleset, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Marks the ruleset as failed. A failed ruleset has no output to write.
+    pub fn set_error(&mut self, error: String) {
+        self.output_module = None;
+        self.error = Some(error);
+    }
+
+    pub fn diff(&self) -> Option<&ModuleDiff> {
+        self.diff.as_ref()
+    }
+
+    pub fn set_diff(&mut self, diff: ModuleDiff) {
+        self.diff = Some(diff);
+    }
+
     pub fn set_output_module(&mut self, module: Module) {
         self.output_module = Some(module);
     }
 
     /// Write output module to specified file if the module was mutated.
-    /// Returns Ok(false) if there is no mutation.
+    /// Returns Ok(false) if there is no mutation, or if the ruleset is transactional and did not
+    /// pass, in which case the output is withheld.
     /// Returns error on writer error or invalid mode.
     pub fn write(&mut self, mode: &str) -> Result<bool, Box<dyn Error>> {
+        if self.transactional && !self.passed() && self.output_module.take().is_some() {
+            self.withheld = true;
+            return Ok(false);
+        }
+
         if let Some(module) = self.output_module.take() {
             let path = PathBuf::from(&self.output_path);
-            let ret = match mode {
+            let module = proposals::into_bytes(module)?;
+            let output = match mode {
                 "bin" => {
-                    if *path == PathBuf::from("/dev/stdout")
-                        || *path == PathBuf::from("/dev/stderr")
+                    if !self.force
+                        && (path == Path::new("/dev/stdout") || path == Path::new("/dev/stderr"))
                     {
                         return Err("cannot write raw binary to a standard stream".into());
                     } else {
-                        let module = module.to_bytes()?;
-                        write(path, module)
+                        module
                     }
                 }
-                "hex" => {
-                    let module = module.to_bytes()?;
-                    let hex = hex::encode(&module);
-                    write(path, hex)
-                }
-                "wat" => {
-                    let module = module.to_bytes()?;
-                    let wat = wasmprinter::print_bytes(&module)?;
-                    write(path, wat)
+                "hex" => hex::encode(&module).into_bytes(),
+                "hex0x" => format!("0x{}", hex::encode(&module)).into_bytes(),
+                "base64" => base64::encode(&module).into_bytes(),
+                "wat" => wasmprinter::print_bytes(&module)?.into_bytes(),
+                "rust" => rust_array(&self.identifier(), &module).into_bytes(),
+                "c" => c_array(&self.identifier(), &module).into_bytes(),
+                "json" => {
+                    let json = serde_json::json!({
+                        "code": format!("0x{}", hex::encode(&module)),
+                        "size": module.len(),
+                    });
+                    format!("{}\n", serde_json::to_string_pretty(&json)?).into_bytes()
                 }
                 _ => return Err("invalid mode".into()),
             };
-            match ret {
-                Ok(()) => Ok(true),
+            // Output paths rendered from a template may point into a directory which does not
+            // exist yet.
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                create_dir_all(dir).map_err(|e| {
+                    format!("failed to create directory '{}': {}", dir.display(), e)
+                })?;
+            }
+            match write(path, output) {
+                Ok(()) => {
+                    self.written = true;
+                    Ok(true)
+                }
                 Err(e) => Err(e.into()),
             }
         } else {
             Ok(false)
         }
     }
+
+    /// Returns an identifier for source-embedding output modes, derived from the stem of the
+    /// input file.
+    fn identifier(&self) -> String {
+        let stem = self
+            .input_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        let mut ret: String = stem
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if ret.is_empty() {
+            ret.push_str("wasm");
+        } else if ret.starts_with(|c: char| c.is_ascii_digit()) {
+            ret.insert(0, '_');
+        }
+        ret
+    }
+}
+
+/// Formats bytes as a comma-separated list of hex literals, 16 per line.
+fn byte_list(bytes: &[u8], indent: &str) -> String {
+    bytes
+        .chunks(16)
+        .map(|chunk| {
+            let line: Vec<String> = chunk.iter().map(|b| format!("0x{:02x}", b)).collect();
+            format!("{}{},\n", indent, line.join(", "))
+        })
+        .collect()
+}
+
+/// Renders bytes as a Rust constant byte array.
+fn rust_array(name: &str, bytes: &[u8]) -> String {
+    format!(
+        "pub const {}: [u8; {}] = [\n{}];\n",
+        name.to_uppercase(),
+        bytes.len(),
+        byte_list(bytes, "    ")
+    )
+}
+
+/// Renders bytes as a C header declaring a byte array and its length.
+fn c_array(name: &str, bytes: &[u8]) -> String {
+    let guard = format!("{}_WASM_H", name.to_uppercase());
+    format!(
+        "#ifndef {guard}\n#define {guard}\n\nstatic const unsigned char {name}[] = {{\n{bytes}}};\nstatic const unsigned int {name}_len = {len};\n\n#endif\n",
+        guard = guard,
+        name = name,
+        bytes = byte_list(bytes, "  "),
+        len = bytes.len()
+    )
+}
+
+/// Returns the message of an error followed by those of its sources, separated by colons.
+pub fn error_chain(error: &(dyn Error + 'static)) -> String {
+    let mut ret = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        ret.push_str(": ");
+        ret.push_str(&error.to_string());
+        source = error.source();
+    }
+    ret
 }
 
 impl Display for ChiselResult {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.0
-            .iter()
-            .map(|ruleset_result| write!(f, "{}", ruleset_result))
-            .fold(Ok(()), |acc, r| if r.is_err() { r } else { acc })
+        // Rulesets expanded from a glob share a name and are grouped under a single heading, with
+        // the results listed per input file.
+        let mut idx = 0;
+        while idx < self.0.len() {
+            let name = self.0[idx].name();
+            let group = self.0[idx..]
+                .iter()
+                .take_while(|ruleset_result| ruleset_result.name() == name)
+                .count();
+
+            if group == 1 {
+                write!(f, "{}", self.0[idx])?;
+            } else {
+                write!(f, "\nRuleset {}:", name)?;
+                for ruleset_result in &self.0[idx..idx + group] {
+                    write!(f, "\n  {}:", ruleset_result.input_path().display())?;
+                    ruleset_result.fmt_results(f)?;
+                }
+            }
+            idx += group;
+        }
+        Ok(())
+    }
+}
+
+impl RulesetResult {
+    /// Writes a line for each module result and for the ruleset error, if any.
+    fn fmt_results(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, module_result) in self.results.iter().enumerate() {
+            write!(f, "\n\t{}", module_result)?;
+            if module_result.failed() {
+                match self.policy(idx) {
+                    FailurePolicy::Error => (),
+                    FailurePolicy::Warn => write!(f, " {}", Yellow.paint("(warning)"))?,
+                    FailurePolicy::Ignore => write!(f, " (ignored)")?,
+                }
+            }
+            let changes = module_result.changes();
+            if let Some(size_delta) = changes.and_then(|changes| changes.size_delta()) {
+                write!(f, " ({:+} bytes)", size_delta)?;
+            }
+            for note in self.notes(idx) {
+                write!(f, "\n\t  {}", note)?;
+            }
+            for change in changes.map_or(&[][..], |changes| changes.changes()) {
+                write!(f, "\n\t  {}", change)?;
+            }
+        }
+        if let Some(diff) = self.diff() {
+            write!(f, "\n\tChanges:")?;
+            for line in diff.to_string().lines() {
+                write!(f, "\n\t  {}", line)?;
+            }
+        }
+        if let Some(error) = self.error() {
+            write!(f, "\n\t{}", Red.bold().paint(format!("ERROR; {}", error)))?;
+        }
+        Ok(())
     }
 }
 
 impl Display for RulesetResult {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let result = write!(f, "\nRuleset {}:", &self.name());
-        if let Err(e) = self
-            .results
-            .iter()
-            .map(|module_result| write!(f, "\n\t{}", module_result))
-            .fold(Ok(()), |acc, r| if r.is_err() { r } else { acc })
-        {
-            Err(e)
-        } else {
-            result
+        write!(f, "\nRuleset {}:", &self.name())?;
+        self.fmt_results(f)
+    }
+}
+
+impl ModuleResult {
+    /// Returns true if the module failed: a validator reported the module invalid, a creator
+    /// failed, or the module returned an error.
+    pub fn failed(&self) -> bool {
+        match self {
+            ModuleResult::Creator(_, result) | ModuleResult::Validator(_, result) => {
+                result != &Ok(true)
+            }
+            ModuleResult::Translator(_, result, _) => result.is_err(),
+        }
+    }
+
+    /// Returns the changes a translator made to the module, if it listed them.
+    pub fn changes(&self) -> Option<&Changes> {
+        match self {
+            ModuleResult::Translator(_, _, changes) => changes.as_ref(),
+            _ => None,
         }
     }
 }
@@ -159,10 +456,10 @@ impl Display for ModuleResult {
                             Red.paint("FAILED")
                         }
                     }
-                    Err(e) => Red.bold().paint(format!("ERROR; {}", e.description())),
+                    Err(e) => Red.bold().paint(format!("ERROR; {}", error_chain(e))),
                 }
             ),
-            ModuleResult::Translator(name, result) => write!(
+            ModuleResult::Translator(name, result, _) => write!(
                 f,
                 "Translator {}: {}",
                 name,
@@ -174,7 +471,7 @@ impl Display for ModuleResult {
                             Green.paint("NO CHANGE")
                         }
                     }
-                    Err(e) => Red.bold().paint(format!("ERROR; {}", e.description())),
+                    Err(e) => Red.bold().paint(format!("ERROR; {}", error_chain(e))),
                 }
             ),
             ModuleResult::Validator(name, result) => write!(
@@ -189,7 +486,7 @@ impl Display for ModuleResult {
                             Red.paint("INVALID")
                         }
                     }
-                    Err(e) => Red.bold().paint(format!("ERROR; {}", e.description())),
+                    Err(e) => Red.bold().paint(format!("ERROR; {}", error_chain(e))),
                 }
             ),
         }
@@ -263,4 +560,118 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(result.expect("Should be Ok"), false);
     }
+
+    #[test]
+    fn display_groups_expanded_rulesets() {
+        let mut result = ChiselResult::new();
+        for file in &["a.wasm", "b.wasm"] {
+            let mut ruleset_result = RulesetResult::new("contracts".to_string());
+            ruleset_result.set_input_path(PathBuf::from(file));
+            result.rulesets_mut().push(ruleset_result);
+        }
+        result
+            .rulesets_mut()
+            .push(RulesetResult::new("single".to_string()));
+
+        assert_eq!(
+            format!("{}", result),
+            "\nRuleset contracts:\n  a.wasm:\n  b.wasm:\nRuleset single:"
+        );
+    }
+
+    #[test]
+    fn writer_withholds_failed_ruleset() {
+        let mut ruleset_result = {
+            let mut result = RulesetResult::new("Test".to_string());
+            result.set_output_module(Module::default());
+            result.set_output_path(PathBuf::from("/dev/stdout"));
+            result.push_result(
+                ModuleResult::Validator("verifyimports".to_string(), Ok(false)),
+                FailurePolicy::Error,
+            );
+            result
+        };
+
+        let result = ruleset_result.write("hex");
+        assert_eq!(result.expect("Should be Ok"), false);
+        assert!(ruleset_result.withheld());
+        assert!(!ruleset_result.written());
+    }
+
+    #[test]
+    fn writer_non_transactional() {
+        let mut ruleset_result = {
+            let mut result = RulesetResult::new("Test".to_string());
+            result.set_output_module(Module::default());
+            result.set_output_path(PathBuf::from("/dev/stdout"));
+            result.set_transactional(false);
+            result.push_result(
+                ModuleResult::Validator("verifyimports".to_string(), Ok(false)),
+                FailurePolicy::Error,
+            );
+            result
+        };
+
+        let result = ruleset_result.write("hex");
+        assert_eq!(result.expect("Should be Ok"), true);
+        assert!(!ruleset_result.withheld());
+    }
+
+    #[test]
+    fn source_embedding_modes() {
+        let module = [0x00, 0x61, 0x73, 0x6d];
+        assert_eq!(
+            rust_array("token", &module),
+            "pub const TOKEN: [u8; 4] = [\n    0x00, 0x61, 0x73, 0x6d,\n];\n"
+        );
+        assert_eq!(
+            c_array("token", &module),
+            "#ifndef TOKEN_WASM_H\n#define TOKEN_WASM_H\n\nstatic const unsigned char token[] = {\n  0x00, 0x61, 0x73, 0x6d,\n};\nstatic const unsigned int token_len = 4;\n\n#endif\n"
+        );
+    }
+
+    #[test]
+    fn output_identifier() {
+        let mut ruleset_result = RulesetResult::new("Test".to_string());
+        assert_eq!(ruleset_result.identifier(), "wasm");
+        ruleset_result.set_input_path(PathBuf::from("out/erc-20.wasm"));
+        assert_eq!(ruleset_result.identifier(), "erc_20");
+        ruleset_result.set_input_path(PathBuf::from("1inch.wasm"));
+        assert_eq!(ruleset_result.identifier(), "_1inch");
+    }
+
+    #[test]
+    fn writer_force_raw_binary_to_stdout() {
+        let mut ruleset_result = RulesetResult::new("Test".to_string());
+        ruleset_result.set_output_module(Module::default());
+        ruleset_result.set_output_path(PathBuf::from("/dev/stdout"));
+        ruleset_result.set_force(true);
+
+        assert!(ruleset_result.write("bin").is_ok());
+    }
+
+    #[test]
+    fn writer_encodings_to_stdout() {
+        for mode in &["hex0x", "base64", "rust", "c", "json"] {
+            let mut ruleset_result = RulesetResult::new("Test".to_string());
+            ruleset_result.set_output_module(Module::default());
+            ruleset_result.set_output_path(PathBuf::from("/dev/stdout"));
+
+            assert_eq!(ruleset_result.write(mode).expect("Should be Ok"), true);
+        }
+    }
+
+    #[test]
+    fn error_chain_includes_sources() {
+        let error = ModuleError::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+            .at(libchisel::Location::Section("code".to_string()));
+        assert_eq!(
+            error_chain(&error),
+            "code section: Failed to serialize the module: unexpected end of file"
+        );
+        assert_eq!(
+            error_chain(&ModuleError::NotFound),
+            ModuleError::NotFound.to_string()
+        );
+    }
 }
@@ -0,0 +1,333 @@
+//! Schema for chisel configuration files.
+//! This module lists the options accepted at the top level of a ruleset and by each module known
+//! to the driver, along with the type of value each option takes.
+//! It is used by the YAML configuration parser to reject unknown modules, unknown options and
+//! values of the wrong type before anything is executed.
+
+use serde_yaml::Value;
+
+use crate::config::{ConfigError, FailurePolicy};
+
+/// The type of value accepted by a configuration option.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptionType {
+    /// A string.
+    String,
+    /// A boolean.
+    Bool,
+    /// An integer.
+    Integer,
+    /// A string, or a sequence of strings. Sequences are joined into a comma-separated list.
+    List,
+}
+
+/// Ruleset key holding the sequence form of the module list.
+pub const MODULE_LIST: &str = "modules";
+
+/// Ruleset key naming the base ruleset. Resolved by the preprocessor.
+pub const EXTENDS: &str = "extends";
+
+/// Ruleset key holding named sets of overrides. Resolved by the preprocessor.
+pub const PROFILES: &str = "profiles";
+
+/// Module option selecting the failure policy of the module. Accepted by every module.
+pub const ON_FAIL: &str = "on_fail";
+
+/// Options accepted at the top level of a ruleset.
+const RULESET_OPTIONS: &[(&str, OptionType)] = &[
+    ("file", OptionType::String),
+    ("output", OptionType::String),
+    ("transactional", OptionType::Bool),
+];
+
+/// Options accepted by each module. Left-hand value: module name. Right-hand value: its options.
+const MODULE_OPTIONS: &[(&str, &[(&str, OptionType)])] = &[
+    ("canonicalizenan", &[]),
+    ("checkfloat", &[("reachable_only", OptionType::Bool)]),
+    (
+        "checkinstructions",
+        &[("allow", OptionType::List), ("deny", OptionType::List)],
+    ),
+    (
+        "checklimits",
+        &[
+            ("max_functions", OptionType::Integer),
+            ("max_locals", OptionType::Integer),
+            ("max_function_size", OptionType::Integer),
+            ("max_code_size", OptionType::Integer),
+            ("max_globals", OptionType::Integer),
+            ("max_table_size", OptionType::Integer),
+            ("max_data_segments", OptionType::Integer),
+            ("max_data_segment_size", OptionType::Integer),
+            ("max_imports", OptionType::Integer),
+            ("max_exports", OptionType::Integer),
+            ("max_nesting_depth", OptionType::Integer),
+        ],
+    ),
+    (
+        "checksize",
+        &[
+            ("max_size", OptionType::Integer),
+            ("preset", OptionType::String),
+        ],
+    ),
+    ("checkstartfunc", &[("require_start", OptionType::Bool)]),
+    ("deployer", &[("preset", OptionType::String)]),
+    ("dropnames", &[]),
+    (
+        "dropsection",
+        &[
+            ("names", OptionType::Bool),
+            ("custom_by_name", OptionType::String),
+            ("custom_by_index", OptionType::Integer),
+            ("unknown_by_index", OptionType::Integer),
+        ],
+    ),
+    ("remapimports", &[("preset", OptionType::List)]),
+    ("remapstart", &[("preset", OptionType::String)]),
+    ("repack", &[]),
+    (
+        "snip",
+        &[
+            ("snip_rust_fmt_code", OptionType::Bool),
+            ("snip_rust_panicking_code", OptionType::Bool),
+            ("skip_producers_section", OptionType::Bool),
+        ],
+    ),
+    (
+        "softfloat",
+        &[
+            ("mode", OptionType::String),
+            ("namespace", OptionType::String),
+        ],
+    ),
+    ("trimexports", &[("preset", OptionType::String)]),
+    ("trimstartfunc", &[("preset", OptionType::String)]),
+    ("validate", &[("features", OptionType::List)]),
+    ("verifyexports", &[("preset", OptionType::String)]),
+    ("verifyimports", &[("preset", OptionType::List)]),
+    #[cfg(feature = "binaryen")]
+    ("binaryenopt", &[("preset", OptionType::String)]),
+];
+
+/// Returns the type of a top-level ruleset option, or None if no such option exists.
+pub fn ruleset_option(name: &str) -> Option<OptionType> {
+    RULESET_OPTIONS
+        .iter()
+        .find(|(option, _)| *option == name)
+        .map(|(_, ty)| *ty)
+}
+
+/// Returns the options accepted by a module, or None if no such module exists.
+pub fn module_options(name: &str) -> Option<&'static [(&'static str, OptionType)]> {
+    MODULE_OPTIONS
+        .iter()
+        .find(|(module, _)| *module == name)
+        .map(|(_, options)| *options)
+}
+
+/// Checks a module configuration against the schema of the module.
+/// Errors carry the path of the offending option, relative to the module.
+pub fn check_module(name: &str, config: &Value) -> Result<(), ConfigError> {
+    let options = match module_options(name) {
+        Some(options) => options,
+        None => {
+            let known: Vec<&str> = RULESET_OPTIONS
+                .iter()
+                .map(|(option, _)| *option)
+                .chain(vec![MODULE_LIST, EXTENDS, PROFILES])
+                .chain(MODULE_OPTIONS.iter().map(|(module, _)| *module))
+                .collect();
+            return Err(ConfigError::new(unknown("module or option", name, &known)));
+        }
+    };
+
+    let config = match config {
+        Value::Null => return Ok(()),
+        Value::Mapping(mapping) => mapping,
+        other => {
+            return Err(ConfigError::new(format!(
+                "expected a mapping of module options, found {}",
+                type_name(other)
+            )))
+        }
+    };
+
+    for (key, value) in config.iter() {
+        let key = match key {
+            Value::String(key) => key,
+            other => {
+                return Err(ConfigError::new(format!(
+                    "expected option name to be a string, found {}",
+                    type_name(other)
+                )))
+            }
+        };
+
+        if key == ON_FAIL {
+            check_value(OptionType::String, value).map_err(|e| e.within(key))?;
+            if let Value::String(policy) = value {
+                policy
+                    .parse::<FailurePolicy>()
+                    .map_err(|e| ConfigError::new(e).within(key))?;
+            }
+            continue;
+        }
+
+        let ty = match options.iter().find(|(option, _)| option == key) {
+            Some((_, ty)) => *ty,
+            None => {
+                let known: Vec<&str> = options
+                    .iter()
+                    .map(|(option, _)| *option)
+                    .chain(Some(ON_FAIL))
+                    .collect();
+                return Err(ConfigError::new(unknown(
+                    &format!("option for '{}'", name),
+                    key,
+                    &known,
+                ))
+                .within(key));
+            }
+        };
+
+        check_value(ty, value).map_err(|e| e.within(key))?;
+    }
+
+    Ok(())
+}
+
+/// Checks that a value is of the given type.
+pub fn check_value(ty: OptionType, value: &Value) -> Result<(), ConfigError> {
+    let matches = match (ty, value) {
+        (OptionType::String, Value::String(_)) => true,
+        (OptionType::Bool, Value::Bool(_)) => true,
+        (OptionType::Integer, Value::Number(n)) => n.is_u64() || n.is_i64(),
+        (OptionType::List, Value::String(_)) => true,
+        (OptionType::List, Value::Sequence(elements)) => {
+            for (idx, element) in elements.iter().enumerate() {
+                check_value(OptionType::String, element)
+                    .map_err(|e| e.within(&format!("[{}]", idx)))?;
+            }
+            true
+        }
+        _ => false,
+    };
+
+    if matches {
+        Ok(())
+    } else {
+        Err(ConfigError::new(format!(
+            "expected {}, found {}",
+            match ty {
+                OptionType::String => "string",
+                OptionType::Bool => "boolean",
+                OptionType::Integer => "integer",
+                OptionType::List => "string or sequence of strings",
+            },
+            type_name(value)
+        )))
+    }
+}
+
+/// Returns a human-readable name for the type of a YAML value.
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "float",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Sequence(_) => "sequence",
+        Value::Mapping(_) => "mapping",
+    }
+}
+
+/// Formats an error for an unknown name, suggesting the closest known name if there is one.
+fn unknown(what: &str, name: &str, known: &[&str]) -> String {
+    match known
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+    {
+        Some((_, suggestion)) => format!(
+            "unknown {} '{}'; did you mean '{}'?",
+            what, name, suggestion
+        ),
+        None => format!("unknown {} '{}'", what, name),
+    }
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = if ca == *cb { diagonal } else { diagonal + 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("verifyimports", "verifyimports"), 0);
+        assert_eq!(edit_distance("verifyimport", "verifyimports"), 1);
+        assert_eq!(edit_distance("prest", "preset"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn unknown_module_suggestion() {
+        let err = check_module("verifyimport", &Value::Null).unwrap_err();
+        assert_eq!(
+            err.message(),
+            "unknown module or option 'verifyimport'; did you mean 'verifyimports'?"
+        );
+    }
+
+    #[test]
+    fn value_types() {
+        assert!(check_value(OptionType::String, &Value::String("a".to_string())).is_ok());
+        assert!(check_value(OptionType::Bool, &Value::Bool(true)).is_ok());
+        assert!(check_value(OptionType::Integer, &Value::Number(1i64.into())).is_ok());
+        assert!(check_value(OptionType::Integer, &Value::Number(1.5f64.into())).is_err());
+        assert!(check_value(OptionType::Bool, &Value::String("true".to_string())).is_err());
+        assert!(check_value(
+            OptionType::List,
+            &Value::Sequence(vec![Value::String("ewasm".to_string())])
+        )
+        .is_ok());
+        assert!(check_value(
+            OptionType::List,
+            &Value::Sequence(vec![Value::Number(1i64.into())])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn failure_policy() {
+        let config: Value = serde_yaml::from_str("preset: ewasm\non_fail: warn").unwrap();
+        assert!(check_module("verifyimports", &config).is_ok());
+
+        let config: Value = serde_yaml::from_str("on_fail: never").unwrap();
+        let err = check_module("checkfloat", &config).unwrap_err();
+        assert_eq!(err.path(), &["on_fail".to_string()]);
+        assert_eq!(
+            err.message(),
+            "expected one of 'error', 'warn' or 'ignore', found 'never'"
+        );
+    }
+}
@@ -0,0 +1,1052 @@
+;; Deterministic software implementations of the WebAssembly floating point instructions, used by
+;; the softfloat translator. Floats are passed as their bit patterns: f32 as i32 and f64 as i64.
+;; Rounding is always to nearest, ties to even, and every NaN result of arithmetic is the canonical
+;; NaN with a positive sign. abs, neg and copysign only change the sign bit, and so keep the payload
+;; of a NaN, like the instructions they replace. Conversions to integers trap like the instructions
+;; they replace.
+;; The routines only use integer instructions, and do not use memory, tables or globals.
+;; Each routine is exported under the name of the instruction it implements, with the dot replaced
+;; by an underscore.
+;;
+;; softfloat.wasm is compiled from this file, e.g. with `wat2wasm softfloat.wat -o softfloat.wasm`.
+;; The algorithms follow Berkeley SoftFloat 3.
+(module
+  ;; f32 helpers
+
+  (func $f32_is_nan (param $a i32) (result i32)
+    (i32.gt_u (i32.and (local.get $a) (i32.const 0x7fffffff)) (i32.const 0x7f800000)))
+
+  ;; Shifts $a right by $dist bits, setting the lowest bit if any bit shifted out was set.
+  (func $f32_shift_right_jam (param $a i32) (param $dist i32) (result i32)
+    (if (result i32) (i32.eqz (local.get $dist))
+      (then (local.get $a))
+      (else
+        (if (result i32) (i32.lt_u (local.get $dist) (i32.const 32))
+          (then
+            (i32.or
+              (i32.shr_u (local.get $a) (local.get $dist))
+              (i32.ne (i32.shl (local.get $a) (i32.sub (i32.const 32) (local.get $dist))) (i32.const 0))))
+          (else (i32.ne (local.get $a) (i32.const 0)))))))
+
+  ;; Rounds to nearest, ties to even, and packs a f32. $sign is the sign bit, $sig has its leading
+  ;; bit at bit 30, and $exp is the biased exponent of the result minus one.
+  (func $f32_round_pack (param $sign i32) (param $exp i32) (param $sig i32) (result i32)
+    (local $round_bits i32)
+    (local.set $round_bits (i32.and (local.get $sig) (i32.const 0x7f)))
+    (if (i32.ge_u (local.get $exp) (i32.const 0xfd))
+      (then
+        (if (i32.lt_s (local.get $exp) (i32.const 0))
+          (then
+            ;; Subnormal result.
+            (local.set $sig
+              (call $f32_shift_right_jam (local.get $sig) (i32.sub (i32.const 0) (local.get $exp))))
+            (local.set $exp (i32.const 0))
+            (local.set $round_bits (i32.and (local.get $sig) (i32.const 0x7f))))
+          (else
+            (if (i32.or
+                  (i32.gt_s (local.get $exp) (i32.const 0xfd))
+                  (i32.ge_u (i32.add (local.get $sig) (i32.const 0x40)) (i32.const 0x80000000)))
+              (then
+                ;; Overflow to infinity.
+                (return (i32.or (local.get $sign) (i32.const 0x7f800000)))))))))
+    (local.set $sig (i32.shr_u (i32.add (local.get $sig) (i32.const 0x40)) (i32.const 7)))
+    (if (i32.eq (local.get $round_bits) (i32.const 0x40))
+      (then (local.set $sig (i32.and (local.get $sig) (i32.const -2)))))
+    (if (i32.eqz (local.get $sig))
+      (then (local.set $exp (i32.const 0))))
+    ;; The hidden bit of $sig carries into the exponent.
+    (i32.or
+      (local.get $sign)
+      (i32.add (i32.shl (local.get $exp) (i32.const 23)) (local.get $sig))))
+
+  ;; Rounds to an integral value. $mode is 0 for nearest (ties to even), 1 towards zero, 2 towards
+  ;; negative infinity and 3 towards positive infinity.
+  (func $f32_round (param $a i32) (param $mode i32) (result i32)
+    (local $exp i32) (local $z i32) (local $last i32) (local $mask i32)
+    (local.set $exp (i32.and (i32.shr_u (local.get $a) (i32.const 23)) (i32.const 0xff)))
+    (if (i32.le_u (local.get $exp) (i32.const 126))
+      (then
+        ;; |a| < 1
+        (if (i32.eqz (i32.shl (local.get $a) (i32.const 1)))
+          (then (return (local.get $a))))
+        (local.set $z (i32.and (local.get $a) (i32.const 0x80000000)))
+        (if (i32.eqz (local.get $mode))
+          (then
+            (if (i32.and
+                  (i32.eq (local.get $exp) (i32.const 126))
+                  (i32.ne (i32.and (local.get $a) (i32.const 0x7fffff)) (i32.const 0)))
+              (then (local.set $z (i32.or (local.get $z) (i32.const 0x3f800000)))))))
+        (if (i32.and (i32.eq (local.get $mode) (i32.const 2)) (i32.ne (local.get $z) (i32.const 0)))
+          (then (local.set $z (i32.const 0xbf800000))))
+        (if (i32.and (i32.eq (local.get $mode) (i32.const 3)) (i32.eqz (local.get $z)))
+          (then (local.set $z (i32.const 0x3f800000))))
+        (return (local.get $z))))
+    (if (i32.ge_u (local.get $exp) (i32.const 150))
+      (then
+        ;; Already integral, infinite or NaN.
+        (if (call $f32_is_nan (local.get $a))
+          (then (return (i32.const 0x7fc00000))))
+        (return (local.get $a))))
+    (local.set $last (i32.shl (i32.const 1) (i32.sub (i32.const 150) (local.get $exp))))
+    (local.set $mask (i32.sub (local.get $last) (i32.const 1)))
+    (local.set $z (local.get $a))
+    (if (i32.eqz (local.get $mode))
+      (then
+        (local.set $z (i32.add (local.get $z) (i32.shr_u (local.get $last) (i32.const 1))))
+        (if (i32.eqz (i32.and (local.get $z) (local.get $mask)))
+          (then (local.set $z (i32.and (local.get $z) (i32.xor (local.get $last) (i32.const -1))))))))
+    (if (i32.or
+          (i32.and (i32.eq (local.get $mode) (i32.const 2)) (i32.lt_s (local.get $z) (i32.const 0)))
+          (i32.and (i32.eq (local.get $mode) (i32.const 3)) (i32.ge_s (local.get $z) (i32.const 0))))
+      (then (local.set $z (i32.add (local.get $z) (local.get $mask)))))
+    (i32.and (local.get $z) (i32.xor (local.get $mask) (i32.const -1))))
+
+  ;; Returns the magnitude of $a truncated towards zero. Traps on NaN, infinity, and magnitudes of
+  ;; 2^64 or more.
+  (func $f32_trunc_magnitude (param $a i32) (result i64)
+    (local $exp i32) (local $shift i32) (local $sig i64)
+    (local.set $exp (i32.and (i32.shr_u (local.get $a) (i32.const 23)) (i32.const 0xff)))
+    (if (i32.lt_u (local.get $exp) (i32.const 127))
+      (then (return (i64.const 0))))
+    (if (i32.ge_u (local.get $exp) (i32.const 191))
+      (then (unreachable)))
+    (local.set $sig (i64.extend_i32_u (i32.or (i32.and (local.get $a) (i32.const 0x7fffff)) (i32.const 0x800000))))
+    (local.set $shift (i32.sub (local.get $exp) (i32.const 150)))
+    (if (result i64) (i32.ge_s (local.get $shift) (i32.const 0))
+      (then (i64.shl (local.get $sig) (i64.extend_i32_u (local.get $shift))))
+      (else (i64.shr_u (local.get $sig) (i64.extend_i32_u (i32.sub (i32.const 0) (local.get $shift)))))))
+
+  ;; f32 arithmetic
+
+  (func $f32_add (export "f32_add") (param $a i32) (param $b i32) (result i32)
+    (local $t i32) (local $exp_a i32) (local $exp_b i32) (local $sig_a i32) (local $sig_b i32)
+    (local $sig i32) (local $shift i32)
+    (if (i32.or (call $f32_is_nan (local.get $a)) (call $f32_is_nan (local.get $b)))
+      (then (return (i32.const 0x7fc00000))))
+    ;; Order the operands by magnitude.
+    (if (i32.lt_u (i32.and (local.get $a) (i32.const 0x7fffffff)) (i32.and (local.get $b) (i32.const 0x7fffffff)))
+      (then
+        (local.set $t (local.get $a))
+        (local.set $a (local.get $b))
+        (local.set $b (local.get $t))))
+    (if (i32.eq (i32.and (local.get $a) (i32.const 0x7fffffff)) (i32.const 0x7f800000))
+      (then
+        ;; Infinities of opposite signs.
+        (if (i32.and
+              (i32.eq (i32.and (local.get $b) (i32.const 0x7fffffff)) (i32.const 0x7f800000))
+              (i32.ne (local.get $a) (local.get $b)))
+          (then (return (i32.const 0x7fc00000))))
+        (return (local.get $a))))
+    (if (i32.eqz (i32.and (local.get $b) (i32.const 0x7fffffff)))
+      (then
+        ;; The sum of zeros is only negative if both are.
+        (if (i32.eqz (i32.and (local.get $a) (i32.const 0x7fffffff)))
+          (then (return (i32.and (local.get $a) (local.get $b)))))
+        (return (local.get $a))))
+    (local.set $exp_a (i32.shr_u (i32.and (local.get $a) (i32.const 0x7fffffff)) (i32.const 23)))
+    (local.set $exp_b (i32.shr_u (i32.and (local.get $b) (i32.const 0x7fffffff)) (i32.const 23)))
+    (local.set $sig_a (i32.and (local.get $a) (i32.const 0x7fffff)))
+    (local.set $sig_b (i32.and (local.get $b) (i32.const 0x7fffff)))
+    (if (i32.eqz (local.get $exp_a))
+      (then (local.set $exp_a (i32.const 1)))
+      (else (local.set $sig_a (i32.or (local.get $sig_a) (i32.const 0x800000)))))
+    (if (i32.eqz (local.get $exp_b))
+      (then (local.set $exp_b (i32.const 1)))
+      (else (local.set $sig_b (i32.or (local.get $sig_b) (i32.const 0x800000)))))
+    ;; Leave one bit of headroom for the carry, and align the smaller operand.
+    (local.set $sig_a (i32.shl (local.get $sig_a) (i32.const 6)))
+    (local.set $sig_b (i32.shl (local.get $sig_b) (i32.const 6)))
+    (local.set $sig_b
+      (call $f32_shift_right_jam (local.get $sig_b) (i32.sub (local.get $exp_a) (local.get $exp_b))))
+    (if (i32.lt_s (i32.xor (local.get $a) (local.get $b)) (i32.const 0))
+      (then
+        (local.set $sig (i32.sub (local.get $sig_a) (local.get $sig_b)))
+        ;; Exact cancellation gives positive zero.
+        (if (i32.eqz (local.get $sig))
+          (then (return (i32.const 0)))))
+      (else
+        (local.set $sig (i32.add (local.get $sig_a) (local.get $sig_b)))))
+    (local.set $shift (i32.sub (i32.clz (local.get $sig)) (i32.const 1)))
+    (call $f32_round_pack
+      (i32.and (local.get $a) (i32.const 0x80000000))
+      (i32.sub (local.get $exp_a) (local.get $shift))
+      (i32.shl (local.get $sig) (local.get $shift))))
+
+  (func $f32_sub (export "f32_sub") (param $a i32) (param $b i32) (result i32)
+    (call $f32_add (local.get $a) (i32.xor (local.get $b) (i32.const 0x80000000))))
+
+  (func $f32_mul (export "f32_mul") (param $a i32) (param $b i32) (result i32)
+    (local $sign i32) (local $abs_a i32) (local $abs_b i32) (local $exp_a i32) (local $exp_b i32)
+    (local $sig_a i32) (local $sig_b i32) (local $shift i32) (local $exp i32) (local $sig i32)
+    (local $product i64)
+    (if (i32.or (call $f32_is_nan (local.get $a)) (call $f32_is_nan (local.get $b)))
+      (then (return (i32.const 0x7fc00000))))
+    (local.set $sign (i32.and (i32.xor (local.get $a) (local.get $b)) (i32.const 0x80000000)))
+    (local.set $abs_a (i32.and (local.get $a) (i32.const 0x7fffffff)))
+    (local.set $abs_b (i32.and (local.get $b) (i32.const 0x7fffffff)))
+    (if (i32.or (i32.eq (local.get $abs_a) (i32.const 0x7f800000)) (i32.eq (local.get $abs_b) (i32.const 0x7f800000)))
+      (then
+        ;; Infinity times zero.
+        (if (i32.or (i32.eqz (local.get $abs_a)) (i32.eqz (local.get $abs_b)))
+          (then (return (i32.const 0x7fc00000))))
+        (return (i32.or (local.get $sign) (i32.const 0x7f800000)))))
+    (if (i32.or (i32.eqz (local.get $abs_a)) (i32.eqz (local.get $abs_b)))
+      (then (return (local.get $sign))))
+    ;; Unpack $a, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_a (i32.and (i32.shr_u (local.get $a) (i32.const 23)) (i32.const 0xff)))
+    (local.set $sig_a (i32.and (local.get $a) (i32.const 0x7fffff)))
+    (if (i32.eqz (local.get $exp_a))
+      (then
+        (local.set $shift (i32.sub (i32.clz (local.get $sig_a)) (i32.const 8)))
+        (local.set $exp_a (i32.sub (i32.const 1) (local.get $shift)))
+        (local.set $sig_a (i32.shl (local.get $sig_a) (local.get $shift))))
+      (else
+        (local.set $sig_a (i32.or (local.get $sig_a) (i32.const 0x800000)))))
+    ;; Unpack $b, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_b (i32.and (i32.shr_u (local.get $b) (i32.const 23)) (i32.const 0xff)))
+    (local.set $sig_b (i32.and (local.get $b) (i32.const 0x7fffff)))
+    (if (i32.eqz (local.get $exp_b))
+      (then
+        (local.set $shift (i32.sub (i32.clz (local.get $sig_b)) (i32.const 8)))
+        (local.set $exp_b (i32.sub (i32.const 1) (local.get $shift)))
+        (local.set $sig_b (i32.shl (local.get $sig_b) (local.get $shift))))
+      (else
+        (local.set $sig_b (i32.or (local.get $sig_b) (i32.const 0x800000)))))
+    (local.set $exp (i32.sub (i32.add (local.get $exp_a) (local.get $exp_b)) (i32.const 127)))
+    (local.set $sig_a (i32.shl (local.get $sig_a) (i32.const 7)))
+    (local.set $sig_b (i32.shl (local.get $sig_b) (i32.const 8)))
+    ;; The upper half of the 64-bit product, with the lower half jammed into the lowest bit.
+    (local.set $product
+      (i64.mul (i64.extend_i32_u (local.get $sig_a)) (i64.extend_i32_u (local.get $sig_b))))
+    (local.set $sig
+      (i32.or
+        (i32.wrap_i64 (i64.shr_u (local.get $product) (i64.const 32)))
+        (i32.ne (i32.wrap_i64 (local.get $product)) (i32.const 0))))
+    (if (i32.lt_u (local.get $sig) (i32.const 0x40000000))
+      (then
+        (local.set $exp (i32.sub (local.get $exp) (i32.const 1)))
+        (local.set $sig (i32.shl (local.get $sig) (i32.const 1)))))
+    (call $f32_round_pack (local.get $sign) (local.get $exp) (local.get $sig)))
+
+  (func $f32_div (export "f32_div") (param $a i32) (param $b i32) (result i32)
+    (local $sign i32) (local $abs_a i32) (local $abs_b i32) (local $exp_a i32) (local $exp_b i32)
+    (local $sig_a i32) (local $sig_b i32) (local $shift i32) (local $exp i32) (local $sig i32)
+    (local $dividend i64) (local $quotient i64)
+    (if (i32.or (call $f32_is_nan (local.get $a)) (call $f32_is_nan (local.get $b)))
+      (then (return (i32.const 0x7fc00000))))
+    (local.set $sign (i32.and (i32.xor (local.get $a) (local.get $b)) (i32.const 0x80000000)))
+    (local.set $abs_a (i32.and (local.get $a) (i32.const 0x7fffffff)))
+    (local.set $abs_b (i32.and (local.get $b) (i32.const 0x7fffffff)))
+    (if (i32.eq (local.get $abs_a) (i32.const 0x7f800000))
+      (then
+        (if (i32.eq (local.get $abs_b) (i32.const 0x7f800000))
+          (then (return (i32.const 0x7fc00000))))
+        (return (i32.or (local.get $sign) (i32.const 0x7f800000)))))
+    (if (i32.eq (local.get $abs_b) (i32.const 0x7f800000))
+      (then (return (local.get $sign))))
+    (if (i32.eqz (local.get $abs_b))
+      (then
+        (if (i32.eqz (local.get $abs_a))
+          (then (return (i32.const 0x7fc00000))))
+        (return (i32.or (local.get $sign) (i32.const 0x7f800000)))))
+    (if (i32.eqz (local.get $abs_a))
+      (then (return (local.get $sign))))
+    ;; Unpack $a, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_a (i32.and (i32.shr_u (local.get $a) (i32.const 23)) (i32.const 0xff)))
+    (local.set $sig_a (i32.and (local.get $a) (i32.const 0x7fffff)))
+    (if (i32.eqz (local.get $exp_a))
+      (then
+        (local.set $shift (i32.sub (i32.clz (local.get $sig_a)) (i32.const 8)))
+        (local.set $exp_a (i32.sub (i32.const 1) (local.get $shift)))
+        (local.set $sig_a (i32.shl (local.get $sig_a) (local.get $shift))))
+      (else
+        (local.set $sig_a (i32.or (local.get $sig_a) (i32.const 0x800000)))))
+    ;; Unpack $b, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_b (i32.and (i32.shr_u (local.get $b) (i32.const 23)) (i32.const 0xff)))
+    (local.set $sig_b (i32.and (local.get $b) (i32.const 0x7fffff)))
+    (if (i32.eqz (local.get $exp_b))
+      (then
+        (local.set $shift (i32.sub (i32.clz (local.get $sig_b)) (i32.const 8)))
+        (local.set $exp_b (i32.sub (i32.const 1) (local.get $shift)))
+        (local.set $sig_b (i32.shl (local.get $sig_b) (local.get $shift))))
+      (else
+        (local.set $sig_b (i32.or (local.get $sig_b) (i32.const 0x800000)))))
+    (local.set $exp (i32.add (i32.sub (local.get $exp_a) (local.get $exp_b)) (i32.const 126)))
+    (if (i32.lt_u (local.get $sig_a) (local.get $sig_b))
+      (then
+        (local.set $exp (i32.sub (local.get $exp) (i32.const 1)))
+        (local.set $dividend (i64.shl (i64.extend_i32_u (local.get $sig_a)) (i64.const 31))))
+      (else
+        (local.set $dividend (i64.shl (i64.extend_i32_u (local.get $sig_a)) (i64.const 30)))))
+    (local.set $quotient (i64.div_u (local.get $dividend) (i64.extend_i32_u (local.get $sig_b))))
+    (local.set $sig (i32.wrap_i64 (local.get $quotient)))
+    ;; Set the sticky bit if the division was inexact.
+    (if (i32.eqz (i32.and (local.get $sig) (i32.const 0x3f)))
+      (then
+        (local.set $sig
+          (i32.or
+            (local.get $sig)
+            (i64.ne
+              (i64.mul (i64.extend_i32_u (local.get $sig_b)) (local.get $quotient))
+              (local.get $dividend))))))
+    (call $f32_round_pack (local.get $sign) (local.get $exp) (local.get $sig)))
+
+  (func $f32_sqrt (export "f32_sqrt") (param $a i32) (result i32)
+    (local $exp_a i32) (local $sig_a i32) (local $shift i32) (local $exp i32) (local $e i32)
+    (local $rem i64) (local $root i64) (local $bit i64)
+    (if (call $f32_is_nan (local.get $a))
+      (then (return (i32.const 0x7fc00000))))
+    ;; Zeros of either sign are their own square root.
+    (if (i32.eqz (i32.shl (local.get $a) (i32.const 1)))
+      (then (return (local.get $a))))
+    (if (i32.lt_s (local.get $a) (i32.const 0))
+      (then (return (i32.const 0x7fc00000))))
+    (if (i32.eq (local.get $a) (i32.const 0x7f800000))
+      (then (return (local.get $a))))
+    ;; Unpack $a, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_a (i32.and (i32.shr_u (local.get $a) (i32.const 23)) (i32.const 0xff)))
+    (local.set $sig_a (i32.and (local.get $a) (i32.const 0x7fffff)))
+    (if (i32.eqz (local.get $exp_a))
+      (then
+        (local.set $shift (i32.sub (i32.clz (local.get $sig_a)) (i32.const 8)))
+        (local.set $exp_a (i32.sub (i32.const 1) (local.get $shift)))
+        (local.set $sig_a (i32.shl (local.get $sig_a) (local.get $shift))))
+      (else
+        (local.set $sig_a (i32.or (local.get $sig_a) (i32.const 0x800000)))))
+    (local.set $e (i32.sub (local.get $exp_a) (i32.const 127)))
+    (local.set $exp (i32.add (i32.shr_s (local.get $e) (i32.const 1)) (i32.const 126)))
+    ;; Integer square root of the significand, shifted so that the root has its leading bit at bit
+    ;; 30 and the exponent of the radicand is even.
+    (local.set $rem
+      (i64.shl
+        (i64.extend_i32_u (local.get $sig_a))
+        (i64.extend_i32_u (i32.add (i32.const 37) (i32.and (local.get $e) (i32.const 1))))))
+    (local.set $root (i64.const 0))
+    (local.set $bit (i64.const 0x4000000000000000))
+    (loop $bits
+      (if (i64.ge_u (local.get $rem) (i64.add (local.get $root) (local.get $bit)))
+        (then
+          (local.set $rem (i64.sub (local.get $rem) (i64.add (local.get $root) (local.get $bit))))
+          (local.set $root (i64.add (i64.shr_u (local.get $root) (i64.const 1)) (local.get $bit))))
+        (else
+          (local.set $root (i64.shr_u (local.get $root) (i64.const 1)))))
+      (local.set $bit (i64.shr_u (local.get $bit) (i64.const 2)))
+      (br_if $bits (i64.ne (local.get $bit) (i64.const 0))))
+    (call $f32_round_pack
+      (i32.const 0)
+      (local.get $exp)
+      (i32.or
+        (i32.wrap_i64 (local.get $root))
+        (i64.ne (local.get $rem) (i64.const 0)))))
+
+  (func $f32_min (export "f32_min") (param $a i32) (param $b i32) (result i32)
+    (if (i32.or (call $f32_is_nan (local.get $a)) (call $f32_is_nan (local.get $b)))
+      (then (return (i32.const 0x7fc00000))))
+    (if (call $f32_lt (local.get $a) (local.get $b))
+      (then (return (local.get $a))))
+    (if (call $f32_lt (local.get $b) (local.get $a))
+      (then (return (local.get $b))))
+    ;; Equal operands, which may be zeros of different signs.
+    (i32.or (local.get $a) (local.get $b)))
+
+  (func $f32_max (export "f32_max") (param $a i32) (param $b i32) (result i32)
+    (if (i32.or (call $f32_is_nan (local.get $a)) (call $f32_is_nan (local.get $b)))
+      (then (return (i32.const 0x7fc00000))))
+    (if (call $f32_lt (local.get $b) (local.get $a))
+      (then (return (local.get $a))))
+    (if (call $f32_lt (local.get $a) (local.get $b))
+      (then (return (local.get $b))))
+    (i32.and (local.get $a) (local.get $b)))
+
+  (func $f32_ceil (export "f32_ceil") (param $a i32) (result i32)
+    (call $f32_round (local.get $a) (i32.const 3)))
+
+  (func $f32_floor (export "f32_floor") (param $a i32) (result i32)
+    (call $f32_round (local.get $a) (i32.const 2)))
+
+  (func $f32_trunc (export "f32_trunc") (param $a i32) (result i32)
+    (call $f32_round (local.get $a) (i32.const 1)))
+
+  (func $f32_nearest (export "f32_nearest") (param $a i32) (result i32)
+    (call $f32_round (local.get $a) (i32.const 0)))
+
+  (func $f32_abs (export "f32_abs") (param $a i32) (result i32)
+    (i32.and (local.get $a) (i32.const 0x7fffffff)))
+
+  (func $f32_neg (export "f32_neg") (param $a i32) (result i32)
+    (i32.xor (local.get $a) (i32.const 0x80000000)))
+
+  (func $f32_copysign (export "f32_copysign") (param $a i32) (param $b i32) (result i32)
+    (i32.or
+      (i32.and (local.get $a) (i32.const 0x7fffffff))
+      (i32.and (local.get $b) (i32.const 0x80000000))))
+
+  ;; f32 comparisons
+
+  (func $f32_eq (export "f32_eq") (param $a i32) (param $b i32) (result i32)
+    (if (i32.or (call $f32_is_nan (local.get $a)) (call $f32_is_nan (local.get $b)))
+      (then (return (i32.const 0))))
+    ;; Zeros are equal regardless of their signs.
+    (i32.or
+      (i32.eq (local.get $a) (local.get $b))
+      (i32.eqz (i32.shl (i32.or (local.get $a) (local.get $b)) (i32.const 1)))))
+
+  (func $f32_ne (export "f32_ne") (param $a i32) (param $b i32) (result i32)
+    (i32.eqz (call $f32_eq (local.get $a) (local.get $b))))
+
+  (func $f32_lt (export "f32_lt") (param $a i32) (param $b i32) (result i32)
+    (local $sign_a i32)
+    (if (i32.or (call $f32_is_nan (local.get $a)) (call $f32_is_nan (local.get $b)))
+      (then (return (i32.const 0))))
+    (local.set $sign_a (i32.lt_s (local.get $a) (i32.const 0)))
+    (if (i32.ne (local.get $sign_a) (i32.lt_s (local.get $b) (i32.const 0)))
+      (then
+        (return
+          (i32.and
+            (local.get $sign_a)
+            (i32.ne (i32.shl (i32.or (local.get $a) (local.get $b)) (i32.const 1)) (i32.const 0))))))
+    (i32.and
+      (i32.ne (local.get $a) (local.get $b))
+      (i32.xor (local.get $sign_a) (i32.lt_u (local.get $a) (local.get $b)))))
+
+  (func $f32_le (export "f32_le") (param $a i32) (param $b i32) (result i32)
+    (local $sign_a i32)
+    (if (i32.or (call $f32_is_nan (local.get $a)) (call $f32_is_nan (local.get $b)))
+      (then (return (i32.const 0))))
+    (local.set $sign_a (i32.lt_s (local.get $a) (i32.const 0)))
+    (if (i32.ne (local.get $sign_a) (i32.lt_s (local.get $b) (i32.const 0)))
+      (then
+        (return
+          (i32.or
+            (local.get $sign_a)
+            (i32.eqz (i32.shl (i32.or (local.get $a) (local.get $b)) (i32.const 1)))))))
+    (i32.or
+      (i32.eq (local.get $a) (local.get $b))
+      (i32.xor (local.get $sign_a) (i32.lt_u (local.get $a) (local.get $b)))))
+
+  (func $f32_gt (export "f32_gt") (param $a i32) (param $b i32) (result i32)
+    (call $f32_lt (local.get $b) (local.get $a)))
+
+  (func $f32_ge (export "f32_ge") (param $a i32) (param $b i32) (result i32)
+    (call $f32_le (local.get $b) (local.get $a)))
+
+  ;; f64 helpers
+
+  (func $f64_is_nan (param $a i64) (result i32)
+    (i64.gt_u (i64.and (local.get $a) (i64.const 0x7fffffffffffffff)) (i64.const 0x7ff0000000000000)))
+
+  ;; Shifts $a right by $dist bits, setting the lowest bit if any bit shifted out was set.
+  (func $f64_shift_right_jam (param $a i64) (param $dist i64) (result i64)
+    (if (result i64) (i64.eqz (local.get $dist))
+      (then (local.get $a))
+      (else
+        (if (result i64) (i64.lt_u (local.get $dist) (i64.const 64))
+          (then
+            (i64.or
+              (i64.shr_u (local.get $a) (local.get $dist))
+              (i64.extend_i32_u (i64.ne (i64.shl (local.get $a) (i64.sub (i64.const 64) (local.get $dist))) (i64.const 0)))))
+          (else (i64.extend_i32_u (i64.ne (local.get $a) (i64.const 0))))))))
+
+  ;; Rounds to nearest, ties to even, and packs a f64. $sign is the sign bit, $sig has its leading
+  ;; bit at bit 62, and $exp is the biased exponent of the result minus one.
+  (func $f64_round_pack (param $sign i64) (param $exp i64) (param $sig i64) (result i64)
+    (local $round_bits i64)
+    (local.set $round_bits (i64.and (local.get $sig) (i64.const 0x3ff)))
+    (if (i64.ge_u (local.get $exp) (i64.const 0x7fd))
+      (then
+        (if (i64.lt_s (local.get $exp) (i64.const 0))
+          (then
+            ;; Subnormal result.
+            (local.set $sig
+              (call $f64_shift_right_jam (local.get $sig) (i64.sub (i64.const 0) (local.get $exp))))
+            (local.set $exp (i64.const 0))
+            (local.set $round_bits (i64.and (local.get $sig) (i64.const 0x3ff))))
+          (else
+            (if (i32.or
+                  (i64.gt_s (local.get $exp) (i64.const 0x7fd))
+                  (i64.ge_u (i64.add (local.get $sig) (i64.const 0x200)) (i64.const 0x8000000000000000)))
+              (then
+                ;; Overflow to infinity.
+                (return (i64.or (local.get $sign) (i64.const 0x7ff0000000000000)))))))))
+    (local.set $sig (i64.shr_u (i64.add (local.get $sig) (i64.const 0x200)) (i64.const 10)))
+    (if (i64.eq (local.get $round_bits) (i64.const 0x200))
+      (then (local.set $sig (i64.and (local.get $sig) (i64.const -2)))))
+    (if (i64.eqz (local.get $sig))
+      (then (local.set $exp (i64.const 0))))
+    ;; The hidden bit of $sig carries into the exponent.
+    (i64.or
+      (local.get $sign)
+      (i64.add (i64.shl (local.get $exp) (i64.const 52)) (local.get $sig))))
+
+  ;; Rounds to an integral value. $mode is 0 for nearest (ties to even), 1 towards zero, 2 towards
+  ;; negative infinity and 3 towards positive infinity.
+  (func $f64_round (param $a i64) (param $mode i32) (result i64)
+    (local $exp i64) (local $z i64) (local $last i64) (local $mask i64)
+    (local.set $exp (i64.and (i64.shr_u (local.get $a) (i64.const 52)) (i64.const 0x7ff)))
+    (if (i64.le_u (local.get $exp) (i64.const 1022))
+      (then
+        ;; |a| < 1
+        (if (i64.eqz (i64.shl (local.get $a) (i64.const 1)))
+          (then (return (local.get $a))))
+        (local.set $z (i64.and (local.get $a) (i64.const 0x8000000000000000)))
+        (if (i32.eqz (local.get $mode))
+          (then
+            (if (i32.and
+                  (i64.eq (local.get $exp) (i64.const 1022))
+                  (i64.ne (i64.and (local.get $a) (i64.const 0xfffffffffffff)) (i64.const 0)))
+              (then (local.set $z (i64.or (local.get $z) (i64.const 0x3ff0000000000000)))))))
+        (if (i32.and (i32.eq (local.get $mode) (i32.const 2)) (i64.ne (local.get $z) (i64.const 0)))
+          (then (local.set $z (i64.const 0xbff0000000000000))))
+        (if (i32.and (i32.eq (local.get $mode) (i32.const 3)) (i64.eqz (local.get $z)))
+          (then (local.set $z (i64.const 0x3ff0000000000000))))
+        (return (local.get $z))))
+    (if (i64.ge_u (local.get $exp) (i64.const 1075))
+      (then
+        ;; Already integral, infinite or NaN.
+        (if (call $f64_is_nan (local.get $a))
+          (then (return (i64.const 0x7ff8000000000000))))
+        (return (local.get $a))))
+    (local.set $last (i64.shl (i64.const 1) (i64.sub (i64.const 1075) (local.get $exp))))
+    (local.set $mask (i64.sub (local.get $last) (i64.const 1)))
+    (local.set $z (local.get $a))
+    (if (i32.eqz (local.get $mode))
+      (then
+        (local.set $z (i64.add (local.get $z) (i64.shr_u (local.get $last) (i64.const 1))))
+        (if (i64.eqz (i64.and (local.get $z) (local.get $mask)))
+          (then (local.set $z (i64.and (local.get $z) (i64.xor (local.get $last) (i64.const -1))))))))
+    (if (i32.or
+          (i32.and (i32.eq (local.get $mode) (i32.const 2)) (i64.lt_s (local.get $z) (i64.const 0)))
+          (i32.and (i32.eq (local.get $mode) (i32.const 3)) (i64.ge_s (local.get $z) (i64.const 0))))
+      (then (local.set $z (i64.add (local.get $z) (local.get $mask)))))
+    (i64.and (local.get $z) (i64.xor (local.get $mask) (i64.const -1))))
+
+  ;; Returns the magnitude of $a truncated towards zero. Traps on NaN, infinity, and magnitudes of
+  ;; 2^64 or more.
+  (func $f64_trunc_magnitude (param $a i64) (result i64)
+    (local $exp i64) (local $shift i64) (local $sig i64)
+    (local.set $exp (i64.and (i64.shr_u (local.get $a) (i64.const 52)) (i64.const 0x7ff)))
+    (if (i64.lt_u (local.get $exp) (i64.const 1023))
+      (then (return (i64.const 0))))
+    (if (i64.ge_u (local.get $exp) (i64.const 1087))
+      (then (unreachable)))
+    (local.set $sig (i64.or (i64.and (local.get $a) (i64.const 0xfffffffffffff)) (i64.const 0x10000000000000)))
+    (local.set $shift (i64.sub (local.get $exp) (i64.const 1075)))
+    (if (result i64) (i64.ge_s (local.get $shift) (i64.const 0))
+      (then (i64.shl (local.get $sig) (local.get $shift)))
+      (else (i64.shr_u (local.get $sig) (i64.sub (i64.const 0) (local.get $shift))))))
+
+  ;; f64 arithmetic
+
+  (func $f64_add (export "f64_add") (param $a i64) (param $b i64) (result i64)
+    (local $t i64) (local $exp_a i64) (local $exp_b i64) (local $sig_a i64) (local $sig_b i64)
+    (local $sig i64) (local $shift i64)
+    (if (i32.or (call $f64_is_nan (local.get $a)) (call $f64_is_nan (local.get $b)))
+      (then (return (i64.const 0x7ff8000000000000))))
+    ;; Order the operands by magnitude.
+    (if (i64.lt_u (i64.and (local.get $a) (i64.const 0x7fffffffffffffff)) (i64.and (local.get $b) (i64.const 0x7fffffffffffffff)))
+      (then
+        (local.set $t (local.get $a))
+        (local.set $a (local.get $b))
+        (local.set $b (local.get $t))))
+    (if (i64.eq (i64.and (local.get $a) (i64.const 0x7fffffffffffffff)) (i64.const 0x7ff0000000000000))
+      (then
+        ;; Infinities of opposite signs.
+        (if (i32.and
+              (i64.eq (i64.and (local.get $b) (i64.const 0x7fffffffffffffff)) (i64.const 0x7ff0000000000000))
+              (i64.ne (local.get $a) (local.get $b)))
+          (then (return (i64.const 0x7ff8000000000000))))
+        (return (local.get $a))))
+    (if (i64.eqz (i64.and (local.get $b) (i64.const 0x7fffffffffffffff)))
+      (then
+        ;; The sum of zeros is only negative if both are.
+        (if (i64.eqz (i64.and (local.get $a) (i64.const 0x7fffffffffffffff)))
+          (then (return (i64.and (local.get $a) (local.get $b)))))
+        (return (local.get $a))))
+    (local.set $exp_a (i64.shr_u (i64.and (local.get $a) (i64.const 0x7fffffffffffffff)) (i64.const 52)))
+    (local.set $exp_b (i64.shr_u (i64.and (local.get $b) (i64.const 0x7fffffffffffffff)) (i64.const 52)))
+    (local.set $sig_a (i64.and (local.get $a) (i64.const 0xfffffffffffff)))
+    (local.set $sig_b (i64.and (local.get $b) (i64.const 0xfffffffffffff)))
+    (if (i64.eqz (local.get $exp_a))
+      (then (local.set $exp_a (i64.const 1)))
+      (else (local.set $sig_a (i64.or (local.get $sig_a) (i64.const 0x10000000000000)))))
+    (if (i64.eqz (local.get $exp_b))
+      (then (local.set $exp_b (i64.const 1)))
+      (else (local.set $sig_b (i64.or (local.get $sig_b) (i64.const 0x10000000000000)))))
+    ;; Leave one bit of headroom for the carry, and align the smaller operand.
+    (local.set $sig_a (i64.shl (local.get $sig_a) (i64.const 9)))
+    (local.set $sig_b (i64.shl (local.get $sig_b) (i64.const 9)))
+    (local.set $sig_b
+      (call $f64_shift_right_jam (local.get $sig_b) (i64.sub (local.get $exp_a) (local.get $exp_b))))
+    (if (i64.lt_s (i64.xor (local.get $a) (local.get $b)) (i64.const 0))
+      (then
+        (local.set $sig (i64.sub (local.get $sig_a) (local.get $sig_b)))
+        ;; Exact cancellation gives positive zero.
+        (if (i64.eqz (local.get $sig))
+          (then (return (i64.const 0)))))
+      (else
+        (local.set $sig (i64.add (local.get $sig_a) (local.get $sig_b)))))
+    (local.set $shift (i64.sub (i64.clz (local.get $sig)) (i64.const 1)))
+    (call $f64_round_pack
+      (i64.and (local.get $a) (i64.const 0x8000000000000000))
+      (i64.sub (local.get $exp_a) (local.get $shift))
+      (i64.shl (local.get $sig) (local.get $shift))))
+
+  (func $f64_sub (export "f64_sub") (param $a i64) (param $b i64) (result i64)
+    (call $f64_add (local.get $a) (i64.xor (local.get $b) (i64.const 0x8000000000000000))))
+
+  (func $f64_mul (export "f64_mul") (param $a i64) (param $b i64) (result i64)
+    (local $sign i64) (local $abs_a i64) (local $abs_b i64) (local $exp_a i64) (local $exp_b i64)
+    (local $sig_a i64) (local $sig_b i64) (local $shift i64) (local $exp i64) (local $sig i64)
+    (local $a_hi i64) (local $a_lo i64) (local $b_hi i64) (local $b_lo i64) (local $lo i64) (local $mid i64) (local $mid1 i64) (local $hi i64)
+    (if (i32.or (call $f64_is_nan (local.get $a)) (call $f64_is_nan (local.get $b)))
+      (then (return (i64.const 0x7ff8000000000000))))
+    (local.set $sign (i64.and (i64.xor (local.get $a) (local.get $b)) (i64.const 0x8000000000000000)))
+    (local.set $abs_a (i64.and (local.get $a) (i64.const 0x7fffffffffffffff)))
+    (local.set $abs_b (i64.and (local.get $b) (i64.const 0x7fffffffffffffff)))
+    (if (i32.or (i64.eq (local.get $abs_a) (i64.const 0x7ff0000000000000)) (i64.eq (local.get $abs_b) (i64.const 0x7ff0000000000000)))
+      (then
+        ;; Infinity times zero.
+        (if (i32.or (i64.eqz (local.get $abs_a)) (i64.eqz (local.get $abs_b)))
+          (then (return (i64.const 0x7ff8000000000000))))
+        (return (i64.or (local.get $sign) (i64.const 0x7ff0000000000000)))))
+    (if (i32.or (i64.eqz (local.get $abs_a)) (i64.eqz (local.get $abs_b)))
+      (then (return (local.get $sign))))
+    ;; Unpack $a, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_a (i64.and (i64.shr_u (local.get $a) (i64.const 52)) (i64.const 0x7ff)))
+    (local.set $sig_a (i64.and (local.get $a) (i64.const 0xfffffffffffff)))
+    (if (i64.eqz (local.get $exp_a))
+      (then
+        (local.set $shift (i64.sub (i64.clz (local.get $sig_a)) (i64.const 11)))
+        (local.set $exp_a (i64.sub (i64.const 1) (local.get $shift)))
+        (local.set $sig_a (i64.shl (local.get $sig_a) (local.get $shift))))
+      (else
+        (local.set $sig_a (i64.or (local.get $sig_a) (i64.const 0x10000000000000)))))
+    ;; Unpack $b, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_b (i64.and (i64.shr_u (local.get $b) (i64.const 52)) (i64.const 0x7ff)))
+    (local.set $sig_b (i64.and (local.get $b) (i64.const 0xfffffffffffff)))
+    (if (i64.eqz (local.get $exp_b))
+      (then
+        (local.set $shift (i64.sub (i64.clz (local.get $sig_b)) (i64.const 11)))
+        (local.set $exp_b (i64.sub (i64.const 1) (local.get $shift)))
+        (local.set $sig_b (i64.shl (local.get $sig_b) (local.get $shift))))
+      (else
+        (local.set $sig_b (i64.or (local.get $sig_b) (i64.const 0x10000000000000)))))
+    (local.set $exp (i64.sub (i64.add (local.get $exp_a) (local.get $exp_b)) (i64.const 1023)))
+    (local.set $sig_a (i64.shl (local.get $sig_a) (i64.const 10)))
+    (local.set $sig_b (i64.shl (local.get $sig_b) (i64.const 11)))
+    ;; The upper half of the 128-bit product, with the lower half jammed into the lowest bit.
+    (local.set $a_hi (i64.shr_u (local.get $sig_a) (i64.const 32)))
+    (local.set $a_lo (i64.and (local.get $sig_a) (i64.const 0xffffffff)))
+    (local.set $b_hi (i64.shr_u (local.get $sig_b) (i64.const 32)))
+    (local.set $b_lo (i64.and (local.get $sig_b) (i64.const 0xffffffff)))
+    (local.set $lo (i64.mul (local.get $a_lo) (local.get $b_lo)))
+    (local.set $mid1 (i64.mul (local.get $a_hi) (local.get $b_lo)))
+    (local.set $mid (i64.add (local.get $mid1) (i64.mul (local.get $a_lo) (local.get $b_hi))))
+    (local.set $hi
+      (i64.add
+        (i64.add
+          (i64.mul (local.get $a_hi) (local.get $b_hi))
+          (i64.shl (i64.extend_i32_u (i64.lt_u (local.get $mid) (local.get $mid1))) (i64.const 32)))
+        (i64.shr_u (local.get $mid) (i64.const 32))))
+    (local.set $mid (i64.shl (local.get $mid) (i64.const 32)))
+    (local.set $lo (i64.add (local.get $lo) (local.get $mid)))
+    (local.set $hi (i64.add (local.get $hi) (i64.extend_i32_u (i64.lt_u (local.get $lo) (local.get $mid)))))
+    (local.set $sig (i64.or (local.get $hi) (i64.extend_i32_u (i64.ne (local.get $lo) (i64.const 0)))))
+    (if (i64.lt_u (local.get $sig) (i64.const 0x4000000000000000))
+      (then
+        (local.set $exp (i64.sub (local.get $exp) (i64.const 1)))
+        (local.set $sig (i64.shl (local.get $sig) (i64.const 1)))))
+    (call $f64_round_pack (local.get $sign) (local.get $exp) (local.get $sig)))
+
+  (func $f64_div (export "f64_div") (param $a i64) (param $b i64) (result i64)
+    (local $sign i64) (local $abs_a i64) (local $abs_b i64) (local $exp_a i64) (local $exp_b i64)
+    (local $sig_a i64) (local $sig_b i64) (local $shift i64) (local $exp i64) (local $sig i64)
+    (local $rem i64) (local $i i32)
+    (if (i32.or (call $f64_is_nan (local.get $a)) (call $f64_is_nan (local.get $b)))
+      (then (return (i64.const 0x7ff8000000000000))))
+    (local.set $sign (i64.and (i64.xor (local.get $a) (local.get $b)) (i64.const 0x8000000000000000)))
+    (local.set $abs_a (i64.and (local.get $a) (i64.const 0x7fffffffffffffff)))
+    (local.set $abs_b (i64.and (local.get $b) (i64.const 0x7fffffffffffffff)))
+    (if (i64.eq (local.get $abs_a) (i64.const 0x7ff0000000000000))
+      (then
+        (if (i64.eq (local.get $abs_b) (i64.const 0x7ff0000000000000))
+          (then (return (i64.const 0x7ff8000000000000))))
+        (return (i64.or (local.get $sign) (i64.const 0x7ff0000000000000)))))
+    (if (i64.eq (local.get $abs_b) (i64.const 0x7ff0000000000000))
+      (then (return (local.get $sign))))
+    (if (i64.eqz (local.get $abs_b))
+      (then
+        (if (i64.eqz (local.get $abs_a))
+          (then (return (i64.const 0x7ff8000000000000))))
+        (return (i64.or (local.get $sign) (i64.const 0x7ff0000000000000)))))
+    (if (i64.eqz (local.get $abs_a))
+      (then (return (local.get $sign))))
+    ;; Unpack $a, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_a (i64.and (i64.shr_u (local.get $a) (i64.const 52)) (i64.const 0x7ff)))
+    (local.set $sig_a (i64.and (local.get $a) (i64.const 0xfffffffffffff)))
+    (if (i64.eqz (local.get $exp_a))
+      (then
+        (local.set $shift (i64.sub (i64.clz (local.get $sig_a)) (i64.const 11)))
+        (local.set $exp_a (i64.sub (i64.const 1) (local.get $shift)))
+        (local.set $sig_a (i64.shl (local.get $sig_a) (local.get $shift))))
+      (else
+        (local.set $sig_a (i64.or (local.get $sig_a) (i64.const 0x10000000000000)))))
+    ;; Unpack $b, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_b (i64.and (i64.shr_u (local.get $b) (i64.const 52)) (i64.const 0x7ff)))
+    (local.set $sig_b (i64.and (local.get $b) (i64.const 0xfffffffffffff)))
+    (if (i64.eqz (local.get $exp_b))
+      (then
+        (local.set $shift (i64.sub (i64.clz (local.get $sig_b)) (i64.const 11)))
+        (local.set $exp_b (i64.sub (i64.const 1) (local.get $shift)))
+        (local.set $sig_b (i64.shl (local.get $sig_b) (local.get $shift))))
+      (else
+        (local.set $sig_b (i64.or (local.get $sig_b) (i64.const 0x10000000000000)))))
+    (local.set $exp (i64.add (i64.sub (local.get $exp_a) (local.get $exp_b)) (i64.const 1022)))
+    ;; Long division, one quotient bit at a time, with the remainder below twice the divisor.
+    (local.set $rem (local.get $sig_a))
+    (if (i64.lt_u (local.get $sig_a) (local.get $sig_b))
+      (then
+        (local.set $exp (i64.sub (local.get $exp) (i64.const 1)))
+        (local.set $rem (i64.shl (local.get $sig_a) (i64.const 1)))))
+    (local.set $sig (i64.const 0))
+    (local.set $i (i32.const 63))
+    (loop $bits
+      (local.set $sig (i64.shl (local.get $sig) (i64.const 1)))
+      (if (i64.ge_u (local.get $rem) (local.get $sig_b))
+        (then
+          (local.set $rem (i64.sub (local.get $rem) (local.get $sig_b)))
+          (local.set $sig (i64.or (local.get $sig) (i64.const 1)))))
+      (local.set $rem (i64.shl (local.get $rem) (i64.const 1)))
+      (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+      (br_if $bits (local.get $i)))
+    (local.set $sig (i64.or (local.get $sig) (i64.extend_i32_u (i64.ne (local.get $rem) (i64.const 0)))))
+    (call $f64_round_pack (local.get $sign) (local.get $exp) (local.get $sig)))
+
+  (func $f64_sqrt (export "f64_sqrt") (param $a i64) (result i64)
+    (local $exp_a i64) (local $sig_a i64) (local $shift i64) (local $exp i64) (local $e i64)
+    (local $sig2 i64) (local $rem i64) (local $root i64) (local $t i64) (local $k i64)
+    (if (call $f64_is_nan (local.get $a))
+      (then (return (i64.const 0x7ff8000000000000))))
+    ;; Zeros of either sign are their own square root.
+    (if (i64.eqz (i64.shl (local.get $a) (i64.const 1)))
+      (then (return (local.get $a))))
+    (if (i64.lt_s (local.get $a) (i64.const 0))
+      (then (return (i64.const 0x7ff8000000000000))))
+    (if (i64.eq (local.get $a) (i64.const 0x7ff0000000000000))
+      (then (return (local.get $a))))
+    ;; Unpack $a, normalizing subnormals so that the hidden bit is set.
+    (local.set $exp_a (i64.and (i64.shr_u (local.get $a) (i64.const 52)) (i64.const 0x7ff)))
+    (local.set $sig_a (i64.and (local.get $a) (i64.const 0xfffffffffffff)))
+    (if (i64.eqz (local.get $exp_a))
+      (then
+        (local.set $shift (i64.sub (i64.clz (local.get $sig_a)) (i64.const 11)))
+        (local.set $exp_a (i64.sub (i64.const 1) (local.get $shift)))
+        (local.set $sig_a (i64.shl (local.get $sig_a) (local.get $shift))))
+      (else
+        (local.set $sig_a (i64.or (local.get $sig_a) (i64.const 0x10000000000000)))))
+    (local.set $e (i64.sub (local.get $exp_a) (i64.const 1023)))
+    (local.set $exp (i64.add (i64.shr_s (local.get $e) (i64.const 1)) (i64.const 1022)))
+    ;; Integer square root of the significand shifted left by 58 or 59 bits, so that the exponent of
+    ;; the radicand is even, computed two bits at a time. The root has its leading bit at bit 55.
+    (local.set $sig2 (i64.shl (local.get $sig_a) (i64.and (local.get $e) (i64.const 1))))
+    (local.set $rem (i64.const 0))
+    (local.set $root (i64.const 0))
+    (local.set $k (i64.const 55))
+    (loop $pairs
+      (local.set $rem (i64.shl (local.get $rem) (i64.const 2)))
+      (if (i64.ge_s (local.get $k) (i64.const 29))
+        (then
+          (local.set $rem
+            (i64.or
+              (local.get $rem)
+              (i64.and
+                (i64.shr_u (local.get $sig2) (i64.shl (i64.sub (local.get $k) (i64.const 29)) (i64.const 1)))
+                (i64.const 3))))))
+      (local.set $t (i64.or (i64.shl (local.get $root) (i64.const 2)) (i64.const 1)))
+      (if (i64.ge_u (local.get $rem) (local.get $t))
+        (then
+          (local.set $rem (i64.sub (local.get $rem) (local.get $t)))
+          (local.set $root (i64.or (i64.shl (local.get $root) (i64.const 1)) (i64.const 1))))
+        (else
+          (local.set $root (i64.shl (local.get $root) (i64.const 1)))))
+      (local.set $k (i64.sub (local.get $k) (i64.const 1)))
+      (br_if $pairs (i64.ge_s (local.get $k) (i64.const 0))))
+    (call $f64_round_pack
+      (i64.const 0)
+      (local.get $exp)
+      (i64.or
+        (i64.shl (local.get $root) (i64.const 7))
+        (i64.extend_i32_u (i64.ne (local.get $rem) (i64.const 0))))))
+
+  (func $f64_min (export "f64_min") (param $a i64) (param $b i64) (result i64)
+    (if (i32.or (call $f64_is_nan (local.get $a)) (call $f64_is_nan (local.get $b)))
+      (then (return (i64.const 0x7ff8000000000000))))
+    (if (call $f64_lt (local.get $a) (local.get $b))
+      (then (return (local.get $a))))
+    (if (call $f64_lt (local.get $b) (local.get $a))
+      (then (return (local.get $b))))
+    ;; Equal operands, which may be zeros of different signs.
+    (i64.or (local.get $a) (local.get $b)))
+
+  (func $f64_max (export "f64_max") (param $a i64) (param $b i64) (result i64)
+    (if (i32.or (call $f64_is_nan (local.get $a)) (call $f64_is_nan (local.get $b)))
+      (then (return (i64.const 0x7ff8000000000000))))
+    (if (call $f64_lt (local.get $b) (local.get $a))
+      (then (return (local.get $a))))
+    (if (call $f64_lt (local.get $a) (local.get $b))
+      (then (return (local.get $b))))
+    (i64.and (local.get $a) (local.get $b)))
+
+  (func $f64_ceil (export "f64_ceil") (param $a i64) (result i64)
+    (call $f64_round (local.get $a) (i32.const 3)))
+
+  (func $f64_floor (export "f64_floor") (param $a i64) (result i64)
+    (call $f64_round (local.get $a) (i32.const 2)))
+
+  (func $f64_trunc (export "f64_trunc") (param $a i64) (result i64)
+    (call $f64_round (local.get $a) (i32.const 1)))
+
+  (func $f64_nearest (export "f64_nearest") (param $a i64) (result i64)
+    (call $f64_round (local.get $a) (i32.const 0)))
+
+  (func $f64_abs (export "f64_abs") (param $a i64) (result i64)
+    (i64.and (local.get $a) (i64.const 0x7fffffffffffffff)))
+
+  (func $f64_neg (export "f64_neg") (param $a i64) (result i64)
+    (i64.xor (local.get $a) (i64.const 0x8000000000000000)))
+
+  (func $f64_copysign (export "f64_copysign") (param $a i64) (param $b i64) (result i64)
+    (i64.or
+      (i64.and (local.get $a) (i64.const 0x7fffffffffffffff))
+      (i64.and (local.get $b) (i64.const 0x8000000000000000))))
+
+  ;; f64 comparisons
+
+  (func $f64_eq (export "f64_eq") (param $a i64) (param $b i64) (result i32)
+    (if (i32.or (call $f64_is_nan (local.get $a)) (call $f64_is_nan (local.get $b)))
+      (then (return (i32.const 0))))
+    ;; Zeros are equal regardless of their signs.
+    (i32.or
+      (i64.eq (local.get $a) (local.get $b))
+      (i64.eqz (i64.shl (i64.or (local.get $a) (local.get $b)) (i64.const 1)))))
+
+  (func $f64_ne (export "f64_ne") (param $a i64) (param $b i64) (result i32)
+    (i32.eqz (call $f64_eq (local.get $a) (local.get $b))))
+
+  (func $f64_lt (export "f64_lt") (param $a i64) (param $b i64) (result i32)
+    (local $sign_a i32)
+    (if (i32.or (call $f64_is_nan (local.get $a)) (call $f64_is_nan (local.get $b)))
+      (then (return (i32.const 0))))
+    (local.set $sign_a (i64.lt_s (local.get $a) (i64.const 0)))
+    (if (i32.ne (local.get $sign_a) (i64.lt_s (local.get $b) (i64.const 0)))
+      (then
+        (return
+          (i32.and
+            (local.get $sign_a)
+            (i64.ne (i64.shl (i64.or (local.get $a) (local.get $b)) (i64.const 1)) (i64.const 0))))))
+    (i32.and
+      (i64.ne (local.get $a) (local.get $b))
+      (i32.xor (local.get $sign_a) (i64.lt_u (local.get $a) (local.get $b)))))
+
+  (func $f64_le (export "f64_le") (param $a i64) (param $b i64) (result i32)
+    (local $sign_a i32)
+    (if (i32.or (call $f64_is_nan (local.get $a)) (call $f64_is_nan (local.get $b)))
+      (then (return (i32.const 0))))
+    (local.set $sign_a (i64.lt_s (local.get $a) (i64.const 0)))
+    (if (i32.ne (local.get $sign_a) (i64.lt_s (local.get $b) (i64.const 0)))
+      (then
+        (return
+          (i32.or
+            (local.get $sign_a)
+            (i64.eqz (i64.shl (i64.or (local.get $a) (local.get $b)) (i64.const 1)))))))
+    (i32.or
+      (i64.eq (local.get $a) (local.get $b))
+      (i32.xor (local.get $sign_a) (i64.lt_u (local.get $a) (local.get $b)))))
+
+  (func $f64_gt (export "f64_gt") (param $a i64) (param $b i64) (result i32)
+    (call $f64_lt (local.get $b) (local.get $a)))
+
+  (func $f64_ge (export "f64_ge") (param $a i64) (param $b i64) (result i32)
+    (call $f64_le (local.get $b) (local.get $a)))
+
+  ;; Conversions
+
+  ;; Converts a magnitude and a sign bit to the nearest f32.
+  (func $f32_from_u64 (param $sign i32) (param $mag i64) (result i32)
+    (local $clz i64) (local $norm i64)
+    (if (i64.eqz (local.get $mag))
+      (then (return (i32.const 0))))
+    (local.set $clz (i64.clz (local.get $mag)))
+    (local.set $norm (i64.shl (local.get $mag) (local.get $clz)))
+    (call $f32_round_pack
+      (local.get $sign)
+      (i32.sub (i32.const 0xbd) (i32.wrap_i64 (local.get $clz)))
+      (i32.or
+        (i32.wrap_i64 (i64.shr_u (local.get $norm) (i64.const 33)))
+        (i64.ne (i64.shl (local.get $norm) (i64.const 31)) (i64.const 0)))))
+
+  ;; Converts a magnitude and a sign bit to the nearest f64.
+  (func $f64_from_u64 (param $sign i64) (param $mag i64) (result i64)
+    (local $clz i64) (local $norm i64)
+    (if (i64.eqz (local.get $mag))
+      (then (return (i64.const 0))))
+    (local.set $clz (i64.clz (local.get $mag)))
+    (local.set $norm (i64.shl (local.get $mag) (local.get $clz)))
+    (call $f64_round_pack
+      (local.get $sign)
+      (i64.sub (i64.const 0x43d) (local.get $clz))
+      (i64.or
+        (i64.shr_u (local.get $norm) (i64.const 1))
+        (i64.and (local.get $norm) (i64.const 1)))))
+
+  (func $f32_convert_i32_s (export "f32_convert_i32_s") (param $a i32) (result i32)
+    (call $f32_from_u64
+      (i32.and (local.get $a) (i32.const 0x80000000))
+      (i64.extend_i32_u
+        (select
+          (i32.sub (i32.const 0) (local.get $a))
+          (local.get $a)
+          (i32.lt_s (local.get $a) (i32.const 0))))))
+
+  (func $f32_convert_i32_u (export "f32_convert_i32_u") (param $a i32) (result i32)
+    (call $f32_from_u64 (i32.const 0) (i64.extend_i32_u (local.get $a))))
+
+  (func $f32_convert_i64_s (export "f32_convert_i64_s") (param $a i64) (result i32)
+    (call $f32_from_u64
+      (i32.and (i32.wrap_i64 (i64.shr_u (local.get $a) (i64.const 32))) (i32.const 0x80000000))
+      (select
+        (i64.sub (i64.const 0) (local.get $a))
+        (local.get $a)
+        (i64.lt_s (local.get $a) (i64.const 0)))))
+
+  (func $f32_convert_i64_u (export "f32_convert_i64_u") (param $a i64) (result i32)
+    (call $f32_from_u64 (i32.const 0) (local.get $a)))
+
+  (func $f64_convert_i32_s (export "f64_convert_i32_s") (param $a i32) (result i64)
+    (call $f64_from_u64
+      (i64.shl (i64.extend_i32_u (i32.and (local.get $a) (i32.const 0x80000000))) (i64.const 32))
+      (i64.extend_i32_u
+        (select
+          (i32.sub (i32.const 0) (local.get $a))
+          (local.get $a)
+          (i32.lt_s (local.get $a) (i32.const 0))))))
+
+  (func $f64_convert_i32_u (export "f64_convert_i32_u") (param $a i32) (result i64)
+    (call $f64_from_u64 (i64.const 0) (i64.extend_i32_u (local.get $a))))
+
+  (func $f64_convert_i64_s (export "f64_convert_i64_s") (param $a i64) (result i64)
+    (call $f64_from_u64
+      (i64.and (local.get $a) (i64.const 0x8000000000000000))
+      (select
+        (i64.sub (i64.const 0) (local.get $a))
+        (local.get $a)
+        (i64.lt_s (local.get $a) (i64.const 0)))))
+
+  (func $f64_convert_i64_u (export "f64_convert_i64_u") (param $a i64) (result i64)
+    (call $f64_from_u64 (i64.const 0) (local.get $a)))
+
+  (func $i32_trunc_f32_s (export "i32_trunc_f32_s") (param $a i32) (result i32)
+    (call $i32_trunc_s (i32.lt_s (local.get $a) (i32.const 0)) (call $f32_trunc_magnitude (local.get $a))))
+
+  (func $i32_trunc_f32_u (export "i32_trunc_f32_u") (param $a i32) (result i32)
+    (call $i32_trunc_u (i32.lt_s (local.get $a) (i32.const 0)) (call $f32_trunc_magnitude (local.get $a))))
+
+  (func $i64_trunc_f32_s (export "i64_trunc_f32_s") (param $a i32) (result i64)
+    (call $i64_trunc_s (i32.lt_s (local.get $a) (i32.const 0)) (call $f32_trunc_magnitude (local.get $a))))
+
+  (func $i64_trunc_f32_u (export "i64_trunc_f32_u") (param $a i32) (result i64)
+    (call $i64_trunc_u (i32.lt_s (local.get $a) (i32.const 0)) (call $f32_trunc_magnitude (local.get $a))))
+
+  (func $i32_trunc_f64_s (export "i32_trunc_f64_s") (param $a i64) (result i32)
+    (call $i32_trunc_s (i64.lt_s (local.get $a) (i64.const 0)) (call $f64_trunc_magnitude (local.get $a))))
+
+  (func $i32_trunc_f64_u (export "i32_trunc_f64_u") (param $a i64) (result i32)
+    (call $i32_trunc_u (i64.lt_s (local.get $a) (i64.const 0)) (call $f64_trunc_magnitude (local.get $a))))
+
+  (func $i64_trunc_f64_s (export "i64_trunc_f64_s") (param $a i64) (result i64)
+    (call $i64_trunc_s (i64.lt_s (local.get $a) (i64.const 0)) (call $f64_trunc_magnitude (local.get $a))))
+
+  (func $i64_trunc_f64_u (export "i64_trunc_f64_u") (param $a i64) (result i64)
+    (call $i64_trunc_u (i64.lt_s (local.get $a) (i64.const 0)) (call $f64_trunc_magnitude (local.get $a))))
+
+  ;; Applies the sign to a truncated magnitude, trapping if the result is out of range.
+  (func $i32_trunc_s (param $negative i32) (param $mag i64) (result i32)
+    (if (i64.gt_u
+          (local.get $mag)
+          (i64.add (i64.const 0x7fffffff) (i64.extend_i32_u (local.get $negative))))
+      (then (unreachable)))
+    (select
+      (i32.sub (i32.const 0) (i32.wrap_i64 (local.get $mag)))
+      (i32.wrap_i64 (local.get $mag))
+      (local.get $negative)))
+
+  (func $i32_trunc_u (param $negative i32) (param $mag i64) (result i32)
+    (if (i32.or
+          (i32.and (local.get $negative) (i64.ne (local.get $mag) (i64.const 0)))
+          (i64.gt_u (local.get $mag) (i64.const 0xffffffff)))
+      (then (unreachable)))
+    (i32.wrap_i64 (local.get $mag)))
+
+  (func $i64_trunc_s (param $negative i32) (param $mag i64) (result i64)
+    (if (i64.gt_u
+          (local.get $mag)
+          (i64.add (i64.const 0x7fffffffffffffff) (i64.extend_i32_u (local.get $negative))))
+      (then (unreachable)))
+    (select
+      (i64.sub (i64.const 0) (local.get $mag))
+      (local.get $mag)
+      (local.get $negative)))
+
+  (func $i64_trunc_u (param $negative i32) (param $mag i64) (result i64)
+    (if (i32.and (local.get $negative) (i64.ne (local.get $mag) (i64.const 0)))
+      (then (unreachable)))
+    (local.get $mag))
+
+  (func $f64_promote_f32 (export "f64_promote_f32") (param $a i32) (result i64)
+    (local $sign i64) (local $abs i32) (local $exp i32) (local $sig i32) (local $shift i32)
+    (if (call $f32_is_nan (local.get $a))
+      (then (return (i64.const 0x7ff8000000000000))))
+    (local.set $sign
+      (i64.shl (i64.extend_i32_u (i32.and (local.get $a) (i32.const 0x80000000))) (i64.const 32)))
+    (local.set $abs (i32.and (local.get $a) (i32.const 0x7fffffff)))
+    (if (i32.eq (local.get $abs) (i32.const 0x7f800000))
+      (then (return (i64.or (local.get $sign) (i64.const 0x7ff0000000000000)))))
+    (if (i32.eqz (local.get $abs))
+      (then (return (local.get $sign))))
+    (local.set $exp (i32.shr_u (local.get $abs) (i32.const 23)))
+    (local.set $sig (i32.and (local.get $abs) (i32.const 0x7fffff)))
+    (if (i32.eqz (local.get $exp))
+      (then
+        ;; Normalize the subnormal. The hidden bit carries into the exponent.
+        (local.set $shift (i32.sub (i32.clz (local.get $sig)) (i32.const 8)))
+        (local.set $exp (i32.sub (i32.const 0) (local.get $shift)))
+        (local.set $sig (i32.shl (local.get $sig) (local.get $shift)))))
+    (i64.or
+      (local.get $sign)
+      (i64.add
+        (i64.shl (i64.extend_i32_u (i32.add (local.get $exp) (i32.const 0x380))) (i64.const 52))
+        (i64.shl (i64.extend_i32_u (local.get $sig)) (i64.const 29)))))
+
+  (func $f32_demote_f64 (export "f32_demote_f64") (param $a i64) (result i32)
+    (local $sign i32) (local $abs i64) (local $exp i32) (local $sig i32)
+    (if (call $f64_is_nan (local.get $a))
+      (then (return (i32.const 0x7fc00000))))
+    (local.set $sign
+      (i32.and (i32.wrap_i64 (i64.shr_u (local.get $a) (i64.const 32))) (i32.const 0x80000000)))
+    (local.set $abs (i64.and (local.get $a) (i64.const 0x7fffffffffffffff)))
+    (if (i64.eq (local.get $abs) (i64.const 0x7ff0000000000000))
+      (then (return (i32.or (local.get $sign) (i32.const 0x7f800000)))))
+    (local.set $exp (i32.wrap_i64 (i64.shr_u (local.get $abs) (i64.const 52))))
+    (local.set $sig
+      (i32.wrap_i64
+        (i64.or
+          (i64.shr_u (i64.and (local.get $abs) (i64.const 0xfffffffffffff)) (i64.const 22))
+          (i64.extend_i32_u
+            (i64.ne (i64.and (local.get $abs) (i64.const 0x3fffff)) (i64.const 0))))))
+    (if (i32.eqz (i32.or (local.get $exp) (local.get $sig)))
+      (then (return (local.get $sign))))
+    (call $f32_round_pack
+      (local.get $sign)
+      (i32.sub (local.get $exp) (i32.const 0x381))
+      (i32.or (local.get $sig) (i32.const 0x40000000))))
+)
@@ -0,0 +1,232 @@
+//! Batched execution of translators which operate on an IR other than parity-wasm.
+//! Running such a translator means serializing the module, parsing it into the other IR, and
+//! converting the result back, which dominates the cost of the translator on large modules.
+//! Consecutive translators on the same IR share a single conversion to and from it.
+
+use std::sync::Arc;
+
+use parity_wasm::elements::Module;
+
+use super::diff::Changes;
+use super::proposals;
+use super::ModuleError;
+
+/// A translator operating on the walrus IR.
+pub trait WalrusTranslator {
+    /// Translates the module in place. Returns true if the module was mutated.
+    fn translate_walrus(&self, module: &mut walrus::Module) -> Result<bool, ModuleError>;
+
+    /// Translates the module in place, like `translate_walrus`, and describes the changes made if
+    /// the module was mutated. The size change is measured by the batch.
+    fn translate_walrus_with_changes(
+        &self,
+        module: &mut walrus::Module,
+    ) -> Result<Option<Changes>, ModuleError> {
+        Ok(self.translate_walrus(module)?.then(Changes::default))
+    }
+}
+
+/// A translator operating on the binaryen IR.
+#[cfg(feature = "binaryen")]
+pub trait BinaryenTranslator {
+    /// Translates the module in place. Returns true if the module was mutated. `debug_info` is
+    /// set if the original module has a names section, which should be preserved.
+    fn translate_binaryen(
+        &self,
+        module: &mut binaryen::Module,
+        debug_info: bool,
+    ) -> Result<bool, ModuleError>;
+}
+
+/// Runs the translators in order on a single walrus conversion of `module`, and returns the
+/// result of each, with changes if the translator mutated the module. The changes are only
+/// itemized and measured if `record_changes` is set, as measuring means emitting the module
+/// after every translator. The module is only converted back if a translator mutated it. If
+/// either conversion fails, every translator gets the error. If a translator fails, the module is
+/// left unchanged and the translators after it are skipped.
+pub fn translate_walrus(
+    module: &mut Module,
+    translators: &[&dyn WalrusTranslator],
+    record_changes: bool,
+) -> Vec<Result<Option<Changes>, ModuleError>> {
+    let bytes = match proposals::into_bytes(module.clone()) {
+        Ok(bytes) => bytes,
+        Err(e) => return vec![Err(e); translators.len()],
+    };
+    let mut converted = match walrus::Module::from_buffer(&bytes) {
+        Ok(converted) => converted,
+        Err(e) => {
+            let e = ModuleError::External("walrus", Arc::new(e.compat()));
+            return vec![Err(e); translators.len()];
+        }
+    };
+
+    let mut size = bytes.len();
+    let results = run_batch(translators, |translator| {
+        if !record_changes {
+            return Ok(translator
+                .translate_walrus(&mut converted)?
+                .then(Changes::default));
+        }
+        let mut changes = translator.translate_walrus_with_changes(&mut converted)?;
+        if let Some(changes) = changes.as_mut() {
+            let new_size = converted.emit_wasm().len();
+            changes.add_size_delta(new_size as isize - size as isize);
+            size = new_size;
+        }
+        Ok(changes)
+    });
+
+    if results.iter().any(|result| matches!(result, Ok(Some(_)))) {
+        match proposals::from_bytes(&converted.emit_wasm()[..]) {
+            Ok(output) => *module = with_names(output),
+            Err(e) => return vec![Err(e); translators.len()],
+        }
+    }
+    results
+}
+
+/// Runs the translators in order on a single binaryen conversion of `module`, and returns the
+/// result of each, with changes if the translator mutated the module. The size change of each
+/// translator is only measured if `record_changes` is set, as measuring means writing the module
+/// after every translator. The module is only converted back if a translator mutated it. If
+/// either conversion fails, every translator gets the error. If a translator fails, the module is
+/// left unchanged and the translators after it are skipped.
+#[cfg(feature = "binaryen")]
+pub fn translate_binaryen(
+    module: &mut Module,
+    translators: &[&dyn BinaryenTranslator],
+    record_changes: bool,
+) -> Vec<Result<Option<Changes>, ModuleError>> {
+    let debug_info = module.has_names_section();
+    let bytes = match proposals::into_bytes(module.clone()) {
+        Ok(bytes) => bytes,
+        Err(e) => return vec![Err(e); translators.len()],
+    };
+    let mut converted = match binaryen::Module::read(&bytes) {
+        Ok(converted) => converted,
+        Err(_) => {
+            // Binaryen does not report why reading failed.
+            let reason: Box<dyn std::error::Error + Send + Sync> =
+                "failed to deserialise the module".into();
+            let e = ModuleError::External("binaryen", reason.into());
+            return vec![Err(e); translators.len()];
+        }
+    };
+
+    let mut size = bytes.len();
+    let results = run_batch(translators, |translator| {
+        let mut changes = translator
+            .translate_binaryen(&mut converted, debug_info)?
+            .then(Changes::default);
+        if let (true, Some(changes)) = (record_changes, changes.as_mut()) {
+            let new_size = converted.write().len();
+            changes.add_size_delta(new_size as isize - size as isize);
+            size = new_size;
+        }
+        Ok(changes)
+    });
+
+    if results.iter().any(|result| matches!(result, Ok(Some(_)))) {
+        match proposals::from_bytes(&converted.write()) {
+            Ok(output) => *module = with_names(output),
+            Err(e) => return vec![Err(e); translators.len()],
+        }
+    }
+    results
+}
+
+/// Runs `run` on each translator in order, and returns the result of each. A translator which
+/// fails may leave the converted module half-mutated, so the translators after it are skipped,
+/// and the changes of those before it are dropped along with the converted module.
+fn run_batch<T>(
+    translators: &[T],
+    mut run: impl FnMut(&T) -> Result<Option<Changes>, ModuleError>,
+) -> Vec<Result<Option<Changes>, ModuleError>> {
+    let mut results = Vec::with_capacity(translators.len());
+    for translator in translators {
+        match run(translator) {
+            Ok(changes) => results.push(Ok(changes)),
+            Err(e) => {
+                for result in results.iter_mut() {
+                    *result = Ok(None);
+                }
+                results.push(Err(e));
+                let skipped = ModuleError::Custom(
+                    "skipped, as an earlier module in the batch failed".to_string(),
+                );
+                results.resize(translators.len(), Err(skipped));
+                break;
+            }
+        }
+    }
+    results
+}
+
+/// Parses the names section of a module converted back from another IR, so that later modules
+/// see function names. A names section which fails to parse is left as a custom section, as it
+/// is for input modules.
+fn with_names(module: Module) -> Module {
+    module.parse_names().unwrap_or_else(|(_, module)| module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(Result<bool, ModuleError>);
+
+    impl WalrusTranslator for Fixed {
+        fn translate_walrus(&self, _module: &mut walrus::Module) -> Result<bool, ModuleError> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn walrus_stops_at_error() {
+        let mut module = Module::default();
+        let results = translate_walrus(
+            &mut module,
+            &[
+                &Fixed(Ok(true)),
+                &Fixed(Err(ModuleError::NotSupported)),
+                &Fixed(Ok(true)),
+            ],
+            false,
+        );
+        assert_eq!(results[..2], [Ok(None), Err(ModuleError::NotSupported)]);
+        assert!(matches!(results[2], Err(ModuleError::Custom(_))));
+        assert_eq!(module, Module::default());
+    }
+
+    #[test]
+    fn walrus_results_in_order() {
+        let mut module = Module::default();
+        let results = translate_walrus(
+            &mut module,
+            &[&Fixed(Ok(false)), &Fixed(Ok(true)), &Fixed(Ok(false))],
+            false,
+        );
+        assert_eq!(
+            results,
+            vec![Ok(None), Ok(Some(Changes::default())), Ok(None)]
+        );
+    }
+
+    #[test]
+    fn walrus_unchanged_module() {
+        let mut module = Module::default();
+        let results = translate_walrus(&mut module, &[&Fixed(Ok(false)), &Fixed(Ok(false))], true);
+        assert_eq!(results, vec![Ok(None), Ok(None)]);
+        assert_eq!(module, Module::default());
+    }
+
+    #[test]
+    fn walrus_records_size_changes() {
+        let mut module = Module::default();
+        let results = translate_walrus(&mut module, &[&Fixed(Ok(true))], true);
+        let changes = results[0].clone().unwrap().expect("Should be mutated");
+        assert!(changes.changes().is_empty());
+        assert!(changes.size_delta().is_some());
+    }
+}
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use parity_wasm::elements::Module;
 
+use super::batch::{translate_binaryen, BinaryenTranslator};
+use super::diff::Changes;
 use super::{ChiselModule, ModuleError, ModuleKind, ModulePreset, ModuleTranslator};
 
 // FIXME: change level names
@@ -58,72 +60,77 @@ impl ModulePreset for BinaryenOptimiser {
     }
 }
 
-impl ModuleTranslator for BinaryenOptimiser {
-    fn translate_inplace(&self, _module: &mut Module) -> Result<bool, ModuleError> {
-        Err(ModuleError::NotSupported)
-    }
-
-    fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
-        let has_names_section = module.has_names_section();
-
+impl BinaryenOptimiser {
+    fn codegen_config(&self, debug_info: bool) -> binaryen::CodegenConfig {
         // FIXME: could just move this into `BinaryenOptimiser`
-        let config = match &self {
+        match self {
             BinaryenOptimiser::O0 => binaryen::CodegenConfig {
                 optimization_level: 0,
                 shrink_level: 0,
-                debug_info: has_names_section,
+                debug_info,
             },
             BinaryenOptimiser::O1 => binaryen::CodegenConfig {
                 optimization_level: 1,
                 shrink_level: 0,
-                debug_info: has_names_section,
+                debug_info,
             },
             BinaryenOptimiser::O2 => binaryen::CodegenConfig {
                 optimization_level: 2,
                 shrink_level: 0,
-                debug_info: has_names_section,
+                debug_info,
             },
             BinaryenOptimiser::O3 => binaryen::CodegenConfig {
                 optimization_level: 3,
                 shrink_level: 0,
-                debug_info: has_names_section,
+                debug_info,
             },
             BinaryenOptimiser::O4 => binaryen::CodegenConfig {
                 optimization_level: 4,
                 shrink_level: 0,
-                debug_info: has_names_section,
+                debug_info,
             },
             BinaryenOptimiser::Os => binaryen::CodegenConfig {
                 optimization_level: 2,
                 shrink_level: 1,
-                debug_info: has_names_section,
+                debug_info,
             },
             BinaryenOptimiser::Oz => binaryen::CodegenConfig {
                 optimization_level: 2,
                 shrink_level: 2,
-                debug_info: has_names_section,
+                debug_info,
             },
-        };
+        }
+    }
+}
 
-        let serialized = module.clone().to_bytes()?;
-        let output = binaryen_optimiser(&serialized, &config)?;
-        let output = Module::from_bytes(&output)?;
-        Ok(Some(output))
+impl BinaryenTranslator for BinaryenOptimiser {
+    fn translate_binaryen(
+        &self,
+        module: &mut binaryen::Module,
+        debug_info: bool,
+    ) -> Result<bool, ModuleError> {
+        module.optimize(&self.codegen_config(debug_info));
+        Ok(true)
     }
 }
 
-fn binaryen_optimiser(
-    input: &[u8],
-    config: &binaryen::CodegenConfig,
-) -> Result<Vec<u8>, ModuleError> {
-    match binaryen::Module::read(&input) {
-        Ok(mut module) => {
-            module.optimize(&config);
-            Ok(module.write())
-        }
-        Err(_) => Err(ModuleError::Custom(
-            "Failed to deserialise binary with binaryen".to_string(),
-        )),
+impl ModuleTranslator for BinaryenOptimiser {
+    fn translate_inplace(&self, module: &mut Module) -> Result<bool, ModuleError> {
+        Ok(
+            translate_binaryen(module, &[self as &dyn BinaryenTranslator], false)
+                .remove(0)?
+                .is_some(),
+        )
+    }
+
+    fn translate_with_changes(&self, module: &mut Module) -> Result<Option<Changes>, ModuleError> {
+        translate_binaryen(module, &[self as &dyn BinaryenTranslator], true).remove(0)
+    }
+
+    fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
+        let mut output = module.clone();
+        self.translate_inplace(&mut output)?;
+        Ok(Some(output))
     }
 }
 
@@ -148,7 +155,7 @@ mod tests {
         let module = Module::from_bytes(&input).unwrap();
         let translator = BinaryenOptimiser::with_preset("O0").unwrap();
         let result = translator.translate(&module).unwrap().unwrap();
-        let serialized = result.to_bytes().unwrap();
+        let serialized = result.into_bytes().unwrap();
         assert_eq!(expected, serialized);
     }
 }
@@ -0,0 +1,289 @@
+//! Canonicalizes the NaNs produced by float instructions.
+//! Engines may give a NaN produced by float arithmetic any payload and sign, so its bit pattern
+//! differs between them. Every instruction which can produce a NaN is followed by a sequence which
+//! replaces a NaN result by the canonical NaN, with a positive sign, so that float results are
+//! identical across engines.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use parity_wasm::elements::{ImportCountType, Instruction, Local, Module, Type, ValueType};
+
+use super::checkfloat::Function;
+use super::{ChiselModule, ModuleError, ModuleKind, ModuleTranslator};
+
+const CANONICAL_NAN_F32: u32 = 0x7fc0_0000;
+const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+pub struct CanonicalizeNan;
+
+/// The number of canonicalizations inserted into a function.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Instrumented {
+    pub function: Function,
+    pub sites: usize,
+}
+
+impl<'a> ChiselModule<'a> for CanonicalizeNan {
+    type ObjectReference = &'a dyn ModuleTranslator;
+
+    fn id(&'a self) -> String {
+        "canonicalizenan".to_string()
+    }
+
+    fn kind(&'a self) -> ModuleKind {
+        ModuleKind::Translator
+    }
+
+    fn as_abstract(&'a self) -> Self::ObjectReference {
+        self as Self::ObjectReference
+    }
+
+    fn with_defaults() -> Result<Self, ModuleError> {
+        Ok(CanonicalizeNan {})
+    }
+
+    fn with_config(_config: &HashMap<String, String>) -> Result<Self, ModuleError> {
+        Err(ModuleError::NotSupported)
+    }
+}
+
+impl ModuleTranslator for CanonicalizeNan {
+    fn translate_inplace(&self, module: &mut Module) -> Result<bool, ModuleError> {
+        Ok(!self.instrument(module).is_empty())
+    }
+
+    fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
+        let mut ret = module.clone();
+        if self.instrument(&mut ret).is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(ret))
+        }
+    }
+}
+
+impl CanonicalizeNan {
+    /// Inserts a canonicalization after every instruction which can produce a NaN. Returns the
+    /// functions which were instrumented, in order.
+    pub fn instrument(&self, module: &mut Module) -> Vec<Instrumented> {
+        let imported = module.import_count(ImportCountType::Function) as u32;
+        let types: Vec<Type> = module
+            .type_section()
+            .map_or(vec![], |section| section.types().to_vec());
+        let params: Vec<u32> = module.function_section().map_or(vec![], |section| {
+            section
+                .entries()
+                .iter()
+                .map(|func| match types.get(func.type_ref() as usize) {
+                    Some(Type::Function(ty)) => ty.params().len() as u32,
+                    None => 0,
+                })
+                .collect()
+        });
+        let names: HashMap<u32, String> = module
+            .names_section()
+            .and_then(|section| section.functions())
+            .map_or(HashMap::new(), |names| {
+                names
+                    .names()
+                    .iter()
+                    .map(|(idx, name)| (idx, name.clone()))
+                    .collect()
+            });
+
+        let bodies = match module.code_section_mut() {
+            Some(section) => section.bodies_mut(),
+            None => return vec![],
+        };
+        let mut instrumented = vec![];
+        for (idx, body) in bodies.iter_mut().enumerate() {
+            // The temporaries are added after the existing locals, when first needed.
+            let mut next_local = params.get(idx).cloned().unwrap_or(0)
+                + body.locals().iter().map(|local| local.count()).sum::<u32>();
+            let mut temps: HashMap<ValueType, u32> = HashMap::new();
+            let mut new_locals = vec![];
+            let mut sites = 0;
+
+            let instructions = body.code_mut().elements_mut();
+            let mut canonicalized = Vec::with_capacity(instructions.len());
+            for instruction in instructions.drain(..) {
+                let ty = nan_result(&instruction);
+                canonicalized.push(instruction);
+                if let Some(ty) = ty {
+                    let temp = *temps.entry(ty).or_insert_with(|| {
+                        new_locals.push(Local::new(1, ty));
+                        next_local += 1;
+                        next_local - 1
+                    });
+                    canonicalized.extend(canonicalize(ty, temp));
+                    sites += 1;
+                }
+            }
+            *instructions = canonicalized;
+            body.locals_mut().extend(new_locals);
+
+            if sites > 0 {
+                let index = imported + idx as u32;
+                instrumented.push(Instrumented {
+                    function: Function {
+                        index,
+                        name: names.get(&index).cloned(),
+                    },
+                    sites,
+                });
+            }
+        }
+        instrumented
+    }
+}
+
+/// Returns the type of the result of an instruction which can produce a NaN.
+fn nan_result(instruction: &Instruction) -> Option<ValueType> {
+    match instruction {
+        Instruction::F32Add
+        | Instruction::F32Sub
+        | Instruction::F32Mul
+        | Instruction::F32Div
+        | Instruction::F32Sqrt
+        | Instruction::F32Min
+        | Instruction::F32Max
+        | Instruction::F32Ceil
+        | Instruction::F32Floor
+        | Instruction::F32Trunc
+        | Instruction::F32Nearest
+        | Instruction::F32DemoteF64 => Some(ValueType::F32),
+        Instruction::F64Add
+        | Instruction::F64Sub
+        | Instruction::F64Mul
+        | Instruction::F64Div
+        | Instruction::F64Sqrt
+        | Instruction::F64Min
+        | Instruction::F64Max
+        | Instruction::F64Ceil
+        | Instruction::F64Floor
+        | Instruction::F64Trunc
+        | Instruction::F64Nearest
+        | Instruction::F64PromoteF32 => Some(ValueType::F64),
+        _ => None,
+    }
+}
+
+/// Returns the instructions replacing the value on top of the stack by the canonical NaN if it is
+/// a NaN, using a temporary local of its type. A NaN is the only value not equal to itself.
+fn canonicalize(ty: ValueType, temp: u32) -> Vec<Instruction> {
+    let (nan, eq) = match ty {
+        ValueType::F32 => (Instruction::F32Const(CANONICAL_NAN_F32), Instruction::F32Eq),
+        _ => (Instruction::F64Const(CANONICAL_NAN_F64), Instruction::F64Eq),
+    };
+    vec![
+        Instruction::TeeLocal(temp),
+        nan,
+        Instruction::GetLocal(temp),
+        Instruction::GetLocal(temp),
+        eq,
+        Instruction::Select,
+    ]
+}
+
+impl fmt::Display for Instrumented {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} NaN canonicalization{}",
+            self.function,
+            self.sites,
+            if self.sites == 1 { "" } else { "s" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hex::FromHex;
+
+    use super::*;
+
+    #[test]
+    fn instrument() {
+        // (module
+        //   (func $main (export "main") (param $x f32) (param $y f64) (result f32)
+        //     (local $z i32)
+        //     (drop (f64.sqrt (local.get $y)))
+        //     (f32.div (local.get $x) (f32.abs (local.get $x))))
+        //   (func $bits (param $x f32) (result i32)
+        //     (i32.reinterpret_f32 (f32.neg (local.get $x)))))
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d01000000010c0260027d7c017d60017d017f0303020001070801046d61696e00000a17020e01
+017f20019f1a200020008b950b060020008cbc0b0027046e616d65010d0200046d61696e0104626974730211
+02000300017801017902017a0101000178",
+        )
+        .unwrap();
+        let mut module = Module::from_bytes(&wasm).unwrap().parse_names().unwrap();
+
+        let instrumented = CanonicalizeNan {}.instrument(&mut module);
+        assert_eq!(
+            instrumented,
+            vec![Instrumented {
+                function: Function {
+                    index: 0,
+                    name: Some("main".to_string())
+                },
+                sites: 2
+            }]
+        );
+        assert_eq!(
+            instrumented[0].to_string(),
+            "function 0 ($main): 2 NaN canonicalizations"
+        );
+
+        let body = &module.code_section().unwrap().bodies()[0];
+        assert_eq!(
+            body.locals(),
+            &[
+                Local::new(1, ValueType::I32),
+                Local::new(1, ValueType::F64),
+                Local::new(1, ValueType::F32)
+            ]
+        );
+        assert_eq!(
+            body.code().elements(),
+            &[
+                Instruction::GetLocal(1),
+                Instruction::F64Sqrt,
+                Instruction::TeeLocal(3),
+                Instruction::F64Const(CANONICAL_NAN_F64),
+                Instruction::GetLocal(3),
+                Instruction::GetLocal(3),
+                Instruction::F64Eq,
+                Instruction::Select,
+                Instruction::Drop,
+                Instruction::GetLocal(0),
+                Instruction::GetLocal(0),
+                Instruction::F32Abs,
+                Instruction::F32Div,
+                Instruction::TeeLocal(4),
+                Instruction::F32Const(CANONICAL_NAN_F32),
+                Instruction::GetLocal(4),
+                Instruction::GetLocal(4),
+                Instruction::F32Eq,
+                Instruction::Select,
+                Instruction::End,
+            ]
+        );
+        // Sign and bit manipulations do not produce NaNs.
+        assert_eq!(
+            module.code_section().unwrap().bodies()[1]
+                .code()
+                .elements()
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn no_floats() {
+        let module = Module::default();
+        assert_eq!(CanonicalizeNan {}.translate(&module), Ok(None));
+    }
+}
@@ -1,11 +1,45 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-use parity_wasm::elements::{Instruction, Module};
+use log::debug;
+use parity_wasm::elements::{
+    External, ImportCountType, Instruction, Internal, Module, Type, ValueType,
+};
 
+use super::depgraph::{DepGraph, DepGraphBuilder};
+use super::instructions::Class;
+use super::proposals;
 use super::{ChiselModule, ModuleError, ModuleKind, ModuleValidator};
 
 /// Struct on which ModuleValidator is implemented.
-pub struct CheckFloat {}
+pub struct CheckFloat {
+    /// If set, only code reachable from the "main" export and the start function is checked.
+    reachable_only: bool,
+}
+
+/// A function, by its index in the function index space, and its name if the module has a names
+/// section.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Function {
+    pub index: u32,
+    pub name: Option<String>,
+}
+
+/// A use of floating point types or instructions.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum FloatUse {
+    /// A function with float parameters or results.
+    Signature(Function),
+    /// A float local of a function, by its index, which counts the parameters first.
+    Local(Function, u32),
+    /// A float instruction, by its offset in the instructions of the function body.
+    Instruction(Function, usize, Instruction),
+    /// A non-trapping float-to-int conversion, by its offset in the instructions of the function
+    /// body and its name.
+    Conversion(Function, usize, &'static str),
+    /// A float global, by its index in the global index space.
+    Global(u32),
+}
 
 impl<'a> ChiselModule<'a> for CheckFloat {
     type ObjectReference = &'a dyn ModuleValidator;
@@ -23,106 +57,231 @@ impl<'a> ChiselModule<'a> for CheckFloat {
     }
 
     fn with_defaults() -> Result<Self, ModuleError> {
-        Ok(CheckFloat {})
+        Ok(CheckFloat::new(false))
     }
 
-    fn with_config(_config: &HashMap<String, String>) -> Result<Self, ModuleError> {
-        Err(ModuleError::NotSupported)
+    fn with_config(config: &HashMap<String, String>) -> Result<Self, ModuleError> {
+        match config.get("reachable_only").map(|value| value.as_str()) {
+            None | Some("false") => Ok(CheckFloat::new(false)),
+            Some("true") => Ok(CheckFloat::new(true)),
+            Some(value) => Err(ModuleError::InvalidConfig(
+                "reachable_only".to_string(),
+                format!("expected true or false, found '{}'", value),
+            )),
+        }
     }
 }
 
-impl ModuleValidator for CheckFloat {
+impl CheckFloat {
+    pub fn new(reachable_only: bool) -> Self {
+        CheckFloat { reachable_only }
+    }
+
+    /// Returns every use of floating point types or instructions in the module: globals first, then
+    /// the signature, locals and instructions of each function, in order.
     // NOTE: this will not check for SIMD instructions.
-    fn validate(&self, module: &Module) -> Result<bool, ModuleError> {
-        let code_section = module.code_section();
-        if code_section.is_none() {
-            return Err(ModuleError::NotFound);
+    pub fn find(&self, module: &Module) -> Result<Vec<FloatUse>, ModuleError> {
+        let code_section = match module.code_section() {
+            Some(section) => section,
+            None => return Err(ModuleError::MissingSection("code".to_string())),
+        };
+
+        let types: Vec<&Type> = module
+            .type_section()
+            .map_or(vec![], |section| section.types().iter().collect());
+        let is_float_type = |idx: u32| match types.get(idx as usize) {
+            Some(Type::Function(ty)) => ty.params().iter().chain(ty.results()).any(is_float),
+            None => false,
+        };
+
+        // Type indices of functions and types of globals, in index space order: imports first.
+        let mut function_types: Vec<u32> = vec![];
+        let mut globals: Vec<ValueType> = vec![];
+        if let Some(section) = module.import_section() {
+            for entry in section.entries() {
+                match entry.external() {
+                    External::Function(idx) => function_types.push(*idx),
+                    External::Global(ty) => globals.push(ty.content_type()),
+                    _ => {}
+                }
+            }
+        }
+        let imported_functions = function_types.len() as u32;
+        if let Some(section) = module.function_section() {
+            function_types.extend(section.entries().iter().map(|func| func.type_ref()));
         }
-        for function in code_section.unwrap().bodies() {
-            for instruction in function.code().elements() {
+        if let Some(section) = module.global_section() {
+            globals.extend(
+                section
+                    .entries()
+                    .iter()
+                    .map(|entry| entry.global_type().content_type()),
+            );
+        }
+
+        let reachable = if self.reachable_only {
+            Some(reachable_functions(module)?)
+        } else {
+            None
+        };
+        let in_scope = |idx: u32| reachable.as_ref().is_none_or(|set| set.contains(&idx));
+
+        let names = module
+            .names_section()
+            .and_then(|section| section.functions());
+        let function = |index: u32| Function {
+            index,
+            name: names.and_then(|names| names.names().get(index).cloned()),
+        };
+
+        // The functions standing in for non-trapping conversions are reported at their calls.
+        let conversions = proposals::conversions(module);
+
+        let mut function_uses = vec![];
+        let mut used_globals = HashSet::new();
+        for (index, type_ref) in function_types.iter().enumerate() {
+            let index = index as u32;
+            if !in_scope(index) || conversions.contains_key(&index) {
+                continue;
+            }
+            if is_float_type(*type_ref) {
+                function_uses.push(FloatUse::Signature(function(index)));
+            }
+            if index < imported_functions {
+                continue;
+            }
+
+            let body = match code_section
+                .bodies()
+                .get((index - imported_functions) as usize)
+            {
+                Some(body) => body,
+                None => continue,
+            };
+            // Locals are indexed after the parameters.
+            let mut local_idx = match types.get(*type_ref as usize) {
+                Some(Type::Function(ty)) => ty.params().len() as u32,
+                None => 0,
+            };
+            for local in body.locals() {
+                if is_float(&local.value_type()) {
+                    for idx in local_idx..local_idx + local.count() {
+                        function_uses.push(FloatUse::Local(function(index), idx));
+                    }
+                }
+                local_idx += local.count();
+            }
+            for (offset, instruction) in body.code().elements().iter().enumerate() {
                 match instruction {
-                    Instruction::F32Eq
-                    | Instruction::F32Ne
-                    | Instruction::F32Lt
-                    | Instruction::F32Gt
-                    | Instruction::F32Le
-                    | Instruction::F32Ge
-                    | Instruction::F32Abs
-                    | Instruction::F32Neg
-                    | Instruction::F32Ceil
-                    | Instruction::F32Floor
-                    | Instruction::F32Trunc
-                    | Instruction::F32Nearest
-                    | Instruction::F32Sqrt
-                    | Instruction::F32Add
-                    | Instruction::F32Sub
-                    | Instruction::F32Mul
-                    | Instruction::F32Div
-                    | Instruction::F32Min
-                    | Instruction::F32Max
-                    | Instruction::F32Copysign
-                    | Instruction::I32TruncSF32
-                    | Instruction::I32TruncUF32
-                    | Instruction::I64TruncSF32
-                    | Instruction::I64TruncUF32
-                    | Instruction::F32ConvertSI32
-                    | Instruction::F32ConvertUI32
-                    | Instruction::F32ConvertSI64
-                    | Instruction::F32ConvertUI64
-                    | Instruction::F32DemoteF64
-                    | Instruction::F64PromoteF32
-                    | Instruction::I32ReinterpretF32
-                    | Instruction::F32ReinterpretI32
-                    | Instruction::F64Eq
-                    | Instruction::F64Ne
-                    | Instruction::F64Lt
-                    | Instruction::F64Gt
-                    | Instruction::F64Le
-                    | Instruction::F64Ge
-                    | Instruction::F64Abs
-                    | Instruction::F64Neg
-                    | Instruction::F64Ceil
-                    | Instruction::F64Floor
-                    | Instruction::F64Trunc
-                    | Instruction::F64Nearest
-                    | Instruction::F64Sqrt
-                    | Instruction::F64Add
-                    | Instruction::F64Sub
-                    | Instruction::F64Mul
-                    | Instruction::F64Div
-                    | Instruction::F64Min
-                    | Instruction::F64Max
-                    | Instruction::F64Copysign
-                    | Instruction::I32TruncSF64
-                    | Instruction::I32TruncUF64
-                    | Instruction::I64TruncSF64
-                    | Instruction::I64TruncUF64
-                    | Instruction::F64ConvertSI32
-                    | Instruction::F64ConvertUI32
-                    | Instruction::F64ConvertSI64
-                    | Instruction::F64ConvertUI64
-                    | Instruction::I64ReinterpretF64
-                    | Instruction::F64ReinterpretI64
-                    | Instruction::F32Const(_)
-                    | Instruction::F32Load(_, _)
-                    | Instruction::F32Store(_, _)
-                    | Instruction::F64Const(_)
-                    | Instruction::F64Load(_, _)
-                    | Instruction::F64Store(_, _) => {
-                        return Ok(false);
+                    Instruction::GetGlobal(idx) | Instruction::SetGlobal(idx) => {
+                        used_globals.insert(*idx);
+                    }
+                    Instruction::Call(idx) => {
+                        if let Some(name) = conversions.get(idx) {
+                            function_uses.push(FloatUse::Conversion(function(index), offset, name));
+                        }
                     }
                     _ => {}
                 }
+                if Class::Float.contains(instruction) {
+                    debug!("Found floating point instruction {:?}", instruction);
+                    function_uses.push(FloatUse::Instruction(
+                        function(index),
+                        offset,
+                        instruction.clone(),
+                    ));
+                }
             }
         }
 
-        Ok(true)
+        // Without a scope, every global is checked. Otherwise only those accessed by reachable
+        // code are.
+        let mut uses: Vec<FloatUse> = globals
+            .iter()
+            .enumerate()
+            .map(|(idx, ty)| (idx as u32, ty))
+            .filter(|(idx, ty)| is_float(ty) && (reachable.is_none() || used_globals.contains(idx)))
+            .map(|(idx, _)| FloatUse::Global(idx))
+            .collect();
+        uses.extend(function_uses);
+        Ok(uses)
+    }
+}
+
+impl ModuleValidator for CheckFloat {
+    fn validate(&self, module: &Module) -> Result<bool, ModuleError> {
+        Ok(self.find(module)?.is_empty())
+    }
+}
+
+/// Returns the functions reachable from the "main" export and the start function.
+fn reachable_functions(module: &Module) -> Result<HashSet<u32>, ModuleError> {
+    let main = module
+        .export_section()
+        .and_then(|section| {
+            section
+                .entries()
+                .iter()
+                .find(|entry| entry.field() == "main")
+        })
+        .and_then(|entry| match entry.internal() {
+            Internal::Function(idx) => Some(*idx),
+            _ => None,
+        })
+        .ok_or(ModuleError::NotFound)?;
+
+    let functions = module.import_count(ImportCountType::Function)
+        + module
+            .code_section()
+            .map_or(0, |section| section.bodies().len());
+    let mut reachable = HashSet::new();
+    for root in std::iter::once(main).chain(module.start_section()) {
+        if root as usize >= functions {
+            return Err(ModuleError::IndexOutOfRange("function", root));
+        }
+        let graph = DepGraph::build(module, root)
+            .map_err(|_| ModuleError::MissingSection("code".to_string()))?;
+        reachable.insert(root);
+        reachable.extend(graph.callees());
+    }
+    Ok(reachable)
+}
+
+fn is_float(ty: &ValueType) -> bool {
+    matches!(ty, ValueType::F32 | ValueType::F64)
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "function {} (${})", self.index, name),
+            None => write!(f, "function {}", self.index),
+        }
+    }
+}
+
+impl fmt::Display for FloatUse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FloatUse::Signature(function) => {
+                write!(f, "{}: float parameters or results", function)
+            }
+            FloatUse::Local(function, idx) => write!(f, "{}: float local {}", function, idx),
+            FloatUse::Instruction(function, offset, instruction) => {
+                write!(f, "{}, instruction {}: {}", function, offset, instruction)
+            }
+            FloatUse::Conversion(function, offset, name) => {
+                write!(f, "{}, instruction {}: {}", function, offset, name)
+            }
+            FloatUse::Global(idx) => write!(f, "global {}: float type", idx),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use parity_wasm::builder;
+    use rustc_hex::FromHex;
 
     use super::*;
 
@@ -186,12 +345,142 @@ mod tests {
         assert_eq!(false, result);
     }
 
+    //  (module
+    //    (import "ethereum" "useGas" (func $useGas (param i64)))
+    //    (global $g (mut f64) (f64.const 0))
+    //    (func $main (export "main") (local i64 f32)
+    //      call $live)
+    //    (func $live
+    //      f64.const 1
+    //      global.set $g)
+    //    (func $dead (param f32) (result f32)
+    //      local.get 0
+    //      f32.neg))
+    fn sample_module() -> Module {
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d01000000010d0360017e0060000060017d017d02130108657468657265756d0675736547617300
+00030403010102060d017c014400000000000000000b070801046d61696e00010a1e030802017e017d10020b0d
+0044000000000000f03f24000b050020008c0b0028046e616d65011b04000675736547617301046d61696e0204
+6c697665030464656164070401000167",
+        )
+        .unwrap();
+        Module::from_bytes(&wasm).unwrap().parse_names().unwrap()
+    }
+
+    fn function(index: u32, name: &str) -> Function {
+        Function {
+            index,
+            name: Some(name.to_string()),
+        }
+    }
+
+    #[test]
+    fn all_locations() {
+        let checker = CheckFloat::with_defaults().unwrap();
+        let uses = checker.find(&sample_module()).unwrap();
+        assert_eq!(
+            uses,
+            vec![
+                FloatUse::Global(0),
+                FloatUse::Local(function(1, "main"), 1),
+                FloatUse::Instruction(
+                    function(2, "live"),
+                    0,
+                    Instruction::F64Const(0x3ff0000000000000)
+                ),
+                FloatUse::Signature(function(3, "dead")),
+                FloatUse::Instruction(function(3, "dead"), 1, Instruction::F32Neg),
+            ]
+        );
+        assert_eq!(uses[1].to_string(), "function 1 ($main): float local 1");
+        assert_eq!(
+            uses[4].to_string(),
+            "function 3 ($dead), instruction 1: f32.neg"
+        );
+        assert_eq!(checker.validate(&sample_module()), Ok(false));
+    }
+
+    #[test]
+    fn saturating_conversion() {
+        let wasm =
+            wat::parse_str(r#"(module (func (result i32) f32.const 1.5 i32.trunc_sat_f32_s))"#)
+                .unwrap();
+        let module = proposals::from_bytes(&wasm).unwrap();
+        let uses = CheckFloat::new(false).find(&module).unwrap();
+        // The function standing in for the conversion is not reported itself.
+        let caller = Function {
+            index: 0,
+            name: None,
+        };
+        assert_eq!(
+            uses,
+            vec![
+                FloatUse::Instruction(caller.clone(), 0, Instruction::F32Const(0x3fc00000)),
+                FloatUse::Conversion(caller, 1, "i32.trunc_sat_f32_s"),
+            ]
+        );
+        assert_eq!(
+            uses[1].to_string(),
+            "function 0, instruction 1: i32.trunc_sat_f32_s"
+        );
+    }
+
+    #[test]
+    fn reachable_only() {
+        let mut config = HashMap::new();
+        config.insert("reachable_only".to_string(), "true".to_string());
+        let checker = CheckFloat::with_config(&config).unwrap();
+        let uses = checker.find(&sample_module()).unwrap();
+        assert_eq!(uses.len(), 3);
+        assert!(uses.iter().all(|u| match u {
+            FloatUse::Signature(function)
+            | FloatUse::Local(function, _)
+            | FloatUse::Instruction(function, _, _)
+            | FloatUse::Conversion(function, _, _) => function.index != 3,
+            FloatUse::Global(_) => true,
+        }));
+    }
+
+    #[test]
+    fn reachable_only_without_main() {
+        //  (module (func (export "add") (param f32 f32) (result f32) ...))
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x07, 0x01, 0x60, 0x02, 0x7d,
+            0x7d, 0x01, 0x7d, 0x03, 0x02, 0x01, 0x00, 0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64,
+            0x00, 0x00, 0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x92, 0x0b,
+        ];
+        let module = Module::from_bytes(&wasm).unwrap();
+        let checker = CheckFloat::new(true);
+        assert_eq!(checker.validate(&module), Err(ModuleError::NotFound));
+    }
+
+    #[test]
+    fn reachable_only_start_out_of_range() {
+        let mut module = sample_module();
+        module.set_start_section(9);
+        let checker = CheckFloat::new(true);
+        assert_eq!(
+            checker.validate(&module),
+            Err(ModuleError::IndexOutOfRange("function", 9))
+        );
+    }
+
+    #[test]
+    fn invalid_config() {
+        let mut config = HashMap::new();
+        config.insert("reachable_only".to_string(), "maybe".to_string());
+        assert!(CheckFloat::with_config(&config).is_err());
+    }
+
     #[test]
     fn no_code_section() {
         let module = builder::module().build();
         let checker = CheckFloat::with_defaults().unwrap();
         let result = checker.validate(&module);
         assert_eq!(true, result.is_err());
-        assert_eq!(result.err().unwrap(), ModuleError::NotFound)
+        assert_eq!(
+            result.err().unwrap(),
+            ModuleError::MissingSection("code".to_string())
+        )
     }
 }
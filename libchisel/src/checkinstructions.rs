@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use parity_wasm::elements::{ImportCountType, Instruction, Module};
+
+use super::checkfloat::Function;
+use super::instructions::{self, Class};
+use super::proposals;
+use super::{ChiselModule, ModuleError, ModuleKind, ModuleValidator};
+
+/// Struct on which ModuleValidator is implemented.
+pub struct CheckInstructions {
+    /// If set, only instructions matching one of these are allowed.
+    allow: Option<Vec<Selector>>,
+    /// Instructions matching one of these are denied, even if they are allowed.
+    deny: Vec<Selector>,
+}
+
+/// Selects instructions by class, e.g. "i64_division", or by name, e.g. "memory.grow".
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Selector {
+    Class(Class),
+    Name(String),
+}
+
+/// A denied instruction, by its offset in the instructions of the function body, and its name.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Violation {
+    pub function: Function,
+    pub offset: usize,
+    pub instruction: String,
+}
+
+impl<'a> ChiselModule<'a> for CheckInstructions {
+    type ObjectReference = &'a dyn ModuleValidator;
+
+    fn id(&'a self) -> String {
+        "checkinstructions".to_string()
+    }
+
+    fn kind(&'a self) -> ModuleKind {
+        ModuleKind::Validator
+    }
+
+    fn as_abstract(&'a self) -> Self::ObjectReference {
+        self as Self::ObjectReference
+    }
+
+    fn with_defaults() -> Result<Self, ModuleError> {
+        Err(ModuleError::NotSupported)
+    }
+
+    fn with_config(config: &HashMap<String, String>) -> Result<Self, ModuleError> {
+        let allow = config
+            .get("allow")
+            .map(|list| parse_list("allow", list))
+            .transpose()?;
+        let deny = config
+            .get("deny")
+            .map(|list| parse_list("deny", list))
+            .transpose()?;
+        if allow.is_none() && deny.is_none() {
+            return Err(ModuleError::InvalidConfig(
+                "allow|deny".to_string(),
+                "either must be given".to_string(),
+            ));
+        }
+        Ok(CheckInstructions::new(allow, deny.unwrap_or_default()))
+    }
+}
+
+impl CheckInstructions {
+    pub fn new(allow: Option<Vec<Selector>>, deny: Vec<Selector>) -> Self {
+        CheckInstructions { allow, deny }
+    }
+
+    /// Returns every denied instruction in the module, in order.
+    pub fn find(&self, module: &Module) -> Result<Vec<Violation>, ModuleError> {
+        let bodies = match module.code_section() {
+            Some(section) => section.bodies(),
+            None => return Ok(vec![]),
+        };
+        let imported = module.import_count(ImportCountType::Function) as u32;
+        let names = module
+            .names_section()
+            .and_then(|section| section.functions());
+
+        // Calls to the functions standing in for non-trapping conversions are checked as the
+        // conversions, and the functions themselves are skipped.
+        let conversions = proposals::conversions(module);
+
+        let mut violations = vec![];
+        for (idx, body) in bodies.iter().enumerate() {
+            let index = imported + idx as u32;
+            if conversions.contains_key(&index) {
+                continue;
+            }
+            for (offset, instruction) in body.code().elements().iter().enumerate() {
+                let conversion = match instruction {
+                    Instruction::Call(idx) => conversions.get(idx),
+                    _ => None,
+                };
+                let name = match conversion {
+                    Some(name) => name.to_string(),
+                    None => instructions::name(instruction),
+                };
+                let matches = |selector: &Selector| match selector {
+                    Selector::Class(Class::Float) if conversion.is_some() => true,
+                    Selector::Class(class) => class.contains(instruction),
+                    Selector::Name(selected) => *selected == name,
+                };
+                let allowed = self
+                    .allow
+                    .as_ref()
+                    .is_none_or(|allow| allow.iter().any(matches));
+                if !allowed || self.deny.iter().any(matches) {
+                    violations.push(Violation {
+                        function: Function {
+                            index,
+                            name: names.and_then(|names| names.names().get(index).cloned()),
+                        },
+                        offset,
+                        instruction: name,
+                    });
+                }
+            }
+        }
+        Ok(violations)
+    }
+}
+
+impl ModuleValidator for CheckInstructions {
+    fn validate(&self, module: &Module) -> Result<bool, ModuleError> {
+        Ok(self.find(module)?.is_empty())
+    }
+}
+
+/// Parses the comma-separated list of instruction classes and names given for the option.
+fn parse_list(option: &str, list: &str) -> Result<Vec<Selector>, ModuleError> {
+    let known = instructions::names();
+    list.split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match Class::from_name(entry) {
+            Some(class) => Ok(Selector::Class(class)),
+            None if known.contains(entry) => Ok(Selector::Name(entry.to_string())),
+            // Modules with SIMD instructions cannot be parsed, so the class could never match.
+            None if entry == "simd" => Err(ModuleError::InvalidConfig(
+                option.to_string(),
+                "SIMD instructions cannot be checked, modules using them cannot be parsed"
+                    .to_string(),
+            )),
+            None => Err(ModuleError::InvalidConfig(
+                option.to_string(),
+                format!("unknown instruction or class '{}'", entry),
+            )),
+        })
+        .collect()
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}, instruction {}: {}",
+            self.function, self.offset, self.instruction
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hex::FromHex;
+
+    use super::*;
+
+    //  (module
+    //    (memory 1)
+    //    (table 1 funcref)
+    //    (func $main (export "main") (param i64) (result i64)
+    //      (drop (memory.grow (i32.const 1)))
+    //      (i64.div_u (local.get 0) (i64.const 3)))
+    //    (func $dispatch (param i32)
+    //      (call_indirect (local.get 0))))
+    fn sample_module() -> Module {
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d01000000010d0360017e017e60017f0060000003030200010404017000010503010001070801046d
+61696e00000a16020c00410140001a20004203800b070020001102000b0018046e616d6501110200046d6169
+6e01086469737061746368",
+        )
+        .unwrap();
+        Module::from_bytes(&wasm).unwrap().parse_names().unwrap()
+    }
+
+    fn config(key: &str, value: &str) -> HashMap<String, String> {
+        let mut config = HashMap::new();
+        config.insert(key.to_string(), value.to_string());
+        config
+    }
+
+    #[test]
+    fn deny() {
+        let checker = CheckInstructions::with_config(&config(
+            "deny",
+            "memory.grow, call_indirect,i64_division",
+        ))
+        .unwrap();
+        let violations = checker.find(&sample_module()).unwrap();
+        assert_eq!(
+            violations
+                .iter()
+                .map(|violation| violation.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "function 0 ($main), instruction 1: memory.grow",
+                "function 0 ($main), instruction 5: i64.div_u",
+                "function 1 ($dispatch), instruction 1: call_indirect",
+            ]
+        );
+        assert_eq!(checker.validate(&sample_module()), Ok(false));
+    }
+
+    #[test]
+    fn allow() {
+        let mut config = config("allow", "local.get,i32.const,i64.const,drop,end,memory");
+        let checker = CheckInstructions::with_config(&config).unwrap();
+        let violations = checker.find(&sample_module()).unwrap();
+        assert_eq!(
+            violations
+                .iter()
+                .map(|violation| violation.instruction.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["i64.div_u", "call_indirect"]
+        );
+
+        // Denials take precedence.
+        config.insert("deny".to_string(), "memory".to_string());
+        let checker = CheckInstructions::with_config(&config).unwrap();
+        assert_eq!(checker.find(&sample_module()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn saturating_conversion() {
+        let wasm = wat::parse_str(
+            r#"(module (func (param f64) (result i64) local.get 0 i64.trunc_sat_f64_u))"#,
+        )
+        .unwrap();
+        let module = proposals::from_bytes(&wasm).unwrap();
+        let checker = CheckInstructions::with_config(&config("allow", "local.get,end")).unwrap();
+        assert_eq!(
+            checker.find(&module).unwrap(),
+            vec![Violation {
+                function: Function {
+                    index: 0,
+                    name: None
+                },
+                offset: 1,
+                instruction: "i64.trunc_sat_f64_u".to_string(),
+            }]
+        );
+        for deny in &["float", "i64.trunc_sat_f64_u"] {
+            let checker = CheckInstructions::with_config(&config("deny", deny)).unwrap();
+            assert_eq!(checker.find(&module).unwrap().len(), 1);
+        }
+        let checker = CheckInstructions::with_config(&config("deny", "call")).unwrap();
+        assert_eq!(checker.validate(&module), Ok(true));
+    }
+
+    #[test]
+    fn nothing_denied() {
+        let checker = CheckInstructions::with_config(&config("deny", "float,bulk_memory")).unwrap();
+        assert_eq!(checker.validate(&sample_module()), Ok(true));
+        assert_eq!(checker.validate(&Module::default()), Ok(true));
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(CheckInstructions::with_config(&HashMap::new()).is_err());
+        assert_eq!(
+            CheckInstructions::with_config(&config("deny", "grow_memory")).err(),
+            Some(ModuleError::InvalidConfig(
+                "deny".to_string(),
+                "unknown instruction or class 'grow_memory'".to_string()
+            ))
+        );
+        assert_eq!(
+            CheckInstructions::with_config(&config("deny", "float,simd")).err(),
+            Some(ModuleError::InvalidConfig(
+                "deny".to_string(),
+                "SIMD instructions cannot be checked, modules using them cannot be parsed"
+                    .to_string()
+            ))
+        );
+    }
+}
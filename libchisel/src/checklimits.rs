@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use parity_wasm::elements::{
+    Deserialize, External, FuncBody, ImportCountType, Instruction, Module, Serialize, Type,
+    VarUint32,
+};
+
+use super::checkfloat::Function;
+use super::proposals;
+use super::{ChiselModule, Location, ModuleError, ModuleKind, ModuleValidator};
+
+/// Struct on which ModuleValidator is implemented. Every limit is optional.
+#[derive(Default, Eq, PartialEq, Debug, Clone)]
+pub struct CheckLimits {
+    /// The number of functions defined by the module, without imports.
+    pub max_functions: Option<u32>,
+    /// The number of locals of each function, including its parameters.
+    pub max_locals: Option<u32>,
+    /// The encoded size of each function body, in bytes.
+    pub max_function_size: Option<u32>,
+    /// The encoded size of all function bodies, in bytes.
+    pub max_code_size: Option<u32>,
+    /// The number of globals defined by the module, without imports.
+    pub max_globals: Option<u32>,
+    /// The initial size of each table, in elements.
+    pub max_table_size: Option<u32>,
+    pub max_data_segments: Option<u32>,
+    /// The size of each data segment, in bytes.
+    pub max_data_segment_size: Option<u32>,
+    pub max_imports: Option<u32>,
+    pub max_exports: Option<u32>,
+    /// The nesting depth of blocks, loops and ifs in each function.
+    pub max_nesting_depth: Option<u32>,
+}
+
+/// A limit exceeded by the module, with the offending value and the limit.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Violation {
+    Functions(u32, u32),
+    Locals(Function, u32, u32),
+    FunctionSize(Function, u32, u32),
+    CodeSize(u32, u32),
+    Globals(u32, u32),
+    /// A table, by its index in the table index space.
+    TableSize(u32, u32, u32),
+    DataSegments(u32, u32),
+    /// A data segment, by its index in the data section.
+    DataSegmentSize(u32, u32, u32),
+    Imports(u32, u32),
+    Exports(u32, u32),
+    NestingDepth(Function, u32, u32),
+}
+
+impl<'a> ChiselModule<'a> for CheckLimits {
+    type ObjectReference = &'a dyn ModuleValidator;
+
+    fn id(&'a self) -> String {
+        "checklimits".to_string()
+    }
+
+    fn kind(&'a self) -> ModuleKind {
+        ModuleKind::Validator
+    }
+
+    fn as_abstract(&'a self) -> Self::ObjectReference {
+        self as Self::ObjectReference
+    }
+
+    fn with_defaults() -> Result<Self, ModuleError> {
+        Err(ModuleError::NotSupported)
+    }
+
+    fn with_config(config: &HashMap<String, String>) -> Result<Self, ModuleError> {
+        let limit = |key: &str| -> Result<Option<u32>, ModuleError> {
+            config
+                .get(key)
+                .map(|value| {
+                    value.parse::<u32>().map_err(|_| {
+                        ModuleError::InvalidConfig(
+                            key.to_string(),
+                            format!("expected a number, found '{}'", value),
+                        )
+                    })
+                })
+                .transpose()
+        };
+        let limits = CheckLimits {
+            max_functions: limit("max_functions")?,
+            max_locals: limit("max_locals")?,
+            max_function_size: limit("max_function_size")?,
+            max_code_size: limit("max_code_size")?,
+            max_globals: limit("max_globals")?,
+            max_table_size: limit("max_table_size")?,
+            max_data_segments: limit("max_data_segments")?,
+            max_data_segment_size: limit("max_data_segment_size")?,
+            max_imports: limit("max_imports")?,
+            max_exports: limit("max_exports")?,
+            max_nesting_depth: limit("max_nesting_depth")?,
+        };
+        if limits == CheckLimits::default() {
+            return Err(ModuleError::InvalidConfig(
+                "max_*".to_string(),
+                "no limits given".to_string(),
+            ));
+        }
+        Ok(limits)
+    }
+}
+
+impl CheckLimits {
+    /// Returns every exceeded limit: the module-wide limits first, then those of each function,
+    /// table and data segment, in order.
+    pub fn find(&self, module: &Module) -> Result<Vec<Violation>, ModuleError> {
+        let mut violations = vec![];
+        let mut check = |value: u32, limit: Option<u32>, violation: fn(u32, u32) -> Violation| {
+            if let Some(limit) = limit {
+                if value > limit {
+                    violations.push(violation(value, limit));
+                }
+            }
+        };
+
+        // The functions implementing non-trapping conversions are not part of the serialized module.
+        let conversions = proposals::conversions(module);
+        let imported = module.import_count(ImportCountType::Function) as u32;
+        let bodies = module
+            .code_section()
+            .map_or(&[][..], |section| section.bodies());
+        let body_sizes = bodies
+            .iter()
+            .enumerate()
+            .map(|(idx, body)| {
+                let index = imported + idx as u32;
+                if conversions.contains_key(&index) {
+                    return Ok(0);
+                }
+                body_size(body, &conversions).map_err(|e| e.at(Location::Function(index)))
+            })
+            .collect::<Result<Vec<u32>, ModuleError>>()?;
+        let segments = module
+            .data_section()
+            .map_or(&[][..], |section| section.entries());
+
+        check(
+            module.function_section().map_or(0, |s| s.entries().len()) as u32
+                - conversions.len() as u32,
+            self.max_functions,
+            Violation::Functions,
+        );
+        check(
+            body_sizes.iter().sum(),
+            self.max_code_size,
+            Violation::CodeSize,
+        );
+        check(
+            module.global_section().map_or(0, |s| s.entries().len()) as u32,
+            self.max_globals,
+            Violation::Globals,
+        );
+        check(
+            segments.len() as u32,
+            self.max_data_segments,
+            Violation::DataSegments,
+        );
+        check(
+            module.import_section().map_or(0, |s| s.entries().len()) as u32,
+            self.max_imports,
+            Violation::Imports,
+        );
+        check(
+            module.export_section().map_or(0, |s| s.entries().len()) as u32,
+            self.max_exports,
+            Violation::Exports,
+        );
+
+        let types = module
+            .type_section()
+            .map_or(&[][..], |section| section.types());
+        let functions = module
+            .function_section()
+            .map_or(&[][..], |section| section.entries());
+        let names = module
+            .names_section()
+            .and_then(|section| section.functions());
+        for (idx, (body, size)) in bodies.iter().zip(body_sizes).enumerate() {
+            let index = imported + idx as u32;
+            if conversions.contains_key(&index) {
+                continue;
+            }
+            let function = || Function {
+                index,
+                name: names.and_then(|names| names.names().get(index).cloned()),
+            };
+            let params = match functions
+                .get(idx)
+                .and_then(|func| types.get(func.type_ref() as usize))
+            {
+                Some(Type::Function(ty)) => ty.params().len() as u32,
+                None => 0,
+            };
+            let locals = body
+                .locals()
+                .iter()
+                .fold(params, |count, local| count.saturating_add(local.count()));
+            let depth = nesting_depth(body.code().elements());
+
+            for (value, limit, violation) in [
+                (
+                    locals,
+                    self.max_locals,
+                    Violation::Locals as fn(_, _, _) -> _,
+                ),
+                (size, self.max_function_size, Violation::FunctionSize),
+                (depth, self.max_nesting_depth, Violation::NestingDepth),
+            ] {
+                match limit {
+                    Some(limit) if value > limit => {
+                        violations.push(violation(function(), value, limit))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(limit) = self.max_table_size {
+            let imported_tables = module
+                .import_section()
+                .map_or(&[][..], |section| section.entries())
+                .iter()
+                .filter_map(|entry| match entry.external() {
+                    External::Table(table) => Some(table),
+                    _ => None,
+                });
+            let tables = module
+                .table_section()
+                .map_or(&[][..], |section| section.entries());
+            for (idx, table) in imported_tables.chain(tables).enumerate() {
+                if table.limits().initial() > limit {
+                    violations.push(Violation::TableSize(
+                        idx as u32,
+                        table.limits().initial(),
+                        limit,
+                    ));
+                }
+            }
+        }
+
+        if let Some(limit) = self.max_data_segment_size {
+            for (idx, segment) in segments.iter().enumerate() {
+                if segment.value().len() > limit as usize {
+                    violations.push(Violation::DataSegmentSize(
+                        idx as u32,
+                        segment.value().len() as u32,
+                        limit,
+                    ));
+                }
+            }
+        }
+
+        Ok(violations)
+    }
+}
+
+impl ModuleValidator for CheckLimits {
+    fn validate(&self, module: &Module) -> Result<bool, ModuleError> {
+        Ok(self.find(module)?.is_empty())
+    }
+}
+
+/// Returns the encoded size of a function body, in bytes, without its size prefix. Calls to the
+/// given conversion functions are written as the conversions, which take two bytes.
+pub(crate) fn body_size(
+    body: &FuncBody,
+    conversions: &HashMap<u32, &str>,
+) -> Result<u32, ModuleError> {
+    let mut buf = vec![];
+    body.clone().serialize(&mut buf)?;
+    let size: u32 = VarUint32::deserialize(&mut &buf[..])?.into();
+    let calls: u32 = body
+        .code()
+        .elements()
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Call(idx) if conversions.contains_key(idx) => {
+                let mut call = vec![];
+                VarUint32::from(*idx).serialize(&mut call).ok()?;
+                Some(call.len() as u32 - 1)
+            }
+            _ => None,
+        })
+        .sum();
+    Ok(size - calls)
+}
+
+/// Returns the deepest nesting of blocks, loops and ifs. The function body itself is at depth 0.
+fn nesting_depth(instructions: &[Instruction]) -> u32 {
+    let (mut depth, mut max_depth) = (0u32, 0u32);
+    for instruction in instructions {
+        match instruction {
+            Instruction::Block(_) | Instruction::Loop(_) | Instruction::If(_) => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            Instruction::End => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max_depth
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::Functions(value, limit) => {
+                write!(f, "{} functions, limit {}", value, limit)
+            }
+            Violation::Locals(function, value, limit) => {
+                write!(f, "{}: {} locals, limit {}", function, value, limit)
+            }
+            Violation::FunctionSize(function, value, limit) => {
+                write!(f, "{}: {} bytes, limit {}", function, value, limit)
+            }
+            Violation::CodeSize(value, limit) => {
+                write!(f, "code: {} bytes, limit {}", value, limit)
+            }
+            Violation::Globals(value, limit) => write!(f, "{} globals, limit {}", value, limit),
+            Violation::TableSize(idx, value, limit) => {
+                write!(f, "table {}: {} elements, limit {}", idx, value, limit)
+            }
+            Violation::DataSegments(value, limit) => {
+                write!(f, "{} data segments, limit {}", value, limit)
+            }
+            Violation::DataSegmentSize(idx, value, limit) => {
+                write!(f, "data segment {}: {} bytes, limit {}", idx, value, limit)
+            }
+            Violation::Imports(value, limit) => write!(f, "{} imports, limit {}", value, limit),
+            Violation::Exports(value, limit) => write!(f, "{} exports, limit {}", value, limit),
+            Violation::NestingDepth(function, value, limit) => {
+                write!(f, "{}: nesting depth {}, limit {}", function, value, limit)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hex::FromHex;
+
+    use super::*;
+
+    //  (module
+    //    (import "env" "gas" (func $gas (param i64)))
+    //    (table 4 funcref)
+    //    (memory 1)
+    //    (global $sp (mut i32) (i32.const 0))
+    //    (func $main (export "main")
+    //      (block (loop (if (i32.const 0) (then (nop))))))
+    //    (func $sum (param i32 i32) (result i32) (local i64 i64)
+    //      (i32.add (local.get 0) (local.get 1)))
+    //    (data (i32.const 0) "chisel")
+    //    (data (i32.const 8) "wasm"))
+    fn sample_module() -> Module {
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d01000000010e0360017e0060000060027f7f017f020b0103656e7603676173000003030201020404
+0170000405030100010606017f0141000b070801046d61696e00010a1a020e000240034041000440010b0b0b
+0b0901027e200020016a0b0b15020041000b0663686973656c0041080b047761736d001f046e616d65011103
+000367617301046d61696e020373756d07050100027370",
+        )
+        .unwrap();
+        Module::from_bytes(&wasm).unwrap().parse_names().unwrap()
+    }
+
+    fn config(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn within_limits() {
+        let checker = CheckLimits::with_config(&config(&[
+            ("max_functions", "2"),
+            ("max_locals", "4"),
+            ("max_nesting_depth", "3"),
+            ("max_table_size", "4"),
+            ("max_data_segment_size", "6"),
+            ("max_imports", "1"),
+        ]))
+        .unwrap();
+        assert_eq!(checker.validate(&sample_module()), Ok(true));
+    }
+
+    #[test]
+    fn saturating_conversion() {
+        let wasm = wat::parse_str(
+            r#"(module (func (param f32) (result i32) local.get 0 i32.trunc_sat_f32_s))"#,
+        )
+        .unwrap();
+        let module = proposals::from_bytes(&wasm).unwrap();
+        // Sizes and counts are those of the serialized module, without the function standing in
+        // for the conversion.
+        let checker = CheckLimits::with_config(&config(&[
+            ("max_functions", "1"),
+            ("max_function_size", "6"),
+            ("max_code_size", "6"),
+        ]))
+        .unwrap();
+        assert_eq!(checker.validate(&module), Ok(true));
+        let checker = CheckLimits::with_config(&config(&[("max_function_size", "5")])).unwrap();
+        assert_eq!(
+            checker.find(&module).unwrap(),
+            vec![Violation::FunctionSize(
+                Function {
+                    index: 0,
+                    name: None
+                },
+                6,
+                5
+            )]
+        );
+    }
+
+    #[test]
+    fn every_violation() {
+        let checker = CheckLimits::with_config(&config(&[
+            ("max_functions", "1"),
+            ("max_locals", "3"),
+            ("max_function_size", "10"),
+            ("max_code_size", "10"),
+            ("max_globals", "0"),
+            ("max_table_size", "2"),
+            ("max_data_segments", "1"),
+            ("max_data_segment_size", "4"),
+            ("max_imports", "0"),
+            ("max_exports", "0"),
+            ("max_nesting_depth", "2"),
+        ]))
+        .unwrap();
+        let violations: Vec<String> = checker
+            .find(&sample_module())
+            .unwrap()
+            .iter()
+            .map(|violation| violation.to_string())
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "2 functions, limit 1",
+                "code: 23 bytes, limit 10",
+                "1 globals, limit 0",
+                "2 data segments, limit 1",
+                "1 imports, limit 0",
+                "1 exports, limit 0",
+                "function 1 ($main): 14 bytes, limit 10",
+                "function 1 ($main): nesting depth 3, limit 2",
+                "function 2 ($sum): 4 locals, limit 3",
+                "table 0: 4 elements, limit 2",
+                "data segment 0: 6 bytes, limit 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(CheckLimits::with_config(&HashMap::new()).is_err());
+        assert_eq!(
+            CheckLimits::with_config(&config(&[("max_locals", "-1")])).err(),
+            Some(ModuleError::InvalidConfig(
+                "max_locals".to_string(),
+                "expected a number, found '-1'".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Checks the size of the serialized module against a limit, such as the contract size limit of
+//! EIP-170. When the module is too large, its largest sections and functions are reported, as
+//! the first candidates for trimming.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+use parity_wasm::elements::{ImportCountType, Module};
+
+use super::checkfloat::Function;
+use super::checklimits::body_size;
+use super::diff::{section_name, section_size};
+use super::proposals;
+use super::{ChiselModule, Location, ModuleError, ModuleKind, ModulePreset, ModuleValidator};
+
+/// The contract size limit of EIP-170, in bytes.
+pub const EIP170_LIMIT: usize = 24 * 1024;
+
+/// The number of sections and of functions reported when the limit is exceeded.
+const REPORTED: usize = 5;
+
+/// Struct on which ModuleValidator is implemented.
+pub struct CheckSize {
+    /// The maximum size of the serialized module, in bytes.
+    limit: usize,
+}
+
+/// The size of a module exceeding the limit, and of its largest parts, in bytes.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Oversize {
+    /// The size of the module and the limit.
+    Module(usize, usize),
+    /// The encoded size of a section, including its id and length.
+    Section(String, usize),
+    /// The encoded size of a function body.
+    Function(Function, usize),
+}
+
+impl<'a> ChiselModule<'a> for CheckSize {
+    type ObjectReference = &'a dyn ModuleValidator;
+
+    fn id(&'a self) -> String {
+        "checksize".to_string()
+    }
+
+    fn kind(&'a self) -> ModuleKind {
+        ModuleKind::Validator
+    }
+
+    fn as_abstract(&'a self) -> Self::ObjectReference {
+        self as Self::ObjectReference
+    }
+
+    fn with_defaults() -> Result<Self, ModuleError> {
+        Ok(CheckSize::new(EIP170_LIMIT))
+    }
+
+    fn with_config(config: &HashMap<String, String>) -> Result<Self, ModuleError> {
+        match (config.get("max_size"), config.get("preset")) {
+            (Some(_), Some(_)) => Err(ModuleError::InvalidConfig(
+                "max_size|preset".to_string(),
+                "only one can be given".to_string(),
+            )),
+            (Some(max_size), None) => match max_size.parse::<usize>() {
+                Ok(limit) => Ok(CheckSize::new(limit)),
+                Err(_) => Err(ModuleError::InvalidConfig(
+                    "max_size".to_string(),
+                    format!("expected a number, found '{}'", max_size),
+                )),
+            },
+            (None, Some(preset)) => CheckSize::with_preset(preset),
+            (None, None) => CheckSize::with_defaults(),
+        }
+    }
+}
+
+impl ModulePreset for CheckSize {
+    fn with_preset(preset: &str) -> Result<Self, ModuleError> {
+        match preset {
+            "ewasm" => Ok(CheckSize::new(EIP170_LIMIT)),
+            _ => Err(ModuleError::NotSupported),
+        }
+    }
+}
+
+impl CheckSize {
+    pub fn new(limit: usize) -> Self {
+        CheckSize { limit }
+    }
+
+    /// Returns nothing if the module is within the limit. Otherwise returns its size, followed by
+    /// its largest sections and functions, largest first.
+    pub fn find(&self, module: &Module) -> Result<Vec<Oversize>, ModuleError> {
+        let size = proposals::into_bytes(module.clone())?.len();
+        if size <= self.limit {
+            return Ok(vec![]);
+        }
+
+        let mut sections: Vec<(String, usize)> = module
+            .sections()
+            .iter()
+            .map(|section| (section_name(section), section_size(section)))
+            .collect();
+        // Stable sorts keep the module order among parts of equal size.
+        sections.sort_by_key(|(_, size)| Reverse(*size));
+
+        let imported = module.import_count(ImportCountType::Function) as u32;
+        let names = module
+            .names_section()
+            .and_then(|section| section.functions());
+        // The functions implementing non-trapping conversions are not part of the serialized module.
+        let conversions = proposals::conversions(module);
+        let mut functions = vec![];
+        if let Some(section) = module.code_section() {
+            for (idx, body) in section.bodies().iter().enumerate() {
+                let index = imported + idx as u32;
+                if conversions.contains_key(&index) {
+                    continue;
+                }
+                let function = Function {
+                    index,
+                    name: names.and_then(|names| names.names().get(index).cloned()),
+                };
+                let size =
+                    body_size(body, &conversions).map_err(|e| e.at(Location::Function(index)))?;
+                functions.push((function, size as usize));
+            }
+        }
+        functions.sort_by_key(|(_, size)| Reverse(*size));
+
+        let mut oversize = vec![Oversize::Module(size, self.limit)];
+        oversize.extend(
+            sections
+                .into_iter()
+                .take(REPORTED)
+                .map(|(name, size)| Oversize::Section(name, size)),
+        );
+        oversize.extend(
+            functions
+                .into_iter()
+                .take(REPORTED)
+                .map(|(function, size)| Oversize::Function(function, size)),
+        );
+        Ok(oversize)
+    }
+}
+
+impl ModuleValidator for CheckSize {
+    fn validate(&self, module: &Module) -> Result<bool, ModuleError> {
+        Ok(self.find(module)?.is_empty())
+    }
+}
+
+impl fmt::Display for Oversize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Oversize::Module(size, limit) => write!(
+                f,
+                "module: {} bytes, limit {}, {} bytes over",
+                size,
+                limit,
+                size - limit
+            ),
+            Oversize::Section(name, size) => write!(f, "{} section: {} bytes", name, size),
+            Oversize::Function(function, size) => write!(f, "{}: {} bytes", function, size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hex::FromHex;
+
+    use super::*;
+
+    //  (module
+    //    (memory 1)
+    //    (func $main (export "main")
+    //      (drop (i64.const 0x7fffffffffffffff)))
+    //    (func $two (result i32)
+    //      (i32.const 2))
+    //    (data (i32.const 0) "0123456789abcdef"))
+    fn sample_module() -> Module {
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d010000000108026000006000017f03030200010503010001070801046d61696e00000a15020e0042
+ffffffffffffffffff001a0b040041020b0b16010041000b1030313233343536373839616263646566001304
+6e616d65010c0200046d61696e010374776f",
+        )
+        .unwrap();
+        Module::from_bytes(&wasm).unwrap().parse_names().unwrap()
+    }
+
+    #[test]
+    fn within_limit() {
+        let module = sample_module();
+        let size = module.clone().into_bytes().unwrap().len();
+        assert_eq!(CheckSize::new(size).validate(&module), Ok(true));
+        assert_eq!(
+            CheckSize::with_defaults().unwrap().validate(&module),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn breakdown() {
+        let oversize = CheckSize::new(64).find(&sample_module()).unwrap();
+        assert_eq!(
+            oversize
+                .iter()
+                .map(|part| part.to_string())
+                .collect::<Vec<String>>(),
+            vec![
+                "module: 106 bytes, limit 64, 42 bytes over",
+                "data section: 24 bytes",
+                "code section: 23 bytes",
+                "custom \"name\" section: 21 bytes",
+                "type section: 10 bytes",
+                "export section: 10 bytes",
+                "function 0 ($main): 14 bytes",
+                "function 1 ($two): 4 bytes",
+            ]
+        );
+    }
+
+    #[test]
+    fn config() {
+        let mut config = HashMap::new();
+        config.insert("max_size".to_string(), "49152".to_string());
+        assert_eq!(CheckSize::with_config(&config).unwrap().limit, 49152);
+        config.insert("preset".to_string(), "ewasm".to_string());
+        assert!(CheckSize::with_config(&config).is_err());
+        config.remove("max_size");
+        assert_eq!(CheckSize::with_config(&config).unwrap().limit, 24576);
+        config.insert("preset".to_string(), "eth3".to_string());
+        assert!(CheckSize::with_config(&config).is_err());
+    }
+}
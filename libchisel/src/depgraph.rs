@@ -13,6 +13,8 @@ pub struct Edge(u32, u32);
 /// Container struct for the function dependency graph.
 pub struct DepGraph {
     edges: HashSet<Edge>,
+    /// Functions in the table, which may be called by any call_indirect.
+    table: Vec<u32>,
 }
 
 /// Private interface for managing the function dependency graph
@@ -35,6 +37,7 @@ impl DepGraph {
     pub fn new() -> Self {
         DepGraph {
             edges: HashSet::new(),
+            table: Vec::new(),
         }
     }
 
@@ -42,7 +45,12 @@ impl DepGraph {
     pub fn edgecount(&self) -> usize {
         self.edges.len()
     }
-    // TODO: better access methods
+
+    /// Returns the indices of every function called from the entry, directly or indirectly.
+    /// The entry itself is only included if it is called.
+    pub fn callees(&self) -> HashSet<u32> {
+        self.edges.iter().map(|edge| edge.1).collect()
+    }
 }
 
 impl DepGraphManager for DepGraph {
@@ -61,15 +69,18 @@ impl DepGraphManager for DepGraph {
         let func_body = &bodies[code_idx];
 
         for instr in func_body.code().elements().iter() {
-            if let Instruction::Call(call_idx) = instr {
-                if self.add_edge(Edge::from((idx, *call_idx))) {
-                    self.probe(*call_idx, imports_len, bodies);
-                } else {
-                    // If the edge already exists then begin backtracking.
-                    return;
+            let callees = match instr {
+                Instruction::Call(call_idx) => vec![*call_idx],
+                // Any function in the table may be called.
+                Instruction::CallIndirect(_, _) => self.table.clone(),
+                _ => continue,
+            };
+            for call_idx in callees {
+                // If the edge already exists then the callee has been probed already.
+                if self.add_edge(Edge::from((idx, call_idx))) {
+                    self.probe(call_idx, imports_len, bodies);
                 }
             }
-            // TODO: Support for call_indirect
         }
     }
 
@@ -90,6 +101,14 @@ impl DepGraphBuilder for DepGraph {
                 0
             };
 
+            if let Some(section) = module.elements_section() {
+                ret.table = section
+                    .entries()
+                    .iter()
+                    .flat_map(|segment| segment.members().iter().cloned())
+                    .collect();
+            }
+
             ret.probe(entry_idx, imports_len, &code_section.bodies());
 
             Ok(ret)
@@ -379,4 +398,58 @@ mod tests {
 
         assert!(g.edgecount() == 15);
     }
+
+    #[test]
+    fn repeated_call() {
+        // wast:
+        // (module
+        //   (func $main (export "main")
+        //     (call $a)
+        //     (call $a)
+        //     (call $b)
+        //   )
+        //   (func $a)
+        //   (func $b)
+        // )
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x04, 0x03, 0x00, 0x00, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e,
+            0x00, 0x00, 0x0a, 0x10, 0x03, 0x08, 0x00, 0x10, 0x01, 0x10, 0x01, 0x10, 0x02, 0x0b,
+            0x02, 0x00, 0x0b, 0x02, 0x00, 0x0b,
+        ];
+
+        let module = Module::from_bytes(&wasm).unwrap();
+        let g = DepGraph::build(&module, 0).unwrap();
+
+        assert!(g.edgecount() == 2);
+        assert_eq!(g.callees(), vec![1, 2].into_iter().collect());
+    }
+
+    #[test]
+    fn call_indirect() {
+        // wast:
+        // (module
+        //   (type $t (func))
+        //   (table 2 funcref)
+        //   (elem (i32.const 0) $a $b)
+        //   (func $main (export "main")
+        //     (call_indirect (type $t) (i32.const 0))
+        //   )
+        //   (func $a)
+        //   (func $b)
+        //   (func $c)
+        // )
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
+            0x03, 0x05, 0x04, 0x00, 0x00, 0x00, 0x00, 0x04, 0x04, 0x01, 0x70, 0x00, 0x02, 0x07,
+            0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00, 0x09, 0x08, 0x01, 0x00, 0x41,
+            0x00, 0x0b, 0x02, 0x01, 0x02, 0x0a, 0x12, 0x04, 0x07, 0x00, 0x41, 0x00, 0x11, 0x00,
+            0x00, 0x0b, 0x02, 0x00, 0x0b, 0x02, 0x00, 0x0b, 0x02, 0x00, 0x0b,
+        ];
+
+        let module = Module::from_bytes(&wasm).unwrap();
+        let g = DepGraph::build(&module, 0).unwrap();
+
+        assert_eq!(g.callees(), vec![1, 2].into_iter().collect());
+    }
 }
@@ -1,8 +1,10 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use parity_wasm::builder;
-use parity_wasm::elements::{CustomSection, Module};
+use parity_wasm::elements::{CustomSection, Module, ValueType};
 
+use super::proposals;
 use super::{ChiselModule, ModuleError, ModuleKind, ModulePreset, ModuleTranslator};
 
 /// Enum on which ModuleTranslator is implemented.
@@ -98,7 +100,7 @@ fn create_custom_deployer(payload: &[u8]) -> Result<Module, ModuleError> {
     let code = deployer_code();
 
     // This is the pre-written deployer code.
-    let mut module = Module::from_bytes(&code)?;
+    let mut module = Module::from_bytes(&code).map_err(|e| ModuleError::Parse(Arc::new(e)))?;
 
     // Re-write memory to pre-allocate enough for code size
     let memory_initial = (payload.len() as u32 / 65536) + 1;
@@ -142,8 +144,7 @@ fn create_memory_deployer(payload: &[u8]) -> Module {
         // Create a func/type for the ethereum::finish
         .function()
             .signature()
-              .param().i32()
-              .param().i32()
+              .with_params(vec![ValueType::I32, ValueType::I32])
               .build()
             .build()
         .import()
@@ -186,7 +187,7 @@ impl ModuleTranslator for Deployer {
     }
 
     fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
-        let payload = module.clone().to_bytes()?;
+        let payload = proposals::into_bytes(module.clone())?;
         let output = match self {
             Deployer::Memory => create_memory_deployer(&payload),
             Deployer::CustomSection => create_custom_deployer(&payload)?,
@@ -206,7 +207,7 @@ mod tests {
         let payload = vec![];
         let output = create_custom_deployer(&payload)
             .unwrap()
-            .to_bytes()
+            .into_bytes()
             .unwrap();
         let expected = FromHex::from_hex(
             "
@@ -229,7 +230,7 @@ mod tests {
         let payload = FromHex::from_hex("80ff007faa550011").unwrap();
         let output = create_custom_deployer(&payload)
             .unwrap()
-            .to_bytes()
+            .into_bytes()
             .unwrap();
         let expected = FromHex::from_hex(
             "
@@ -260,7 +261,7 @@ mod tests {
     #[test]
     fn memory_zero_payload() {
         let payload = vec![];
-        let output = create_memory_deployer(&payload).to_bytes().unwrap();
+        let output = create_memory_deployer(&payload).into_bytes().unwrap();
         let expected = FromHex::from_hex(
             "
             0061736d0100000001090260027f7f0060000002130108657468657265756d0666
@@ -275,7 +276,7 @@ mod tests {
     #[test]
     fn memory_nonzero_payload() {
         let payload = FromHex::from_hex("80ff007faa550011").unwrap();
-        let output = create_memory_deployer(&payload).to_bytes().unwrap();
+        let output = create_memory_deployer(&payload).into_bytes().unwrap();
         let expected = FromHex::from_hex(
             "
             0061736d0100000001090260027f7f0060000002130108657468657265756d0666
@@ -317,7 +318,7 @@ mod tests {
         ",
         )
         .unwrap();
-        let output = module.to_bytes().unwrap();
+        let output = module.into_bytes().unwrap();
         assert_eq!(output, expected);
     }
 
@@ -338,7 +339,7 @@ mod tests {
         ",
         )
         .unwrap();
-        let output = module.to_bytes().unwrap();
+        let output = module.into_bytes().unwrap();
         assert_eq!(output, expected);
     }
 }
@@ -0,0 +1,646 @@
+//! Semantic diff between two versions of a module.
+//! Lists the imports, exports, sections and functions which differ between a module and the
+//! result of translating it, along with the size of each section before and after.
+//! Translators describe the changes they make with the same vocabulary, in `Changes`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use parity_wasm::elements::{
+    External, FunctionType, ImportCountType, Internal, Module, Section, Serialize, Type,
+};
+
+use super::proposals;
+
+/// A single semantic change between two modules.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// An import was added. Fields are the module and field names.
+    ImportAdded(String, String),
+    /// An import was removed. Fields are the module and field names.
+    ImportRemoved(String, String),
+    /// An import of the same kind and type was replaced by one with a different name. Fields are
+    /// the module and field names before and after.
+    ImportRenamed((String, String), (String, String)),
+    /// An export was added.
+    ExportAdded(String),
+    /// An export was removed.
+    ExportRemoved(String),
+    /// An export now refers to a different kind of entity. Fields are the export name and the
+    /// kinds before and after.
+    ExportChanged(String, &'static str, &'static str),
+    /// A section was added.
+    SectionAdded(String),
+    /// A section was dropped.
+    SectionDropped(String),
+    /// A named function was removed.
+    FunctionRemoved(String),
+    /// The number of functions, including imported functions, changed.
+    FunctionCount(usize, usize),
+    /// The signature of an imported, exported or named function changed. Fields are the function
+    /// and the signatures before and after.
+    SignatureChanged(String, String, String),
+}
+
+/// The encoded size of a section, in bytes, before and after. A size of zero means the section
+/// is not present.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionSize {
+    pub name: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+/// The differences between two modules.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModuleDiff {
+    changes: Vec<Change>,
+    sections: Vec<SectionSize>,
+}
+
+impl ModuleDiff {
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Returns the size of every section present in either module, in order of appearance.
+    pub fn sections(&self) -> &[SectionSize] {
+        &self.sections
+    }
+
+    /// Returns the total size delta of all sections, in bytes.
+    pub fn size_delta(&self) -> isize {
+        self.sections
+            .iter()
+            .map(|s| s.after as isize - s.before as isize)
+            .sum()
+    }
+
+    /// Returns true if the modules are semantically identical and all sections are of equal size.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.sections.iter().all(|s| s.before == s.after)
+    }
+}
+
+/// The changes made by a single translator, as reported by the translator itself.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Changes {
+    changes: Vec<Change>,
+    size_delta: Option<isize>,
+}
+
+impl Changes {
+    pub fn push(&mut self, change: Change) {
+        self.changes.push(change);
+    }
+
+    /// Records that the module grew by `delta` bytes, or shrank if it is negative.
+    pub fn add_size_delta(&mut self, delta: isize) {
+        *self.size_delta.get_or_insert(0) += delta;
+    }
+
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Returns the number of bytes added to the module, negative if bytes were removed, or none
+    /// if the translator does not measure its changes.
+    pub fn size_delta(&self) -> Option<isize> {
+        self.size_delta
+    }
+}
+
+/// Computes the semantic differences between `before` and `after`.
+pub fn diff(before: &Module, after: &Module) -> ModuleDiff {
+    let mut changes = Vec::new();
+    diff_imports(before, after, &mut changes);
+    diff_exports(before, after, &mut changes);
+    diff_sections_present(before, after, &mut changes);
+    diff_functions(before, after, &mut changes);
+
+    ModuleDiff {
+        changes,
+        sections: section_sizes(before, after),
+    }
+}
+
+fn diff_imports(before: &Module, after: &Module, changes: &mut Vec<Change>) {
+    let imports = |module: &Module| -> Vec<(String, String, External)> {
+        module
+            .import_section()
+            .map(|section| {
+                section
+                    .entries()
+                    .iter()
+                    .map(|e| (e.module().to_string(), e.field().to_string(), *e.external()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let (old, new) = (imports(before), imports(after));
+    let same_name =
+        |a: &(String, String, External), b: &(String, String, External)| a.0 == b.0 && a.1 == b.1;
+
+    for import in old.iter() {
+        if let Some(matching) = new.iter().find(|other| same_name(import, other)) {
+            diff_import_signature(before, after, import, matching, changes);
+        }
+    }
+
+    let mut added: Vec<&(String, String, External)> = new
+        .iter()
+        .filter(|import| !old.iter().any(|other| same_name(import, other)))
+        .collect();
+    for import in old
+        .iter()
+        .filter(|import| !new.iter().any(|other| same_name(import, other)))
+    {
+        // An import replaced by another of the same kind in its place was renamed, as done by
+        // remapimports.
+        match added
+            .iter()
+            .position(|other| external_kind(&import.2) == external_kind(&other.2))
+        {
+            Some(idx) => {
+                let renamed = added.remove(idx);
+                changes.push(Change::ImportRenamed(
+                    (import.0.clone(), import.1.clone()),
+                    (renamed.0.clone(), renamed.1.clone()),
+                ));
+                diff_import_signature(before, after, import, renamed, changes);
+            }
+            None => changes.push(Change::ImportRemoved(import.0.clone(), import.1.clone())),
+        }
+    }
+    for import in added {
+        changes.push(Change::ImportAdded(import.0.clone(), import.1.clone()));
+    }
+}
+
+fn diff_import_signature(
+    before: &Module,
+    after: &Module,
+    old: &(String, String, External),
+    new: &(String, String, External),
+    changes: &mut Vec<Change>,
+) {
+    if let (External::Function(old_type), External::Function(new_type)) = (old.2, new.2) {
+        let old_sig = signature(function_type(before, old_type));
+        let new_sig = signature(function_type(after, new_type));
+        if old_sig != new_sig {
+            changes.push(Change::SignatureChanged(
+                format!("import {}.{}", new.0, new.1),
+                old_sig,
+                new_sig,
+            ));
+        }
+    }
+}
+
+fn diff_exports(before: &Module, after: &Module, changes: &mut Vec<Change>) {
+    let exports = |module: &Module| -> Vec<(String, Internal)> {
+        module
+            .export_section()
+            .map(|section| {
+                section
+                    .entries()
+                    .iter()
+                    .map(|e| (e.field().to_string(), *e.internal()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let (old, new) = (exports(before), exports(after));
+
+    for (name, internal) in old.iter() {
+        match new.iter().find(|(other, _)| other == name) {
+            Some((_, new_internal)) => match (internal, new_internal) {
+                (Internal::Function(old_idx), Internal::Function(new_idx)) => {
+                    let old_sig = signature(function_signature(before, *old_idx));
+                    let new_sig = signature(function_signature(after, *new_idx));
+                    if old_sig != new_sig {
+                        changes.push(Change::SignatureChanged(
+                            format!("export {}", name),
+                            old_sig,
+                            new_sig,
+                        ));
+                    }
+                }
+                (old_internal, new_internal) => {
+                    let (old_kind, new_kind) =
+                        (internal_kind(old_internal), internal_kind(new_internal));
+                    if old_kind != new_kind {
+                        changes.push(Change::ExportChanged(name.clone(), old_kind, new_kind));
+                    }
+                }
+            },
+            None => changes.push(Change::ExportRemoved(name.clone())),
+        }
+    }
+    for (name, _) in new.iter() {
+        if !old.iter().any(|(other, _)| other == name) {
+            changes.push(Change::ExportAdded(name.clone()));
+        }
+    }
+}
+
+fn diff_sections_present(before: &Module, after: &Module, changes: &mut Vec<Change>) {
+    let (old, new) = (section_names(before), section_names(after));
+    for name in old.iter().filter(|name| !new.contains(name)) {
+        changes.push(Change::SectionDropped(name.clone()));
+    }
+    for name in new.iter().filter(|name| !old.contains(name)) {
+        changes.push(Change::SectionAdded(name.clone()));
+    }
+}
+
+fn diff_functions(before: &Module, after: &Module, changes: &mut Vec<Change>) {
+    let (old_names, new_names) = (function_names(before), function_names(after));
+    // Functions are only compared by name if both modules have a names section, since
+    // otherwise every function would appear removed.
+    if !old_names.is_empty() && !new_names.is_empty() {
+        let mut removed: Vec<(&u32, &String)> = old_names
+            .iter()
+            .filter(|(_, name)| !new_names.values().any(|other| other == *name))
+            .collect();
+        removed.sort();
+        for (_, name) in removed {
+            changes.push(Change::FunctionRemoved(name.clone()));
+        }
+
+        let mut kept: Vec<(&u32, &String)> = old_names.iter().collect();
+        kept.sort();
+        for (old_idx, name) in kept {
+            if let Some((new_idx, _)) = new_names.iter().find(|(_, other)| *other == name) {
+                let old_sig = signature(function_signature(before, *old_idx));
+                let new_sig = signature(function_signature(after, *new_idx));
+                if old_sig != new_sig {
+                    changes.push(Change::SignatureChanged(name.clone(), old_sig, new_sig));
+                }
+            }
+        }
+    }
+
+    // The functions implementing non-trapping conversions are not part of the serialized module.
+    let count = |module: &Module| module.functions_space() - proposals::conversions(module).len();
+    let (old_count, new_count) = (count(before), count(after));
+    if old_count != new_count {
+        changes.push(Change::FunctionCount(old_count, new_count));
+    }
+}
+
+/// Returns the names of the functions in the names section, by function index.
+fn function_names(module: &Module) -> HashMap<u32, String> {
+    module
+        .names_section()
+        .and_then(|names| names.functions())
+        .map(|functions| {
+            functions
+                .names()
+                .iter()
+                .map(|(idx, name)| (idx, name.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the type at `type_idx` if it is a function type.
+fn function_type(module: &Module, type_idx: u32) -> Option<&FunctionType> {
+    module
+        .type_section()
+        .and_then(|section| section.types().get(type_idx as usize))
+        .map(|ty| match ty {
+            Type::Function(ty) => ty,
+        })
+}
+
+/// Returns the type of the function at `func_idx` in the function index space, which starts with
+/// imported functions.
+fn function_signature(module: &Module, func_idx: u32) -> Option<&FunctionType> {
+    let imported = module.import_count(ImportCountType::Function) as u32;
+    let type_idx = if func_idx < imported {
+        module
+            .import_section()?
+            .entries()
+            .iter()
+            .filter_map(|e| match e.external() {
+                External::Function(type_idx) => Some(*type_idx),
+                _ => None,
+            })
+            .nth(func_idx as usize)?
+    } else {
+        module
+            .function_section()?
+            .entries()
+            .get((func_idx - imported) as usize)?
+            .type_ref()
+    };
+    function_type(module, type_idx)
+}
+
+/// Renders a function type as "(params) -> result", or "(params) -> (results)" for multiple
+/// results.
+fn signature(ty: Option<&FunctionType>) -> String {
+    match ty {
+        Some(ty) => {
+            let params: Vec<String> = ty.params().iter().map(|p| p.to_string()).collect();
+            let results: Vec<String> = ty.results().iter().map(|r| r.to_string()).collect();
+            match results.len() {
+                0 => format!("({})", params.join(", ")),
+                1 => format!("({}) -> {}", params.join(", "), results[0]),
+                _ => format!("({}) -> ({})", params.join(", "), results.join(", ")),
+            }
+        }
+        None => "<unknown>".to_string(),
+    }
+}
+
+fn external_kind(external: &External) -> &'static str {
+    match external {
+        External::Function(_) => "function",
+        External::Table(_) => "table",
+        External::Memory(_) => "memory",
+        External::Global(_) => "global",
+    }
+}
+
+fn internal_kind(internal: &Internal) -> &'static str {
+    match internal {
+        Internal::Function(_) => "function",
+        Internal::Table(_) => "table",
+        Internal::Memory(_) => "memory",
+        Internal::Global(_) => "global",
+    }
+}
+
+/// Returns a human-readable name for a section. Custom sections are named after their name.
+pub(crate) fn section_name(section: &Section) -> String {
+    match section {
+        Section::Unparsed { id, .. } => format!("unknown ({})", id),
+        Section::Custom(custom) => format!("custom \"{}\"", custom.name()),
+        Section::Type(_) => "type".to_string(),
+        Section::Import(_) => "import".to_string(),
+        Section::Function(_) => "function".to_string(),
+        Section::Table(_) => "table".to_string(),
+        Section::Memory(_) => "memory".to_string(),
+        Section::Global(_) => "global".to_string(),
+        Section::Export(_) => "export".to_string(),
+        Section::Start(_) => "start".to_string(),
+        Section::Element(_) => "element".to_string(),
+        Section::DataCount(_) => "datacount".to_string(),
+        Section::Code(_) => "code".to_string(),
+        Section::Data(_) => "data".to_string(),
+        Section::Name(_) => "custom \"name\"".to_string(),
+        Section::Reloc(reloc) => format!("custom \"{}\"", reloc.name()),
+    }
+}
+
+fn section_names(module: &Module) -> Vec<String> {
+    module.sections().iter().map(section_name).collect()
+}
+
+/// Returns the encoded size of a section, including its id and length.
+pub(crate) fn section_size(section: &Section) -> usize {
+    encoded_size(section.clone())
+}
+
+/// Returns the encoded size of a section or an entry of one.
+pub(crate) fn encoded_size<T: Serialize>(value: T) -> usize {
+    let mut buf = Vec::new();
+    match value.serialize(&mut buf) {
+        Ok(()) => buf.len(),
+        Err(_) => 0,
+    }
+}
+
+fn section_sizes(before: &Module, after: &Module) -> Vec<SectionSize> {
+    let mut sizes: Vec<SectionSize> = Vec::new();
+    for (module, is_before) in [(before, true), (after, false)].iter() {
+        for section in module.sections() {
+            let name = section_name(section);
+            let size = section_size(section);
+            let idx = match sizes.iter().position(|s| s.name == name) {
+                Some(idx) => idx,
+                None => {
+                    sizes.push(SectionSize {
+                        name,
+                        before: 0,
+                        after: 0,
+                    });
+                    sizes.len() - 1
+                }
+            };
+            if *is_before {
+                sizes[idx].before += size;
+            } else {
+                sizes[idx].after += size;
+            }
+        }
+    }
+    sizes
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::ImportAdded(module, field) => write!(f, "+ import {}.{}", module, field),
+            Change::ImportRemoved(module, field) => write!(f, "- import {}.{}", module, field),
+            Change::ImportRenamed(from, to) => write!(
+                f,
+                "~ import {}.{} renamed to {}.{}",
+                from.0, from.1, to.0, to.1
+            ),
+            Change::ExportAdded(name) => write!(f, "+ export {}", name),
+            Change::ExportRemoved(name) => write!(f, "- export {}", name),
+            Change::ExportChanged(name, from, to) => {
+                write!(f, "~ export {} changed from {} to {}", name, from, to)
+            }
+            Change::SectionAdded(name) => write!(f, "+ section {}", name),
+            Change::SectionDropped(name) => write!(f, "- section {}", name),
+            Change::FunctionRemoved(name) => write!(f, "- function {}", name),
+            Change::FunctionCount(from, to) => write!(f, "~ functions: {} -> {}", from, to),
+            Change::SignatureChanged(name, from, to) => {
+                write!(f, "~ signature of {}: {} -> {}", name, from, to)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ModuleDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+        for size in self.sections.iter().filter(|s| s.before != s.after) {
+            writeln!(
+                f,
+                "section {}: {} -> {} bytes ({:+})",
+                size.name,
+                size.before,
+                size.after,
+                size.after as isize - size.before as isize
+            )?;
+        }
+        write!(f, "total size delta: {:+} bytes", self.size_delta())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parity_wasm::elements::{CustomSection, ValueType};
+
+    use super::*;
+    use crate::dropsection::DropSection;
+    use crate::remapimports::RemapImports;
+    use crate::trimexports::TrimExports;
+    use crate::{ModulePreset, ModuleTranslator};
+
+    fn sample() -> Module {
+        // wast:
+        // (module
+        //   (import "env" "ethereum_useGas" (func (param i64)))
+        //   (memory 1)
+        //   (export "main" (func $main))
+        //   (export "memory" (memory 0))
+        //   (export "foo" (func $main))
+        //
+        //   (func $main)
+        // )
+        let wasm: Vec<u8> = vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x60, 0x01, 0x7e,
+            0x00, 0x60, 0x00, 0x00, 0x02, 0x17, 0x01, 0x03, 0x65, 0x6e, 0x76, 0x0f, 0x65, 0x74,
+            0x68, 0x65, 0x72, 0x65, 0x75, 0x6d, 0x5f, 0x75, 0x73, 0x65, 0x47, 0x61, 0x73, 0x00,
+            0x00, 0x03, 0x02, 0x01, 0x01, 0x05, 0x03, 0x01, 0x00, 0x01, 0x07, 0x17, 0x03, 0x04,
+            0x6d, 0x61, 0x69, 0x6e, 0x00, 0x01, 0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02,
+            0x00, 0x03, 0x66, 0x6f, 0x6f, 0x00, 0x01, 0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+        ];
+        Module::from_bytes(&wasm).unwrap()
+    }
+
+    #[test]
+    fn identical_modules() {
+        let module = sample();
+        let diff = diff(&module, &module);
+        assert!(diff.is_empty());
+        assert_eq!(diff.size_delta(), 0);
+    }
+
+    #[test]
+    fn renamed_import() {
+        let before = sample();
+        let after = RemapImports::with_preset("ewasm")
+            .unwrap()
+            .translate(&before)
+            .unwrap()
+            .expect("Should be mutated");
+
+        let diff = diff(&before, &after);
+        assert_eq!(
+            diff.changes(),
+            &[Change::ImportRenamed(
+                ("env".to_string(), "ethereum_useGas".to_string()),
+                ("ethereum".to_string(), "useGas".to_string())
+            )]
+        );
+        let import = diff
+            .sections()
+            .iter()
+            .find(|s| s.name == "import")
+            .expect("Import section is present");
+        assert!(import.after < import.before);
+        assert_eq!(
+            diff.to_string().lines().next(),
+            Some("~ import env.ethereum_useGas renamed to ethereum.useGas")
+        );
+    }
+
+    #[test]
+    fn removed_export() {
+        let before = sample();
+        let mut after = before.clone();
+        assert!(TrimExports::with_preset("ewasm")
+            .unwrap()
+            .translate_inplace(&mut after)
+            .unwrap());
+
+        let diff = diff(&before, &after);
+        assert_eq!(diff.changes(), &[Change::ExportRemoved("foo".to_string())]);
+        assert!(diff.size_delta() < 0);
+    }
+
+    #[test]
+    fn translator_changes() {
+        let mut module = sample();
+        let trimmer = TrimExports::with_preset("ewasm").unwrap();
+        let changes = trimmer
+            .translate_with_changes(&mut module)
+            .unwrap()
+            .expect("Should be mutated");
+        assert_eq!(
+            changes.changes(),
+            &[Change::ExportRemoved("foo".to_string())]
+        );
+        let diff = diff(&sample(), &module);
+        assert_eq!(changes.size_delta(), Some(diff.size_delta()));
+        assert_eq!(trimmer.translate_with_changes(&mut module), Ok(None));
+    }
+
+    #[test]
+    fn dropped_section() {
+        let mut before = sample();
+        before
+            .sections_mut()
+            .push(Section::Custom(CustomSection::new(
+                "extra".to_string(),
+                vec![1, 2, 3],
+            )));
+        let mut after = before.clone();
+        assert!(DropSection::CustomSectionByName("extra".to_string())
+            .translate_inplace(&mut after)
+            .unwrap());
+
+        let diff = diff(&before, &after);
+        assert_eq!(
+            diff.changes(),
+            &[Change::SectionDropped("custom \"extra\"".to_string())]
+        );
+        assert_eq!(diff.size_delta(), -11);
+    }
+
+    #[test]
+    fn saturating_conversion() {
+        let parse = |text: &str| proposals::from_bytes(&wat::parse_str(text).unwrap()).unwrap();
+        let before = parse("(module (func (result i32) i32.const 0))");
+        let after = parse("(module (func (result i32) f32.const 0 i32.trunc_sat_f32_s))");
+        // The function standing in for the conversion is not counted.
+        assert_eq!(diff(&before, &after).changes(), &[]);
+    }
+
+    #[test]
+    fn changed_signature() {
+        let before = sample();
+        let mut after = before.clone();
+        if let Some(types) = after.type_section_mut() {
+            match &mut types.types_mut()[1] {
+                Type::Function(ty) => *ty.results_mut() = vec![ValueType::I32],
+            }
+        }
+
+        let diff = diff(&before, &after);
+        assert_eq!(
+            diff.changes(),
+            &[
+                Change::SignatureChanged(
+                    "export main".to_string(),
+                    "()".to_string(),
+                    "() -> i32".to_string()
+                ),
+                Change::SignatureChanged(
+                    "export foo".to_string(),
+                    "()".to_string(),
+                    "() -> i32".to_string()
+                )
+            ]
+        );
+    }
+}
@@ -1,16 +1,11 @@
 use std::collections::HashMap;
-use std::error::Error;
 
+use log::debug;
 use parity_wasm::elements::{Module, Section};
 
+use super::diff::{encoded_size, section_name, Change, Changes};
 use super::{ChiselModule, ModuleError, ModuleKind, ModuleTranslator};
 
-impl From<std::num::ParseIntError> for ModuleError {
-    fn from(error: std::num::ParseIntError) -> Self {
-        ModuleError::Custom(error.description().to_string())
-    }
-}
-
 /// Enum on which ModuleTranslator is implemented.
 #[derive(Debug)]
 pub enum DropSection {
@@ -51,33 +46,49 @@ impl<'a> ChiselModule<'a> for DropSection {
             ("unknown_by_index", config.get("unknown_by_index".into())),
         ];
 
-        // Filter out modes which were provided.
+        // Filter out modes which were provided. The names mode is a flag, and is not provided if
+        // it is false.
         let mut matches: Vec<(&'static str, &String)> = modes
             .iter()
-            .filter_map(|(k, v)| {
-                if let Some(v) = v {
-                    Some((*k, *v))
-                } else {
-                    None
-                }
+            .filter_map(|(k, v)| match v {
+                Some(v) if *k == "names" && v.as_str() == "false" => None,
+                Some(v) => Some((*k, *v)),
+                None => None,
             })
             .collect();
 
         // Reject multiple modes
         if matches.len() != 1 {
-            return Err(ModuleError::Custom(
-                "Only one mode allowed at a time".to_string(),
+            return Err(ModuleError::InvalidConfig(
+                modes
+                    .iter()
+                    .map(|(k, _)| *k)
+                    .collect::<Vec<&str>>()
+                    .join("|"),
+                "only one mode allowed at a time".to_string(),
             ));
         }
 
         let (mode, val) = matches.pop().expect("Verified that one match is present");
+        let index = || {
+            str::parse::<usize>(val).map_err(|_| {
+                ModuleError::InvalidConfig(
+                    mode.to_string(),
+                    format!("expected an index, found '{}'", val),
+                )
+            })
+        };
         match mode {
-            "names" => Ok(DropSection::NamesSection),
+            "names" => match val.as_str() {
+                "true" => Ok(DropSection::NamesSection),
+                _ => Err(ModuleError::InvalidConfig(
+                    mode.to_string(),
+                    format!("expected true or false, found '{}'", val),
+                )),
+            },
             "custom_by_name" => Ok(DropSection::CustomSectionByName(val.clone())),
-            "custom_by_index" => Ok(DropSection::CustomSectionByIndex(str::parse::<usize>(val)?)),
-            "unknown_by_index" => Ok(DropSection::UnknownSectionByIndex(str::parse::<usize>(
-                val,
-            )?)),
+            "custom_by_index" => Ok(DropSection::CustomSectionByIndex(index()?)),
+            "unknown_by_index" => Ok(DropSection::UnknownSectionByIndex(index()?)),
             _ => panic!("Only one of the above was present in the array"),
         }
     }
@@ -102,27 +113,37 @@ impl DropSection {
         }
     }
 
-    fn drop_section(&self, module: &mut Module) -> Result<bool, ModuleError> {
+    /// Returns the dropped section, if any.
+    fn drop_section(&self, module: &mut Module) -> Result<Option<Section>, ModuleError> {
         if let Some(index) = self.find_index(&module) {
             let sections = module.sections_mut();
             if index < sections.len() {
-                sections.remove(index);
-                return Ok(true);
+                debug!("Dropping section at index {}", index);
+                return Ok(Some(sections.remove(index)));
             }
         }
 
-        Ok(false)
+        Ok(None)
     }
 }
 
 impl<'a> ModuleTranslator for DropSection {
     fn translate_inplace(&self, module: &mut Module) -> Result<bool, ModuleError> {
-        Ok(self.drop_section(module)?)
+        Ok(self.drop_section(module)?.is_some())
+    }
+
+    fn translate_with_changes(&self, module: &mut Module) -> Result<Option<Changes>, ModuleError> {
+        Ok(self.drop_section(module)?.map(|section| {
+            let mut changes = Changes::default();
+            changes.push(Change::SectionDropped(section_name(&section)));
+            changes.add_size_delta(-(encoded_size(section) as isize));
+            changes
+        }))
     }
 
     fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
         let mut ret = module.clone();
-        if self.drop_section(&mut ret)? {
+        if self.drop_section(&mut ret)?.is_some() {
             Ok(Some(ret))
         } else {
             Ok(None)
@@ -313,6 +334,35 @@ mod tests {
         assert!(custom_section_index_for(&module1, "name").is_none());
     }
 
+    #[test]
+    fn with_config_names_flag() {
+        let mut conf = HashMap::new();
+        conf.insert("names".to_string(), "true".to_string());
+        assert!(matches!(
+            DropSection::with_config(&conf),
+            Ok(DropSection::NamesSection)
+        ));
+
+        // A false flag is not a mode.
+        conf.insert("names".to_string(), "false".to_string());
+        assert!(DropSection::with_config(&conf).is_err());
+        conf.insert("custom_by_name".to_string(), "producers".to_string());
+        assert!(matches!(
+            DropSection::with_config(&conf),
+            Ok(DropSection::CustomSectionByName(_))
+        ));
+
+        conf.clear();
+        conf.insert("names".to_string(), "yes".to_string());
+        assert_eq!(
+            DropSection::with_config(&conf).unwrap_err(),
+            ModuleError::InvalidConfig(
+                "names".to_string(),
+                "expected true or false, found 'yes'".to_string()
+            )
+        );
+    }
+
     #[test]
     fn with_config_multiple_modes() {
         let mut conf = HashMap::new();
@@ -322,7 +372,10 @@ mod tests {
         let module = DropSection::with_config(&conf);
         assert_eq!(
             module.unwrap_err(),
-            ModuleError::Custom("Only one mode allowed at a time".to_string())
+            ModuleError::InvalidConfig(
+                "names|custom_by_name|custom_by_index|unknown_by_index".to_string(),
+                "only one mode allowed at a time".to_string()
+            )
         );
     }
 }
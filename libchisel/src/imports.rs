@@ -90,27 +90,27 @@ impl<'a> ModulePreset for ImportList<'a> {
                 ImportType::Function(
                     "ethereum",
                     "useGas",
-                    FunctionType::new(vec![ValueType::I64], None),
+                    FunctionType::new(vec![ValueType::I64], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getGasLeft",
-                    FunctionType::new(vec![], Some(ValueType::I64)),
+                    FunctionType::new(vec![], vec![ValueType::I64]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getAddress",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getExternalBalance",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getBlockHash",
-                    FunctionType::new(vec![ValueType::I64, ValueType::I32], Some(ValueType::I32)),
+                    FunctionType::new(vec![ValueType::I64, ValueType::I32], vec![ValueType::I32]),
                 ),
                 ImportType::Function(
                     "ethereum",
@@ -123,7 +123,7 @@ impl<'a> ModulePreset for ImportList<'a> {
                             ValueType::I32,
                             ValueType::I32,
                         ],
-                        Some(ValueType::I32),
+                        vec![ValueType::I32],
                     ),
                 ),
                 ImportType::Function(
@@ -137,7 +137,7 @@ impl<'a> ModulePreset for ImportList<'a> {
                             ValueType::I32,
                             ValueType::I32,
                         ],
-                        Some(ValueType::I32),
+                        vec![ValueType::I32],
                     ),
                 ),
                 ImportType::Function(
@@ -150,7 +150,7 @@ impl<'a> ModulePreset for ImportList<'a> {
                             ValueType::I32,
                             ValueType::I32,
                         ],
-                        Some(ValueType::I32),
+                        vec![ValueType::I32],
                     ),
                 ),
                 ImportType::Function(
@@ -163,7 +163,7 @@ impl<'a> ModulePreset for ImportList<'a> {
                             ValueType::I32,
                             ValueType::I32,
                         ],
-                        Some(ValueType::I32),
+                        vec![ValueType::I32],
                     ),
                 ),
                 ImportType::Function(
@@ -176,28 +176,28 @@ impl<'a> ModulePreset for ImportList<'a> {
                             ValueType::I32,
                             ValueType::I32,
                         ],
-                        Some(ValueType::I32),
+                        vec![ValueType::I32],
                     ),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "callDataCopy",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getCallDataSize",
-                    FunctionType::new(vec![], Some(ValueType::I32)),
+                    FunctionType::new(vec![], vec![ValueType::I32]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getCodeSize",
-                    FunctionType::new(vec![], Some(ValueType::I32)),
+                    FunctionType::new(vec![], vec![ValueType::I32]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getExternalCodeSize",
-                    FunctionType::new(vec![ValueType::I32], Some(ValueType::I32)),
+                    FunctionType::new(vec![ValueType::I32], vec![ValueType::I32]),
                 ),
                 ImportType::Function(
                     "ethereum",
@@ -209,68 +209,68 @@ impl<'a> ModulePreset for ImportList<'a> {
                             ValueType::I32,
                             ValueType::I32,
                         ],
-                        None,
+                        vec![],
                     ),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "codeCopy",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getCaller",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getCallValue",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getBlockDifficulty",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getBlockCoinbase",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getBlockNumber",
-                    FunctionType::new(vec![], Some(ValueType::I64)),
+                    FunctionType::new(vec![], vec![ValueType::I64]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getBlockGasLimit",
-                    FunctionType::new(vec![], Some(ValueType::I64)),
+                    FunctionType::new(vec![], vec![ValueType::I64]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getBlockTimestamp",
-                    FunctionType::new(vec![], Some(ValueType::I64)),
+                    FunctionType::new(vec![], vec![ValueType::I64]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getTxGasPrice",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getTxOrigin",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "storageStore",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "storageLoad",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
@@ -285,99 +285,99 @@ impl<'a> ModulePreset for ImportList<'a> {
                             ValueType::I32,
                             ValueType::I32,
                         ],
-                        None,
+                        vec![],
                     ),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "getReturnDataSize",
-                    FunctionType::new(vec![], Some(ValueType::I32)),
+                    FunctionType::new(vec![], vec![ValueType::I32]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "returnDataCopy",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "finish",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "revert",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "ethereum",
                     "selfDestruct",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
             ])),
             "eth2" => Ok(ImportList(vec![
                 ImportType::Function(
                     "eth2",
                     "loadPreStateRoot",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "eth2",
                     "blockDataSize",
-                    FunctionType::new(vec![], Some(ValueType::I32)),
+                    FunctionType::new(vec![], vec![ValueType::I32]),
                 ),
                 ImportType::Function(
                     "eth2",
                     "blockDataCopy",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "eth2",
                     "savePostStateRoot",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "eth2",
                     "pushNewDeposit",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
                 ),
             ])),
             "debug" => Ok(ImportList(vec![
                 ImportType::Function(
                     "debug",
                     "print32",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "debug",
                     "print64",
-                    FunctionType::new(vec![ValueType::I64], None),
+                    FunctionType::new(vec![ValueType::I64], vec![]),
                 ),
                 ImportType::Function(
                     "debug",
                     "printMem",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "debug",
                     "printMemHex",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "debug",
                     "printStorage",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "debug",
                     "printStorageHex",
-                    FunctionType::new(vec![ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32], vec![]),
                 ),
             ])),
             "bignum" => Ok(ImportList(vec![
                 ImportType::Function(
                     "bignum",
                     "mul256",
-                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], None),
+                    FunctionType::new(vec![ValueType::I32, ValueType::I32, ValueType::I32], vec![]),
                 ),
                 ImportType::Function(
                     "bignum",
@@ -389,7 +389,7 @@ impl<'a> ModulePreset for ImportList<'a> {
                             ValueType::I32,
                             ValueType::I32,
                         ],
-                        None,
+                        vec![],
                     ),
                 ),
             ])),
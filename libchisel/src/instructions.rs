@@ -0,0 +1,282 @@
+//! Names and classes of instructions, as used in module configurations.
+//! Instructions are named as in the text format, e.g. "memory.grow" or "i64.div_u".
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use parity_wasm::elements::{BulkInstruction, Deserialize, Instruction};
+
+use super::proposals::CONVERSIONS;
+
+/// A class of instructions.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+pub enum Class {
+    /// Float instructions, including float loads, stores and constants.
+    Float,
+    /// i32 division and remainder.
+    I32Division,
+    /// i64 division and remainder.
+    I64Division,
+    /// Loads, stores, memory.size and memory.grow.
+    Memory,
+    SignExtension,
+    BulkMemory,
+}
+
+const CLASSES: &[(&str, Class)] = &[
+    ("float", Class::Float),
+    ("i32_division", Class::I32Division),
+    ("i64_division", Class::I64Division),
+    ("memory", Class::Memory),
+    ("sign_extension", Class::SignExtension),
+    ("bulk_memory", Class::BulkMemory),
+];
+
+impl Class {
+    /// Returns the class of the given name, e.g. "float".
+    pub fn from_name(name: &str) -> Option<Class> {
+        CLASSES
+            .iter()
+            .find(|(class_name, _)| *class_name == name)
+            .map(|(_, class)| *class)
+    }
+
+    /// Returns true if the instruction belongs to the class.
+    pub fn contains(self, instruction: &Instruction) -> bool {
+        match self {
+            Class::Float => is_float(instruction),
+            Class::I32Division => matches!(
+                instruction,
+                Instruction::I32DivS
+                    | Instruction::I32DivU
+                    | Instruction::I32RemS
+                    | Instruction::I32RemU
+            ),
+            Class::I64Division => matches!(
+                instruction,
+                Instruction::I64DivS
+                    | Instruction::I64DivU
+                    | Instruction::I64RemS
+                    | Instruction::I64RemU
+            ),
+            Class::Memory => is_memory(instruction),
+            Class::SignExtension => matches!(instruction, Instruction::SignExt(_)),
+            Class::BulkMemory => matches!(instruction, Instruction::Bulk(_)),
+        }
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = CLASSES
+            .iter()
+            .find(|(_, class)| class == self)
+            .map(|(name, _)| *name)
+            .expect("every class is named");
+        write!(f, "{}", name)
+    }
+}
+
+/// Returns the name of the instruction, without its immediates.
+pub fn name(instruction: &Instruction) -> String {
+    // parity-wasm displays the names used before the text format was finalized.
+    let display = instruction.to_string();
+    let legacy = display.split(' ').next().unwrap_or_default();
+    let renamed = match legacy {
+        "get_local" => "local.get",
+        "set_local" => "local.set",
+        "tee_local" => "local.tee",
+        "get_global" => "global.get",
+        "set_global" => "global.set",
+        "current_memory" => "memory.size",
+        "grow_memory" => "memory.grow",
+        _ => match instruction {
+            Instruction::Bulk(BulkInstruction::MemoryDrop(_)) => "data.drop",
+            Instruction::Bulk(BulkInstruction::TableDrop(_)) => "elem.drop",
+            _ => legacy,
+        },
+    };
+    // Conversions are named after both types, e.g. "i32.trunc_s/f32" is "i32.trunc_f32_s".
+    match renamed.find('/') {
+        Some(idx) => {
+            let (op, ty) = (&renamed[..idx], &renamed[idx + 1..]);
+            if op.ends_with("_s") || op.ends_with("_u") {
+                let (op, sign) = op.split_at(op.len() - 2);
+                format!("{}_{}{}", op, ty, sign)
+            } else {
+                format!("{}_{}", op, ty)
+            }
+        }
+        None => renamed.to_string(),
+    }
+}
+
+/// Returns the names of every instruction which can be parsed.
+pub fn names() -> BTreeSet<String> {
+    // Every opcode is decoded with zeroes for its immediates, which are valid for all of them.
+    (0..=0xffu8)
+        .map(|opcode| vec![opcode])
+        .chain((0..=0xffu8).map(|opcode| vec![0xfc, opcode]))
+        .filter_map(|mut bytes| {
+            bytes.extend_from_slice(&[0; 9]);
+            Instruction::deserialize(&mut &bytes[..]).ok()
+        })
+        .map(|instruction| name(&instruction))
+        .chain(CONVERSIONS.iter().map(|(name, _, _)| name.to_string()))
+        .collect()
+}
+
+fn is_memory(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::I32Load(_, _)
+            | Instruction::I64Load(_, _)
+            | Instruction::F32Load(_, _)
+            | Instruction::F64Load(_, _)
+            | Instruction::I32Load8S(_, _)
+            | Instruction::I32Load8U(_, _)
+            | Instruction::I32Load16S(_, _)
+            | Instruction::I32Load16U(_, _)
+            | Instruction::I64Load8S(_, _)
+            | Instruction::I64Load8U(_, _)
+            | Instruction::I64Load16S(_, _)
+            | Instruction::I64Load16U(_, _)
+            | Instruction::I64Load32S(_, _)
+            | Instruction::I64Load32U(_, _)
+            | Instruction::I32Store(_, _)
+            | Instruction::I64Store(_, _)
+            | Instruction::F32Store(_, _)
+            | Instruction::F64Store(_, _)
+            | Instruction::I32Store8(_, _)
+            | Instruction::I32Store16(_, _)
+            | Instruction::I64Store8(_, _)
+            | Instruction::I64Store16(_, _)
+            | Instruction::I64Store32(_, _)
+            | Instruction::CurrentMemory(_)
+            | Instruction::GrowMemory(_)
+    )
+}
+
+fn is_float(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::F32Eq
+            | Instruction::F32Ne
+            | Instruction::F32Lt
+            | Instruction::F32Gt
+            | Instruction::F32Le
+            | Instruction::F32Ge
+            | Instruction::F32Abs
+            | Instruction::F32Neg
+            | Instruction::F32Ceil
+            | Instruction::F32Floor
+            | Instruction::F32Trunc
+            | Instruction::F32Nearest
+            | Instruction::F32Sqrt
+            | Instruction::F32Add
+            | Instruction::F32Sub
+            | Instruction::F32Mul
+            | Instruction::F32Div
+            | Instruction::F32Min
+            | Instruction::F32Max
+            | Instruction::F32Copysign
+            | Instruction::I32TruncSF32
+            | Instruction::I32TruncUF32
+            | Instruction::I64TruncSF32
+            | Instruction::I64TruncUF32
+            | Instruction::F32ConvertSI32
+            | Instruction::F32ConvertUI32
+            | Instruction::F32ConvertSI64
+            | Instruction::F32ConvertUI64
+            | Instruction::F32DemoteF64
+            | Instruction::F64PromoteF32
+            | Instruction::I32ReinterpretF32
+            | Instruction::F32ReinterpretI32
+            | Instruction::F64Eq
+            | Instruction::F64Ne
+            | Instruction::F64Lt
+            | Instruction::F64Gt
+            | Instruction::F64Le
+            | Instruction::F64Ge
+            | Instruction::F64Abs
+            | Instruction::F64Neg
+            | Instruction::F64Ceil
+            | Instruction::F64Floor
+            | Instruction::F64Trunc
+            | Instruction::F64Nearest
+            | Instruction::F64Sqrt
+            | Instruction::F64Add
+            | Instruction::F64Sub
+            | Instruction::F64Mul
+            | Instruction::F64Div
+            | Instruction::F64Min
+            | Instruction::F64Max
+            | Instruction::F64Copysign
+            | Instruction::I32TruncSF64
+            | Instruction::I32TruncUF64
+            | Instruction::I64TruncSF64
+            | Instruction::I64TruncUF64
+            | Instruction::F64ConvertSI32
+            | Instruction::F64ConvertUI32
+            | Instruction::F64ConvertSI64
+            | Instruction::F64ConvertUI64
+            | Instruction::I64ReinterpretF64
+            | Instruction::F64ReinterpretI64
+            | Instruction::F32Const(_)
+            | Instruction::F32Load(_, _)
+            | Instruction::F32Store(_, _)
+            | Instruction::F64Const(_)
+            | Instruction::F64Load(_, _)
+            | Instruction::F64Store(_, _)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parity_wasm::elements::SignExtInstruction;
+
+    #[test]
+    fn names_in_text_format() {
+        assert_eq!(name(&Instruction::GetLocal(0)), "local.get");
+        assert_eq!(name(&Instruction::GrowMemory(0)), "memory.grow");
+        assert_eq!(name(&Instruction::CallIndirect(1, 0)), "call_indirect");
+        assert_eq!(name(&Instruction::F64Store(0, 24)), "f64.store");
+        assert_eq!(name(&Instruction::I32TruncSF32), "i32.trunc_f32_s");
+        assert_eq!(name(&Instruction::I32WrapI64), "i32.wrap_i64");
+        assert_eq!(name(&Instruction::F32DemoteF64), "f32.demote_f64");
+        assert_eq!(
+            name(&Instruction::SignExt(SignExtInstruction::I64Extend32S)),
+            "i64.extend32_s"
+        );
+        assert_eq!(
+            name(&Instruction::Bulk(BulkInstruction::MemoryDrop(0))),
+            "data.drop"
+        );
+    }
+
+    #[test]
+    fn all_names() {
+        let names = names();
+        // The MVP, sign-extension, bulk memory and non-trapping conversion instructions.
+        assert_eq!(names.len(), 172 + 5 + 7 + 8);
+        assert!(names.contains("memory.grow"));
+        assert!(names.contains("i64.div_u"));
+        assert!(names.contains("f64.convert_i64_u"));
+        assert!(names.contains("elem.drop"));
+        assert!(names.contains("i64.trunc_sat_f64_u"));
+        assert!(!names.contains("grow_memory"));
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(Class::from_name("i64_division"), Some(Class::I64Division));
+        assert_eq!(Class::from_name("floats"), None);
+        assert_eq!(Class::I64Division.to_string(), "i64_division");
+        assert!(Class::I64Division.contains(&Instruction::I64RemU));
+        assert!(!Class::I64Division.contains(&Instruction::I32RemU));
+        assert!(Class::Memory.contains(&Instruction::GrowMemory(0)));
+        assert!(Class::Float.contains(&Instruction::F32Const(0)));
+        assert!(!Class::Float.contains(&Instruction::I32Const(0)));
+    }
+}
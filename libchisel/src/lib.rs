@@ -1,22 +1,33 @@
 pub use parity_wasm::elements::Module;
 
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::{error, fmt};
 
+pub mod batch;
 pub mod imports;
 
 #[cfg(feature = "binaryen")]
 pub mod binaryenopt;
+pub mod canonicalizenan;
 pub mod checkfloat;
+pub mod checkinstructions;
+pub mod checklimits;
+pub mod checksize;
 pub mod checkstartfunc;
 pub mod deployer;
+pub mod diff;
 pub mod dropsection;
+pub mod instructions;
+pub mod proposals;
 pub mod remapimports;
 pub mod remapstart;
 pub mod repack;
 pub mod snip;
+pub mod softfloat;
 pub mod trimexports;
 pub mod trimstartfunc;
+pub mod validate;
 pub mod verifyexports;
 pub mod verifyimports;
 
@@ -29,13 +40,42 @@ pub enum ModuleKind {
     Validator,
 }
 
-#[derive(Eq, PartialEq, Debug, Clone)]
+/// The underlying error of a ModuleError. It is shared, so that errors can be cloned.
+pub type Source = Arc<dyn error::Error + Send + Sync>;
+
+#[derive(Debug, Clone)]
 pub enum ModuleError {
     NotSupported,
     NotFound,
+    /// The module could not be deserialized.
+    Parse(Source),
+    /// The module could not be serialized.
+    Serialization(Source),
+    /// A configuration option is invalid or missing. Left-hand is the option, right-hand is the
+    /// reason.
+    InvalidConfig(String, String),
+    /// A section required by the module is missing, by name.
+    MissingSection(String),
+    /// An index is out of range. Left-hand is the index space, e.g. "function".
+    IndexOutOfRange(&'static str, u32),
+    /// An external tool, such as walrus or binaryen, failed.
+    External(&'static str, Source),
+    /// An error at a location in the module.
+    Located(Location, Box<ModuleError>),
     Custom(String),
 }
 
+/// The location of an error in a module.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Location {
+    /// A function, by its index in the function index space.
+    Function(u32),
+    /// A section, by name.
+    Section(String),
+    /// An offset in the serialized module.
+    Offset(usize),
+}
+
 /// Utility interface for chisel modules.
 pub trait ChiselModule<'a> {
     type ObjectReference: ?Sized;
@@ -69,6 +109,16 @@ pub trait ModuleTranslator {
 
     /// Translates module in-place. Returns true if the module was modified. Can fail with ModuleError::NotSupported.
     fn translate_inplace(&self, module: &mut Module) -> Result<bool, ModuleError>;
+
+    /// Translates module in-place. Returns the changes made to it, such as remapped imports,
+    /// trimmed exports or dropped sections, or none if the module was not modified. Translators
+    /// which do not list their changes return an empty summary.
+    fn translate_with_changes(
+        &self,
+        module: &mut Module,
+    ) -> Result<Option<diff::Changes>, ModuleError> {
+        Ok(self.translate_inplace(module)?.then(diff::Changes::default))
+    }
 }
 
 pub trait ModuleValidator {
@@ -89,46 +139,107 @@ impl From<String> for ModuleError {
     }
 }
 
+// I/O only happens when reading or writing a serialized module.
 impl From<std::io::Error> for ModuleError {
     fn from(error: std::io::Error) -> Self {
-        use std::error::Error;
-        ModuleError::Custom(error.description().to_string())
+        ModuleError::Serialization(Arc::new(error))
     }
 }
 
-// Also aliased as parity_wasm::SerializationError
+// Also aliased as parity_wasm::SerializationError. Deserialization errors are converted explicitly
+// to ModuleError::Parse.
 impl From<parity_wasm::elements::Error> for ModuleError {
-    fn from(a: parity_wasm::elements::Error) -> Self {
-        use std::error::Error;
-        ModuleError::Custom(a.description().to_string())
+    fn from(error: parity_wasm::elements::Error) -> Self {
+        ModuleError::Serialization(Arc::new(error))
+    }
+}
+
+// wasmparser only reads serialized modules.
+impl From<wasmparser::BinaryReaderError> for ModuleError {
+    fn from(error: wasmparser::BinaryReaderError) -> Self {
+        ModuleError::Parse(Arc::new(error))
     }
 }
 
+impl ModuleError {
+    /// Attaches the location of the error in the module.
+    pub fn at(self, location: Location) -> Self {
+        ModuleError::Located(location, Box::new(self))
+    }
+
+    /// Returns the innermost location of the error, if it is known.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            ModuleError::Located(location, error) => error.location().or(Some(location)),
+            _ => None,
+        }
+    }
+}
+
+// Sources are compared by their messages, as the underlying errors need not be comparable.
+impl PartialEq for ModuleError {
+    fn eq(&self, other: &Self) -> bool {
+        use ModuleError::*;
+        match (self, other) {
+            (NotSupported, NotSupported) | (NotFound, NotFound) => true,
+            (Parse(a), Parse(b)) | (Serialization(a), Serialization(b)) => {
+                a.to_string() == b.to_string()
+            }
+            (InvalidConfig(a, b), InvalidConfig(c, d)) => a == c && b == d,
+            (MissingSection(a), MissingSection(b)) => a == b,
+            (IndexOutOfRange(a, b), IndexOutOfRange(c, d)) => a == c && b == d,
+            (External(a, b), External(c, d)) => a == c && b.to_string() == d.to_string(),
+            (Located(a, b), Located(c, d)) => a == c && b == d,
+            (Custom(a), Custom(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ModuleError {}
+
+// The source of the error is not included, so that it can be printed separately.
 impl fmt::Display for ModuleError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                ModuleError::NotSupported => "Method unsupported",
-                ModuleError::NotFound => "Not found",
-                ModuleError::Custom(msg) => msg,
+        match self {
+            ModuleError::NotSupported => write!(f, "Method unsupported"),
+            ModuleError::NotFound => write!(f, "Not found"),
+            ModuleError::Parse(_) => write!(f, "Failed to parse the module"),
+            ModuleError::Serialization(_) => write!(f, "Failed to serialize the module"),
+            ModuleError::InvalidConfig(key, reason) => {
+                write!(f, "Invalid option '{}': {}", key, reason)
             }
-        )
+            ModuleError::MissingSection(name) => write!(f, "Missing {} section", name),
+            ModuleError::IndexOutOfRange(space, index) => {
+                write!(f, "{} index {} out of range", space, index)
+            }
+            ModuleError::External(tool, _) => write!(f, "{} failed", tool),
+            ModuleError::Located(location, error) => write!(f, "{}: {}", location, error),
+            ModuleError::Custom(msg) => write!(f, "{}", msg),
+        }
     }
 }
 
 impl error::Error for ModuleError {
-    fn description(&self) -> &str {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
-            ModuleError::NotSupported => "Method unsupported",
-            ModuleError::NotFound => "Not found",
-            ModuleError::Custom(msg) => msg,
+            ModuleError::Parse(source)
+            | ModuleError::Serialization(source)
+            | ModuleError::External(_, source) => Some(source.as_ref()),
+            // The located error is part of the message.
+            ModuleError::Located(_, error) => error.source(),
+            _ => None,
         }
     }
+}
 
-    fn cause(&self) -> Option<&dyn error::Error> {
-        None
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Location::Function(index) => write!(f, "function {}", index),
+            Location::Section(name) => write!(f, "{} section", name),
+            Location::Offset(offset) => write!(f, "offset {:#x}", offset),
+        }
     }
 }
 
@@ -229,18 +340,74 @@ mod tests {
 
         let fmt_result_custom = format!("{}", ModuleError::Custom("foo".to_string()));
         assert_eq!("foo", fmt_result_custom);
+
+        let fmt_result_config = format!(
+            "{}",
+            ModuleError::InvalidConfig("preset".to_string(), "unknown preset 'eth3'".to_string())
+        );
+        assert_eq!(
+            "Invalid option 'preset': unknown preset 'eth3'",
+            fmt_result_config
+        );
+
+        let fmt_result_section = format!("{}", ModuleError::MissingSection("code".to_string()));
+        assert_eq!("Missing code section", fmt_result_section);
+
+        let fmt_result_index = format!("{}", ModuleError::IndexOutOfRange("function", 7));
+        assert_eq!("function index 7 out of range", fmt_result_index);
     }
 
     #[test]
     fn error_good() {
         // Add new tests for each enum variant here as they are implemented.
         let err_unsupported = ModuleError::NotSupported;
-        let err_description_unsupported = err_unsupported.description();
-        assert_eq!("Method unsupported", err_description_unsupported);
+        assert!(err_unsupported.source().is_none());
 
         let err_custom = ModuleError::Custom("bar".to_string());
-        let err_description_custom = err_custom.description();
-        assert_eq!("bar", err_description_custom);
+        assert!(err_custom.source().is_none());
+
+        // The truncated module ends in the middle of the type section.
+        let err_parse = Module::from_bytes([0, 0x61, 0x73, 0x6d, 1, 0, 0, 0, 1, 5])
+            .map_err(|e| ModuleError::Parse(Arc::new(e)))
+            .unwrap_err();
+        assert_eq!("Failed to parse the module", err_parse.to_string());
+        assert_eq!(
+            "I/O Error: UnexpectedEof",
+            err_parse.source().unwrap().to_string()
+        );
+
+        let err_io: ModuleError = std::io::Error::from(std::io::ErrorKind::WriteZero).into();
+        assert_eq!("Failed to serialize the module", err_io.to_string());
+        assert_eq!("write zero", err_io.source().unwrap().to_string());
+    }
+
+    #[test]
+    fn located_error() {
+        let err = ModuleError::Serialization(Arc::new(parity_wasm::elements::Error::Other(
+            "invalid body",
+        )))
+        .at(Location::Function(3))
+        .at(Location::Section("code".to_string()));
+        assert_eq!(
+            "code section: function 3: Failed to serialize the module",
+            err.to_string()
+        );
+        assert_eq!(Some(&Location::Function(3)), err.location());
+        assert_eq!("invalid body", err.source().unwrap().to_string());
+        assert_eq!(None, ModuleError::NotFound.location());
+    }
+
+    #[test]
+    fn compare_sources() {
+        let err = |msg: &'static str| {
+            ModuleError::External("walrus", Arc::new(parity_wasm::elements::Error::Other(msg)))
+        };
+        assert_eq!(err("bad magic"), err("bad magic"));
+        assert_ne!(err("bad magic"), err("bad version"));
+        assert_ne!(
+            err("bad magic"),
+            ModuleError::Parse(Arc::new(parity_wasm::elements::Error::Other("bad magic")))
+        );
     }
 
     #[test]
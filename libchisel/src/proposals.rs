@@ -0,0 +1,1143 @@
+//! WebAssembly proposals beyond the MVP.
+//! parity-wasm parses the sign-extension operators, bulk memory operations and multi-value, and
+//! `detect` lists which of the proposals a module uses. Other constructs compilers emit by default
+//! cannot be represented by parity-wasm, so `from_bytes` lowers them to ones it can, and
+//! `into_bytes` restores them:
+//! - The non-trapping float-to-int conversions become calls to functions implementing them with
+//!   MVP instructions, appended to the module. The module keeps its behavior, and `conversions`
+//!   tells these functions apart.
+//! - A `call_indirect` with its table index padded, as emitted by wasm-ld when reference types are
+//!   enabled, is re-encoded.
+//! - Element segments in the encodings of bulk memory and reference types, for the first table and
+//!   listing functions, and `table.init` and `table.copy`, which parity-wasm decodes differently
+//!   from the specification, are re-encoded.
+//!
+//! Reference values, i.e. `externref` and `funcref` types, `ref.*` and `table.*` instructions,
+//! and multiple tables, as well as SIMD and threads, cannot be represented. Modules using them are
+//! rejected, naming the proposal.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+
+use parity_wasm::elements::{
+    BlockType, BulkInstruction, External, Func, FuncBody, FunctionType, ImportCountType,
+    Instruction, Instructions, Internal, Module, Serialize, Type, ValueType,
+};
+use wasmparser::{
+    BinaryReader, CodeSectionReader, ElementItems, ElementKind, ElementSectionReader,
+    ImportSectionReader, Operator, RefType, TypeRef, Validator, WasmFeatures,
+};
+
+use super::softfloat::{function_count, type_index};
+use super::{Location, ModuleError};
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone, Copy)]
+pub enum Proposal {
+    SignExtension,
+    BulkMemory,
+    MultiValue,
+    ReferenceTypes,
+    NonTrappingFloatToInt,
+    Simd,
+    Threads,
+}
+
+impl Proposal {
+    /// Returns true if modules using the proposal can be parsed. Reference types are not, although
+    /// the encodings compilers emit for the first table are accepted.
+    pub fn is_supported(self) -> bool {
+        matches!(
+            self,
+            Proposal::SignExtension
+                | Proposal::BulkMemory
+                | Proposal::MultiValue
+                | Proposal::NonTrappingFloatToInt
+        )
+    }
+}
+
+impl fmt::Display for Proposal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Proposal::SignExtension => "sign-extension operators",
+                Proposal::BulkMemory => "bulk memory operations",
+                Proposal::MultiValue => "multi-value",
+                Proposal::ReferenceTypes => "reference types",
+                Proposal::NonTrappingFloatToInt => "non-trapping float-to-int conversions",
+                Proposal::Simd => "SIMD",
+                Proposal::Threads => "threads",
+            }
+        )
+    }
+}
+
+/// A construct of a proposal which cannot be represented, such as an instruction.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Unsupported {
+    pub proposal: Proposal,
+    pub construct: Option<String>,
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.construct {
+            Some(construct) => write!(
+                f,
+                "{} of the {} proposal is not supported",
+                construct, self.proposal
+            ),
+            None => write!(f, "the {} proposal is not supported", self.proposal),
+        }
+    }
+}
+
+impl error::Error for Unsupported {}
+
+/// The non-trapping float-to-int conversions, in the order of their opcodes after the 0xfc
+/// prefix, with the float type they convert and the integer type they produce.
+pub(crate) const CONVERSIONS: [(&str, ValueType, ValueType); 8] = [
+    ("i32.trunc_sat_f32_s", ValueType::F32, ValueType::I32),
+    ("i32.trunc_sat_f32_u", ValueType::F32, ValueType::I32),
+    ("i32.trunc_sat_f64_s", ValueType::F64, ValueType::I32),
+    ("i32.trunc_sat_f64_u", ValueType::F64, ValueType::I32),
+    ("i64.trunc_sat_f32_s", ValueType::F32, ValueType::I64),
+    ("i64.trunc_sat_f32_u", ValueType::F32, ValueType::I64),
+    ("i64.trunc_sat_f64_s", ValueType::F64, ValueType::I64),
+    ("i64.trunc_sat_f64_u", ValueType::F64, ValueType::I64),
+];
+
+const SECTION_IMPORT: u8 = 2;
+const SECTION_ELEMENT: u8 = 9;
+const SECTION_CODE: u8 = 10;
+
+const NOP: u8 = 0x01;
+const CALL_INDIRECT: u8 = 0x11;
+const PREFIX: u8 = 0xfc;
+const TABLE_INIT: u8 = 0x0c;
+const TABLE_COPY: u8 = 0x0e;
+
+/// A lowered conversion: the index of the function body in the code section, the index of the
+/// instruction in the body, and the opcode of the conversion.
+type Site = (usize, usize, u8);
+
+/// Returns the proposals used by the module.
+pub fn detect(module: &Module) -> BTreeSet<Proposal> {
+    let mut proposals = BTreeSet::new();
+
+    if let Some(types) = module.type_section() {
+        if types.types().iter().any(|ty| match ty {
+            Type::Function(ty) => ty.results().len() > 1,
+        }) {
+            proposals.insert(Proposal::MultiValue);
+        }
+    }
+
+    if let Some(data) = module.data_section() {
+        if data.entries().iter().any(|segment| segment.passive()) {
+            proposals.insert(Proposal::BulkMemory);
+        }
+    }
+    if let Some(elements) = module.elements_section() {
+        if elements.entries().iter().any(|segment| segment.passive()) {
+            proposals.insert(Proposal::BulkMemory);
+        }
+    }
+
+    let conversions = conversions(module);
+    if let Some(code) = module.code_section() {
+        for body in code.bodies() {
+            for instruction in body.code().elements() {
+                match instruction {
+                    Instruction::SignExt(_) => {
+                        proposals.insert(Proposal::SignExtension);
+                    }
+                    Instruction::Bulk(_) => {
+                        proposals.insert(Proposal::BulkMemory);
+                    }
+                    Instruction::Block(BlockType::TypeIndex(_))
+                    | Instruction::Loop(BlockType::TypeIndex(_))
+                    | Instruction::If(BlockType::TypeIndex(_)) => {
+                        proposals.insert(Proposal::MultiValue);
+                    }
+                    Instruction::Call(idx) if conversions.contains_key(idx) => {
+                        proposals.insert(Proposal::NonTrappingFloatToInt);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    proposals
+}
+
+/// Returns the functions implementing a non-trapping float-to-int conversion, by function index,
+/// along with the name of the conversion, e.g. "i32.trunc_sat_f32_s".
+pub fn conversions(module: &Module) -> HashMap<u32, &'static str> {
+    stand_ins(module)
+        .into_iter()
+        .map(|(idx, opcode)| (idx, CONVERSIONS[opcode as usize].0))
+        .collect()
+}
+
+/// Deserializes a module, lowering the constructs parity-wasm cannot represent.
+pub fn from_bytes(bytes: &[u8]) -> Result<Module, ModuleError> {
+    let mut sites = vec![];
+    let mut imported = 0;
+    let lowered = rewrite_sections(bytes, |id, content, offset| match id {
+        SECTION_IMPORT => {
+            imported = ImportSectionReader::new(content, offset)?
+                .into_iter()
+                .filter(
+                    |import| matches!(import, Ok(import) if matches!(import.ty, TypeRef::Func(_))),
+                )
+                .count() as u32;
+            Ok(None)
+        }
+        SECTION_ELEMENT => lower_elements(content, offset).map(Some),
+        SECTION_CODE => lower_code(content, offset, imported, &mut sites).map(Some),
+        _ => Ok(None),
+    })?;
+
+    let mut module = Module::from_bytes(&lowered).map_err(|e| match unsupported(bytes) {
+        Some(proposal) => ModuleError::Parse(Arc::new(Unsupported {
+            proposal,
+            construct: None,
+        })),
+        None => ModuleError::Parse(Arc::new(e)),
+    })?;
+    call_conversions(&mut module, &sites);
+    Ok(module)
+}
+
+/// Serializes a module, restoring the constructs lowered by `from_bytes`.
+pub fn into_bytes(mut module: Module) -> Result<Vec<u8>, ModuleError> {
+    let stand_ins = stand_ins(&module);
+    let mut sites = vec![];
+    if let Some(section) = module.code_section() {
+        for (body_idx, body) in section.bodies().iter().enumerate() {
+            for (idx, instruction) in body.code().elements().iter().enumerate() {
+                if let Instruction::Call(callee) = instruction {
+                    if let Some(opcode) = stand_ins.get(callee) {
+                        sites.push((body_idx, idx, *opcode));
+                    }
+                }
+            }
+        }
+    }
+    remove_stand_ins(&mut module, &stand_ins);
+
+    let code = raise_code(&module, &sites)?;
+    let elements = raise_elements(&module)?;
+    let bytes = module.into_bytes()?;
+    if code.is_none() && elements.is_none() {
+        return Ok(bytes);
+    }
+    rewrite_sections(&bytes, |id, _, _| match id {
+        SECTION_ELEMENT => Ok(elements.clone()),
+        SECTION_CODE => Ok(code.clone()),
+        _ => Ok(None),
+    })
+}
+
+/// Returns the first proposal used by the serialized module which cannot be parsed, if any.
+///
+/// parity-wasm reports unknown opcodes without their prefix, so its errors cannot tell e.g. a SIMD
+/// instruction apart from an exception handling instruction. Instead, the module is validated by
+/// wasmparser with every proposal enabled except one: if it only fails then, it uses that
+/// proposal.
+pub fn unsupported(bytes: &[u8]) -> Option<Proposal> {
+    let validates = |features: WasmFeatures| {
+        Validator::new_with_features(features)
+            .validate_all(bytes)
+            .is_ok()
+    };
+
+    if !validates(WasmFeatures::all()) {
+        return None;
+    }
+    [Proposal::ReferenceTypes, Proposal::Simd, Proposal::Threads]
+        .iter()
+        .copied()
+        .find(|proposal| {
+            let all = WasmFeatures::all();
+            !validates(match proposal {
+                Proposal::ReferenceTypes => WasmFeatures {
+                    reference_types: false,
+                    function_references: false,
+                    gc: false,
+                    ..all
+                },
+                Proposal::Simd => WasmFeatures {
+                    simd: false,
+                    relaxed_simd: false,
+                    ..all
+                },
+                _ => WasmFeatures {
+                    threads: false,
+                    ..all
+                },
+            })
+        })
+}
+
+/// Calls `rewrite` with the id, contents and offset of every section of the serialized module,
+/// and replaces the contents of the section by those it returns, if any.
+fn rewrite_sections(
+    bytes: &[u8],
+    mut rewrite: impl FnMut(u8, &[u8], usize) -> Result<Option<Vec<u8>>, ModuleError>,
+) -> Result<Vec<u8>, ModuleError> {
+    let mut reader = BinaryReader::new(bytes);
+    // The magic number and version.
+    let mut out = reader.read_bytes(8)?.to_vec();
+    while !reader.eof() {
+        let start = reader.original_position();
+        let id = reader.read_u8()?;
+        let size = reader.read_var_u32()? as usize;
+        let offset = reader.original_position();
+        let content = reader.read_bytes(size)?;
+        match rewrite(id, content, offset)? {
+            Some(content) => {
+                out.push(id);
+                write_u32(&mut out, content.len() as u32);
+                out.extend(content);
+            }
+            None => out.extend_from_slice(&bytes[start..offset + size]),
+        }
+    }
+    Ok(out)
+}
+
+fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn unsupported_construct(proposal: Proposal, construct: &str) -> ModuleError {
+    ModuleError::Parse(Arc::new(Unsupported {
+        proposal,
+        construct: Some(construct.to_string()),
+    }))
+}
+
+/// Re-encodes the function bodies of the code section, replacing the non-trapping conversions by
+/// a `nop` and recording where they were.
+fn lower_code(
+    content: &[u8],
+    offset: usize,
+    imported: u32,
+    sites: &mut Vec<Site>,
+) -> Result<Vec<u8>, ModuleError> {
+    let section = CodeSectionReader::new(content, offset)?;
+    let mut out = vec![];
+    write_u32(&mut out, section.count());
+    for (body_idx, body) in section.into_iter().enumerate() {
+        let body = body?;
+        let mut operators = body.get_operators_reader()?;
+        // The locals are kept as they are.
+        let mut code =
+            content[body.range().start - offset..operators.original_position() - offset].to_vec();
+        let mut idx = 0;
+        while !operators.eof() {
+            let start = operators.original_position();
+            let operator = operators.read()?;
+            match operator {
+                Operator::CallIndirect {
+                    type_index,
+                    table_index: 0,
+                    ..
+                } => {
+                    code.push(CALL_INDIRECT);
+                    write_u32(&mut code, type_index);
+                    code.push(0);
+                }
+                // parity-wasm reads a reserved byte before the segment index.
+                Operator::TableInit {
+                    elem_index,
+                    table: 0,
+                } => {
+                    code.extend([PREFIX, TABLE_INIT, 0]);
+                    write_u32(&mut code, elem_index);
+                }
+                // parity-wasm reads a single table index.
+                Operator::TableCopy {
+                    dst_table: 0,
+                    src_table: 0,
+                } => code.extend([PREFIX, TABLE_COPY, 0]),
+                Operator::CallIndirect { .. }
+                | Operator::TableInit { .. }
+                | Operator::TableCopy { .. } => {
+                    return Err(unsupported_construct(
+                        Proposal::ReferenceTypes,
+                        "a table other than the first",
+                    )
+                    .at(Location::Function(imported + body_idx as u32)))
+                }
+                operator => match conversion_opcode(&operator) {
+                    Some(opcode) => {
+                        sites.push((body_idx, idx, opcode));
+                        code.push(NOP);
+                    }
+                    None => {
+                        if let Some(proposal) = unsupported_operator(&operator) {
+                            return Err(unsupported_construct(proposal, &operator_name(&operator))
+                                .at(Location::Function(imported + body_idx as u32)));
+                        }
+                        code.extend_from_slice(
+                            &content[start - offset..operators.original_position() - offset],
+                        );
+                    }
+                },
+            }
+            idx += 1;
+        }
+        write_u32(&mut out, code.len() as u32);
+        out.extend(code);
+    }
+    Ok(out)
+}
+
+/// Re-encodes the element segments in the encodings parity-wasm reads: active segments of the
+/// first table and passive segments, both listing function indices.
+fn lower_elements(content: &[u8], offset: usize) -> Result<Vec<u8>, ModuleError> {
+    let section = ElementSectionReader::new(content, offset)?;
+    let mut out = vec![];
+    write_u32(&mut out, section.count());
+    for element in section {
+        let element = element?;
+        let functions = match element.items {
+            ElementItems::Functions(functions) => {
+                functions.into_iter().collect::<Result<Vec<u32>, _>>()?
+            }
+            ElementItems::Expressions(RefType::FUNCREF, expressions) => expressions
+                .into_iter()
+                .map(|expression| {
+                    let mut operators = expression?.get_operators_reader();
+                    match (operators.read()?, operators.read()?) {
+                        (Operator::RefFunc { function_index }, Operator::End) => Ok(function_index),
+                        _ => Err(unsupported_construct(
+                            Proposal::ReferenceTypes,
+                            "an element segment with null references",
+                        )),
+                    }
+                })
+                .collect::<Result<Vec<u32>, ModuleError>>()?,
+            ElementItems::Expressions(..) => {
+                return Err(unsupported_construct(
+                    Proposal::ReferenceTypes,
+                    "an element segment of externref",
+                ))
+            }
+        };
+        match element.kind {
+            // parity-wasm reads no element kind.
+            ElementKind::Passive => out.push(1),
+            ElementKind::Active {
+                table_index: None | Some(0),
+                offset_expr,
+            } => {
+                out.push(0);
+                let range = offset_expr.get_binary_reader().range();
+                out.extend_from_slice(&content[range.start - offset..range.end - offset]);
+            }
+            ElementKind::Active { .. } => {
+                return Err(unsupported_construct(
+                    Proposal::ReferenceTypes,
+                    "an element segment of a table other than the first",
+                ))
+            }
+            ElementKind::Declared => {
+                return Err(unsupported_construct(
+                    Proposal::ReferenceTypes,
+                    "a declarative element segment",
+                ))
+            }
+        }
+        write_u32(&mut out, functions.len() as u32);
+        for function in functions {
+            write_u32(&mut out, function);
+        }
+    }
+    Ok(out)
+}
+
+/// Appends a function for each conversion used, and replaces the `nop` at each site by a call to
+/// it.
+fn call_conversions(module: &mut Module, sites: &[Site]) {
+    let opcodes: BTreeSet<u8> = sites.iter().map(|(_, _, opcode)| *opcode).collect();
+    let base = function_count(module);
+    let mut indices = HashMap::new();
+    for (offset, opcode) in opcodes.into_iter().enumerate() {
+        let (_, from, to) = CONVERSIONS[opcode as usize];
+        let type_ref = type_index(module, &FunctionType::new(vec![from], vec![to]));
+        // Sites are only found in the code section, which comes with a function section.
+        module
+            .function_section_mut()
+            .expect("the function section exists")
+            .entries_mut()
+            .push(Func::new(type_ref));
+        module
+            .code_section_mut()
+            .expect("the code section exists")
+            .bodies_mut()
+            .push(FuncBody::new(
+                vec![],
+                Instructions::new(conversion_body(opcode)),
+            ));
+        indices.insert(opcode, base + offset as u32);
+    }
+
+    if let Some(section) = module.code_section_mut() {
+        for (body_idx, idx, opcode) in sites {
+            let instruction = &mut section.bodies_mut()[*body_idx].code_mut().elements_mut()[*idx];
+            debug_assert_eq!(*instruction, Instruction::Nop);
+            *instruction = Instruction::Call(indices[opcode]);
+        }
+    }
+}
+
+/// Returns the body of a function implementing the conversion with MVP instructions: NaN becomes
+/// zero, values out of range become the nearest bound, and others are truncated.
+fn conversion_body(opcode: u8) -> Vec<Instruction> {
+    use Instruction::*;
+
+    let (name, from, to) = CONVERSIONS[opcode as usize];
+    let signed = name.ends_with("_s");
+    let bits = if to == ValueType::I32 { 32 } else { 64 };
+    // The bounds as floats: values below the lower one become the minimum, and values at or above
+    // the upper one become the maximum. Both are powers of two, so they are exact.
+    let (lower, upper) = if signed {
+        (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1))
+    } else {
+        (0.0, 2f64.powi(bits))
+    };
+    let float = |value: f64| match from {
+        ValueType::F32 => F32Const((value as f32).to_bits()),
+        _ => F64Const(value.to_bits()),
+    };
+    let (ne, ge, lt) = match from {
+        ValueType::F32 => (F32Ne, F32Ge, F32Lt),
+        _ => (F64Ne, F64Ge, F64Lt),
+    };
+    let (zero, min, max) = match (to, signed) {
+        (ValueType::I32, true) => (I32Const(0), I32Const(i32::MIN), I32Const(i32::MAX)),
+        (ValueType::I32, false) => (I32Const(0), I32Const(0), I32Const(-1)),
+        (_, true) => (I64Const(0), I64Const(i64::MIN), I64Const(i64::MAX)),
+        (_, false) => (I64Const(0), I64Const(0), I64Const(-1)),
+    };
+    let trunc = match opcode {
+        0 => I32TruncSF32,
+        1 => I32TruncUF32,
+        2 => I32TruncSF64,
+        3 => I32TruncUF64,
+        4 => I64TruncSF32,
+        5 => I64TruncUF32,
+        6 => I64TruncSF64,
+        _ => I64TruncUF64,
+    };
+    let result = BlockType::Value(to);
+    vec![
+        GetLocal(0),
+        GetLocal(0),
+        ne,
+        If(result),
+        zero,
+        Else,
+        GetLocal(0),
+        float(upper),
+        ge,
+        If(result),
+        max,
+        Else,
+        GetLocal(0),
+        float(lower),
+        lt,
+        If(result),
+        min,
+        Else,
+        GetLocal(0),
+        trunc,
+        End,
+        End,
+        End,
+        End,
+    ]
+}
+
+/// Returns the functions implementing a conversion, by function index, along with its opcode.
+fn stand_ins(module: &Module) -> HashMap<u32, u8> {
+    let (functions, bodies) = match (module.function_section(), module.code_section()) {
+        (Some(functions), Some(code)) => (functions.entries(), code.bodies()),
+        _ => return HashMap::new(),
+    };
+    let types = module
+        .type_section()
+        .map_or(&[][..], |section| section.types());
+    let imported = module.import_count(ImportCountType::Function) as u32;
+    functions
+        .iter()
+        .zip(bodies)
+        .enumerate()
+        .filter(|(_, (_, body))| body.locals().is_empty())
+        .filter_map(|(idx, (func, body))| {
+            let Type::Function(ty) = types.get(func.type_ref() as usize)?;
+            (0..CONVERSIONS.len() as u8)
+                .find(|opcode| {
+                    let (_, from, to) = CONVERSIONS[*opcode as usize];
+                    ty.params() == [from]
+                        && ty.results() == [to]
+                        && body.code().elements() == conversion_body(*opcode).as_slice()
+                })
+                .map(|opcode| (imported + idx as u32, opcode))
+        })
+        .collect()
+}
+
+/// Removes the functions implementing a conversion at the end of the module which are only
+/// called, along with the types added for them.
+fn remove_stand_ins(module: &mut Module, stand_ins: &HashMap<u32, u8>) {
+    let imported = module.import_count(ImportCountType::Function) as u32;
+    let mut referenced: HashSet<u32> = module.start_section().into_iter().collect();
+    if let Some(section) = module.export_section() {
+        referenced.extend(
+            section
+                .entries()
+                .iter()
+                .filter_map(|entry| match entry.internal() {
+                    Internal::Function(idx) => Some(*idx),
+                    _ => None,
+                }),
+        );
+    }
+    if let Some(section) = module.elements_section() {
+        for segment in section.entries() {
+            referenced.extend(segment.members());
+        }
+    }
+
+    let mut count = function_count(module) - imported;
+    while count > 0 {
+        let idx = imported + count - 1;
+        if !stand_ins.contains_key(&idx) || referenced.contains(&idx) {
+            break;
+        }
+        count -= 1;
+    }
+    if let Some(section) = module.function_section_mut() {
+        section.entries_mut().truncate(count as usize);
+    }
+    if let Some(section) = module.code_section_mut() {
+        section.bodies_mut().truncate(count as usize);
+    }
+
+    let used_types: HashSet<u32> = used_types(module);
+    if let Some(section) = module.type_section_mut() {
+        let types = section.types_mut();
+        while let Some(Type::Function(ty)) = types.last() {
+            let is_conversion = CONVERSIONS
+                .iter()
+                .any(|(_, from, to)| ty.params() == [*from] && ty.results() == [*to]);
+            if !is_conversion || used_types.contains(&(types.len() as u32 - 1)) {
+                break;
+            }
+            types.pop();
+        }
+    }
+}
+
+/// Returns the indices of the types referred to by imports, functions and instructions.
+fn used_types(module: &Module) -> HashSet<u32> {
+    let mut used = HashSet::new();
+    if let Some(section) = module.import_section() {
+        used.extend(
+            section
+                .entries()
+                .iter()
+                .filter_map(|entry| match entry.external() {
+                    External::Function(idx) => Some(*idx),
+                    _ => None,
+                }),
+        );
+    }
+    if let Some(section) = module.function_section() {
+        used.extend(section.entries().iter().map(|func| func.type_ref()));
+    }
+    if let Some(section) = module.code_section() {
+        for body in section.bodies() {
+            used.extend(body.code().elements().iter().filter_map(
+                |instruction| match instruction {
+                    Instruction::CallIndirect(idx, _)
+                    | Instruction::Block(BlockType::TypeIndex(idx))
+                    | Instruction::Loop(BlockType::TypeIndex(idx))
+                    | Instruction::If(BlockType::TypeIndex(idx)) => Some(*idx),
+                    _ => None,
+                },
+            ));
+        }
+    }
+    used
+}
+
+/// Encodes the code section with the conversions at the sites, and `table.init` and `table.copy`
+/// as specified. Returns None if the encoding of parity-wasm can be kept.
+fn raise_code(module: &Module, sites: &[Site]) -> Result<Option<Vec<u8>>, ModuleError> {
+    let bodies = match module.code_section() {
+        Some(section) => section.bodies(),
+        None => return Ok(None),
+    };
+    let is_table_instruction = |instruction: &Instruction| {
+        matches!(
+            instruction,
+            Instruction::Bulk(BulkInstruction::TableInit(_))
+                | Instruction::Bulk(BulkInstruction::TableCopy)
+        )
+    };
+    if sites.is_empty()
+        && !bodies
+            .iter()
+            .any(|body| body.code().elements().iter().any(is_table_instruction))
+    {
+        return Ok(None);
+    }
+
+    let sites: HashMap<(usize, usize), u8> = sites
+        .iter()
+        .map(|(body_idx, idx, opcode)| ((*body_idx, *idx), *opcode))
+        .collect();
+    let mut out = vec![];
+    write_u32(&mut out, bodies.len() as u32);
+    for (body_idx, body) in bodies.iter().enumerate() {
+        let mut code = vec![];
+        write_u32(&mut code, body.locals().len() as u32);
+        for local in body.locals() {
+            (*local).serialize(&mut code)?;
+        }
+        for (idx, instruction) in body.code().elements().iter().enumerate() {
+            match (sites.get(&(body_idx, idx)), instruction) {
+                (Some(opcode), _) => code.extend([PREFIX, *opcode]),
+                (None, Instruction::Bulk(BulkInstruction::TableInit(segment))) => {
+                    code.extend([PREFIX, TABLE_INIT]);
+                    write_u32(&mut code, *segment);
+                    code.push(0);
+                }
+                (None, Instruction::Bulk(BulkInstruction::TableCopy)) => {
+                    code.extend([PREFIX, TABLE_COPY, 0, 0])
+                }
+                (None, instruction) => instruction.clone().serialize(&mut code)?,
+            }
+        }
+        write_u32(&mut out, code.len() as u32);
+        out.extend(code);
+    }
+    Ok(Some(out))
+}
+
+/// Encodes the element section with the element kind of passive segments and segments of other
+/// tables. Returns None if the encoding of parity-wasm can be kept.
+fn raise_elements(module: &Module) -> Result<Option<Vec<u8>>, ModuleError> {
+    let segments = match module.elements_section() {
+        Some(section) => section.entries(),
+        None => return Ok(None),
+    };
+    if !segments
+        .iter()
+        .any(|segment| segment.passive() || segment.index() != 0)
+    {
+        return Ok(None);
+    }
+
+    let mut out = vec![];
+    write_u32(&mut out, segments.len() as u32);
+    for segment in segments {
+        match segment.offset() {
+            Some(offset) if segment.index() == 0 => {
+                out.push(0);
+                offset.clone().serialize(&mut out)?;
+            }
+            Some(offset) => {
+                out.push(2);
+                write_u32(&mut out, segment.index());
+                offset.clone().serialize(&mut out)?;
+                // The element kind, funcref.
+                out.push(0);
+            }
+            None => out.extend([1, 0]),
+        }
+        write_u32(&mut out, segment.members().len() as u32);
+        for member in segment.members() {
+            write_u32(&mut out, *member);
+        }
+    }
+    Ok(Some(out))
+}
+
+fn conversion_opcode(operator: &Operator) -> Option<u8> {
+    Some(match operator {
+        Operator::I32TruncSatF32S => 0,
+        Operator::I32TruncSatF32U => 1,
+        Operator::I32TruncSatF64S => 2,
+        Operator::I32TruncSatF64U => 3,
+        Operator::I64TruncSatF32S => 4,
+        Operator::I64TruncSatF32U => 5,
+        Operator::I64TruncSatF64S => 6,
+        Operator::I64TruncSatF64U => 7,
+        _ => return None,
+    })
+}
+
+macro_rules! define_operator_proposal {
+    ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident)*) => {
+        /// Returns the proposal which introduced the operator, as named by wasmparser, e.g.
+        /// "sign_extension", and the name of its visitor, e.g. "visit_i32_extend8_s".
+        fn operator_proposal(operator: &Operator) -> (&'static str, &'static str) {
+            match operator {
+                $(Operator::$op { .. } => (stringify!($proposal), stringify!($visit)),)*
+            }
+        }
+    };
+}
+
+wasmparser::for_each_operator!(define_operator_proposal);
+
+/// Returns the proposal of the operator if it cannot be represented. Operators of other
+/// proposals, such as exception handling, are left for parity-wasm to reject.
+fn unsupported_operator(operator: &Operator) -> Option<Proposal> {
+    match operator_proposal(operator).0 {
+        "reference_types" | "function_references" | "gc" => Some(Proposal::ReferenceTypes),
+        "simd" | "relaxed_simd" => Some(Proposal::Simd),
+        "threads" => Some(Proposal::Threads),
+        _ => None,
+    }
+}
+
+/// Returns the name of the operator in the text format, e.g. "ref.func".
+fn operator_name(operator: &Operator) -> String {
+    let name = operator_proposal(operator).1.trim_start_matches("visit_");
+    match name.split_once('_') {
+        Some(("typed", _)) => "select".to_string(),
+        Some((prefix, rest)) => format!("{}.{}", prefix, rest),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hex::FromHex;
+
+    use super::*;
+    use crate::checkfloat::CheckFloat;
+    use crate::remapstart::RemapStart;
+    use crate::verifyimports::VerifyImports;
+    use crate::{ChiselModule, ModulePreset, ModuleTranslator, ModuleValidator};
+
+    #[test]
+    fn mvp_module() {
+        let module = Module::default();
+        assert!(detect(&module).is_empty());
+    }
+
+    #[test]
+    fn post_mvp_module() {
+        // (module
+        //   (import "ethereum" "useGas" (func (param i64)))
+        //   (memory (export "memory") 1)
+        //   (data "abc")
+        //   (func $pair (result i32 i32) i32.const 1 i32.const 2)
+        //   (func (export "main")
+        //     i32.const 0 i32.extend8_s drop
+        //     i32.const 0 i32.const 0 i32.const 3 memory.init 0
+        //     data.drop 0
+        //     call $pair drop drop
+        //     block (result i32 i32) i32.const 1 i32.const 2 end drop drop))
+        let input: Vec<u8> = FromHex::from_hex(
+            "0061736d01000000010d0360017e006000027f7f60000002130108657468657265756d0675736547
+6173000003030201020503010001071102066d656d6f72790200046d61696e00020c01010a2902060041014102
+0b20004100c01a410041004103fc080000fc090010011a1a0201410141020b1a1a0b0b06010103616263",
+        )
+        .unwrap();
+        let module = Module::from_bytes(&input).unwrap();
+        assert_eq!(
+            detect(&module).into_iter().collect::<Vec<_>>(),
+            vec![
+                Proposal::SignExtension,
+                Proposal::BulkMemory,
+                Proposal::MultiValue
+            ]
+        );
+        assert!(detect(&module).iter().all(|p| p.is_supported()));
+
+        let checkfloat = CheckFloat::with_defaults().unwrap();
+        assert_eq!(checkfloat.validate(&module), Ok(true));
+        let verifyimports = VerifyImports::with_preset("ewasm").unwrap();
+        assert_eq!(verifyimports.validate(&module), Ok(true));
+        let remapstart = RemapStart::with_defaults().unwrap();
+        assert_eq!(remapstart.translate(&module), Ok(None));
+
+        assert_eq!(module.into_bytes().unwrap(), input);
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let input = wat::parse_str(
+            r#"(module
+                 (func (export "i32_s") (param f32) (result i32)
+                   local.get 0 i32.trunc_sat_f32_s)
+                 (func (param f64) (result i64)
+                   local.get 0 i64.trunc_sat_f64_u
+                   local.get 0 i64.trunc_sat_f64_u
+                   i64.add))"#,
+        )
+        .unwrap();
+        assert!(Module::from_bytes(&input).is_err());
+
+        let module = from_bytes(&input).unwrap();
+        assert_eq!(
+            detect(&module).into_iter().collect::<Vec<_>>(),
+            vec![Proposal::NonTrappingFloatToInt]
+        );
+        // The conversions are appended after the functions of the module, so their indices stay
+        // the same.
+        let mut conversions: Vec<(u32, &str)> = conversions(&module).into_iter().collect();
+        conversions.sort();
+        assert_eq!(
+            conversions,
+            vec![(2, "i32.trunc_sat_f32_s"), (3, "i64.trunc_sat_f64_u")]
+        );
+        let body = &module.code_section().unwrap().bodies()[1];
+        assert_eq!(body.code().elements()[1], Instruction::Call(3));
+        assert_eq!(body.code().elements()[3], Instruction::Call(3));
+
+        assert_eq!(into_bytes(module).unwrap(), input);
+    }
+
+    #[test]
+    fn conversions_saturate() {
+        let input = wat::parse_str(
+            r#"(module
+                 (func (export "i32_f32_s") (param f32) (result i32) local.get 0 i32.trunc_sat_f32_s)
+                 (func (export "i32_f32_u") (param f32) (result i32) local.get 0 i32.trunc_sat_f32_u)
+                 (func (export "i32_f64_s") (param f64) (result i32) local.get 0 i32.trunc_sat_f64_s)
+                 (func (export "i32_f64_u") (param f64) (result i32) local.get 0 i32.trunc_sat_f64_u)
+                 (func (export "i64_f32_s") (param f32) (result i64) local.get 0 i64.trunc_sat_f32_s)
+                 (func (export "i64_f32_u") (param f32) (result i64) local.get 0 i64.trunc_sat_f32_u)
+                 (func (export "i64_f64_s") (param f64) (result i64) local.get 0 i64.trunc_sat_f64_s)
+                 (func (export "i64_f64_u") (param f64) (result i64) local.get 0 i64.trunc_sat_f64_u))"#,
+        )
+        .unwrap();
+        // The lowered module only uses MVP instructions, and behaves like the conversions.
+        let lowered = from_bytes(&input).unwrap().into_bytes().unwrap();
+        let features = WasmFeatures {
+            saturating_float_to_int: false,
+            ..Default::default()
+        };
+        Validator::new_with_features(features)
+            .validate_all(&lowered)
+            .unwrap();
+
+        let engine = wasmi::Engine::default();
+        let module = wasmi::Module::new(&engine, &lowered[..]).unwrap();
+        let mut store = wasmi::Store::new(&engine, ());
+        let instance = wasmi::Linker::new(&engine)
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .unwrap();
+        let mut call = |name: &str, value: f64| -> i64 {
+            let func = instance.get_func(&store, name).unwrap();
+            let param = if name.contains("f32") {
+                wasmi::Value::F32((value as f32).into())
+            } else {
+                wasmi::Value::F64(value.into())
+            };
+            let mut results = [wasmi::Value::I64(0)];
+            if name.starts_with("i32") {
+                results[0] = wasmi::Value::I32(0);
+            }
+            func.call(&mut store, &[param], &mut results).unwrap();
+            match results[0] {
+                wasmi::Value::I32(result) => result as i64,
+                wasmi::Value::I64(result) => result,
+                _ => unreachable!(),
+            }
+        };
+
+        // Rust casts from floats to integers saturate like the conversions.
+        let values = [
+            f64::NAN,
+            -f64::NAN,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            0.0,
+            -0.0,
+            -0.5,
+            -1.0,
+            1.5,
+            -1.5,
+            2147483647.0,
+            2147483648.0,
+            -2147483648.0,
+            -2147483648.5,
+            -2147483649.0,
+            4294967295.0,
+            4294967296.0,
+            9223372036854775807.0,
+            -9223372036854775808.0,
+            18446744073709551615.0,
+            1e30,
+            -1e30,
+        ];
+        for value in values.iter().copied() {
+            let single = value as f32;
+            assert_eq!(call("i32_f32_s", value), single as i32 as i64, "{}", value);
+            assert_eq!(
+                call("i32_f32_u", value),
+                single as u32 as i32 as i64,
+                "{}",
+                value
+            );
+            assert_eq!(call("i32_f64_s", value), value as i32 as i64, "{}", value);
+            assert_eq!(
+                call("i32_f64_u", value),
+                value as u32 as i32 as i64,
+                "{}",
+                value
+            );
+            assert_eq!(call("i64_f32_s", value), single as i64, "{}", value);
+            assert_eq!(call("i64_f32_u", value), single as u64 as i64, "{}", value);
+            assert_eq!(call("i64_f64_s", value), value as i64, "{}", value);
+            assert_eq!(call("i64_f64_u", value), value as u64 as i64, "{}", value);
+        }
+    }
+
+    #[test]
+    fn table_encodings() {
+        // A call_indirect with its table index padded to two bytes, as emitted by wasm-ld when
+        // reference types are enabled.
+        // (module (type (func)) (table 1 funcref) (func i32.const 0 call_indirect (type 0)))
+        let input = FromHex::from_hex(
+            "0061736d01000000010401600000030201000404017000010a0a0108004100110080000b",
+        )
+        .unwrap();
+        assert!(Module::from_bytes(&input).is_err());
+        let module = from_bytes(&input).unwrap();
+        assert!(detect(&module).is_empty());
+        let output = into_bytes(module).unwrap();
+        assert_eq!(output.len(), input.len() - 1);
+        Validator::new().validate_all(&output).unwrap();
+
+        // Passive segments, table.init and table.copy are restored as they were.
+        let input = wat::parse_str(
+            r#"(module
+                 (table 2 funcref)
+                 (func $f)
+                 (elem $passive func $f)
+                 (elem (i32.const 1) func $f)
+                 (func
+                   (table.init $passive (i32.const 0) (i32.const 0) (i32.const 1))
+                   (table.copy (i32.const 1) (i32.const 0) (i32.const 1))
+                   elem.drop $passive))"#,
+        )
+        .unwrap();
+        let module = from_bytes(&input).unwrap();
+        let body = &module.code_section().unwrap().bodies()[1];
+        assert_eq!(
+            body.code().elements()[3],
+            Instruction::Bulk(BulkInstruction::TableInit(0))
+        );
+        assert!(detect(&module).contains(&Proposal::BulkMemory));
+        assert_eq!(into_bytes(module).unwrap(), input);
+
+        // Other encodings of the segments for the first table are accepted.
+        let input = wat::parse_str(
+            r#"(module
+                 (table 2 funcref)
+                 (func $f)
+                 (elem (table 0) (i32.const 0) func $f)
+                 (elem (i32.const 1) funcref (ref.func $f)))"#,
+        )
+        .unwrap();
+        let module = from_bytes(&input).unwrap();
+        let segments = module.elements_section().unwrap().entries();
+        assert_eq!(segments[0].members(), segments[1].members());
+        Validator::new()
+            .validate_all(&into_bytes(module).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn unsupported_proposals() {
+        let error = |wat: &str| from_bytes(&wat::parse_str(wat).unwrap()).unwrap_err();
+        let source = |error: &ModuleError| error::Error::source(error).unwrap().to_string();
+
+        let err = error("(module (table 1 externref))");
+        assert_eq!(
+            source(&err),
+            "the reference types proposal is not supported"
+        );
+
+        let err =
+            error("(module (func $f) (elem declare func $f) (func (result funcref) ref.func $f))");
+        assert_eq!(err.location(), None);
+        assert_eq!(
+            source(&err),
+            "a declarative element segment of the reference types proposal is not supported"
+        );
+
+        let err = error("(module (table 1 funcref) (func (result i32) table.size 0))");
+        assert_eq!(err.location(), Some(&Location::Function(0)));
+        assert_eq!(
+            source(&err),
+            "table.size of the reference types proposal is not supported"
+        );
+
+        let err = error(
+            r#"(module
+                 (table $a 1 funcref) (table $b 1 funcref)
+                 (func (table.copy $a $b (i32.const 0) (i32.const 0) (i32.const 1))))"#,
+        );
+        assert_eq!(err.location(), Some(&Location::Function(0)));
+        assert_eq!(
+            source(&err),
+            "a table other than the first of the reference types proposal is not supported"
+        );
+
+        let err = error("(module (func (param v128) (result v128) local.get 0 i8x16.abs))");
+        assert_eq!(err.location(), Some(&Location::Function(0)));
+        assert_eq!(
+            source(&err),
+            "i8x16.abs of the SIMD proposal is not supported"
+        );
+
+        let err = error("(module (memory 1 1 shared))");
+        assert_eq!(source(&err), "the threads proposal is not supported");
+    }
+
+    #[test]
+    fn unsupported_other_proposals() {
+        // An exception handling try block, opcode 0x06 without the 0xfc prefix.
+        // (module (func try end))
+        let input =
+            FromHex::from_hex("0061736d01000000010401600000030201000a0701050006400b0b").unwrap();
+        assert!(Module::from_bytes(&input).is_err());
+        assert_eq!(unsupported(&input), None);
+
+        // Not a module at all.
+        assert_eq!(unsupported(&[0, 1, 2, 3]), None);
+
+        // (module (memory 1 1 shared))
+        let input = FromHex::from_hex("0061736d01000000050401030101").unwrap();
+        assert!(Module::from_bytes(&input).is_err());
+        assert_eq!(unsupported(&input), Some(Proposal::Threads));
+    }
+}
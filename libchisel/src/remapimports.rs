@@ -1,7 +1,9 @@
 use std::collections::HashMap;
 
+use log::debug;
 use parity_wasm::elements::{ImportEntry, ImportSection, Module};
 
+use super::diff::{encoded_size, Change, Changes};
 use super::{
     imports::ImportList, ChiselModule, ModuleError, ModuleKind, ModulePreset, ModuleTranslator,
 };
@@ -99,6 +101,33 @@ impl<'a> ModuleTranslator for RemapImports<'a> {
         Ok(was_mutated)
     }
 
+    fn translate_with_changes(&self, module: &mut Module) -> Result<Option<Changes>, ModuleError> {
+        let (before, size_before) = match module.import_section() {
+            Some(section) => (section.entries().to_vec(), encoded_size(section.clone())),
+            None => return Ok(None),
+        };
+
+        if !self.translate_inplace(module)? {
+            return Ok(None);
+        }
+
+        // Remapping only renames entries, so they still line up one to one.
+        let mut changes = Changes::default();
+        let section = module
+            .import_section()
+            .expect("Remapping keeps the import section");
+        for (old, new) in before.iter().zip(section.entries()) {
+            if old.module() != new.module() || old.field() != new.field() {
+                changes.push(Change::ImportRenamed(
+                    (old.module().to_string(), old.field().to_string()),
+                    (new.module().to_string(), new.field().to_string()),
+                ));
+            }
+        }
+        changes.add_size_delta(encoded_size(section.clone()) as isize - size_before as isize);
+        Ok(Some(changes))
+    }
+
     fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
         let mut new_module = module.clone();
         let mut was_mutated = false;
@@ -164,6 +193,13 @@ impl<'a> RemapImports<'a> {
                         .imports()
                         .lookup_by_field(&entry.field()[prefix_len..])
                     {
+                        debug!(
+                            "Remapping import {}::{} to {}::{}",
+                            entry.module(),
+                            entry.field(),
+                            import.module(),
+                            import.field()
+                        );
                         *mutflag = true;
                         return ImportEntry::new(
                             import.module().into(),
@@ -176,6 +212,13 @@ impl<'a> RemapImports<'a> {
             }
             None => {
                 if let Some(import) = interface.imports().lookup_by_field(&entry.field()) {
+                    debug!(
+                        "Remapping import {}::{} to {}::{}",
+                        entry.module(),
+                        entry.field(),
+                        import.module(),
+                        import.field()
+                    );
                     *mutflag = true;
                     ImportEntry::new(
                         import.module().into(),
@@ -212,7 +255,7 @@ mod tests {
             .unwrap()
             .translate_inplace(&mut module)
             .unwrap();
-        let output = module.to_bytes().unwrap();
+        let output = module.into_bytes().unwrap();
         let expected = FromHex::from_hex(
             "
             0061736d0100000001050160017e0002130108657468657265756d067573
@@ -224,6 +267,32 @@ mod tests {
         assert!(did_change);
     }
 
+    #[test]
+    fn remap_changes() {
+        let input = FromHex::from_hex(
+            "
+            0061736d0100000001050160017e0002170103656e760f65746865726575
+            6d5f7573654761730000
+        ",
+        )
+        .unwrap();
+        let mut module = Module::from_bytes(&input).unwrap();
+        let remapimports = RemapImports::with_preset("ewasm").unwrap();
+        let changes = remapimports
+            .translate_with_changes(&mut module)
+            .unwrap()
+            .expect("Should be mutated");
+        assert_eq!(
+            changes.changes(),
+            &[Change::ImportRenamed(
+                ("env".to_string(), "ethereum_useGas".to_string()),
+                ("ethereum".to_string(), "useGas".to_string()),
+            )]
+        );
+        assert_eq!(changes.size_delta(), Some(-4));
+        assert_eq!(remapimports.translate_with_changes(&mut module), Ok(None));
+    }
+
     #[test]
     fn remap_did_mutate() {
         // wast:
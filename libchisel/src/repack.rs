@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use parity_wasm::builder;
-use parity_wasm::elements::Module;
+use parity_wasm::elements::{Module, Section};
 
 use super::{ChiselModule, ModuleError, ModuleKind, ModuleTranslator};
 
@@ -32,16 +32,37 @@ impl<'a> ChiselModule<'a> for Repack {
 }
 
 impl ModuleTranslator for Repack {
-    fn translate_inplace(&self, _module: &mut Module) -> Result<bool, ModuleError> {
-        Err(ModuleError::NotSupported)
+    fn translate_inplace(&self, module: &mut Module) -> Result<bool, ModuleError> {
+        let taken = std::mem::take(module);
+        *module = repack(taken);
+        Ok(true)
     }
 
     fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
-        // TODO: check in names section is carried over.
-        let module = module.clone();
-        let module = builder::from_module(module).build();
-        Ok(Some(module))
+        Ok(Some(repack(module.clone())))
+    }
+}
+
+/// Rebuilds the module from its known sections. The builder carries custom sections over, so they
+/// are dropped explicitly. It also appends the data count section last, so it is moved back in
+/// front of the code section.
+fn repack(module: Module) -> Module {
+    let mut module = builder::from_module(module).build();
+    let mut data_count = None;
+    module.sections_mut().retain(|section| match section {
+        Section::Custom(_) | Section::Name(_) | Section::Reloc(_) => false,
+        Section::DataCount(count) => {
+            data_count = Some(*count);
+            false
+        }
+        _ => true,
+    });
+    if let Some(count) = data_count {
+        module
+            .insert_section(Section::DataCount(count))
+            .expect("The data count section was removed above");
     }
+    module
 }
 
 #[cfg(test)]
@@ -60,6 +81,15 @@ mod tests {
         assert_eq!(module, repack.translate(&module).unwrap().unwrap());
     }
 
+    #[test]
+    fn smoke_test_inplace() {
+        let mut module = Module::default();
+
+        let repack = Repack::with_defaults().unwrap();
+        assert!(repack.translate_inplace(&mut module).unwrap());
+        assert_eq!(module, Module::default());
+    }
+
     #[test]
     fn basic_sections_only() {
         let module = builder::module()
@@ -134,4 +164,19 @@ mod tests {
         let output = repack.translate(&module).unwrap().unwrap();
         assert_eq!(output.has_names_section(), false);
     }
+
+    #[test]
+    fn data_count_section() {
+        // A passive data segment used by memory.init, which requires a data count section in
+        // front of the code section.
+        let input = FromHex::from_hex(
+            "0061736d01000000010401600000030201000503010001070801046d61696e00000c
+01010a0e010c00410041004103fc0800000b0b06010103616263",
+        )
+        .unwrap();
+        let module = Module::from_bytes(&input).unwrap();
+        let repack = Repack::with_defaults().unwrap();
+        let output = repack.translate(&module).unwrap().unwrap();
+        assert_eq!(output.into_bytes().unwrap(), input);
+    }
 }
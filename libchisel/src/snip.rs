@@ -1,7 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use parity_wasm::elements::Module;
 
+use super::batch::{translate_walrus, WalrusTranslator};
+use super::diff::{Change, Changes};
 use super::{ChiselModule, ModuleError, ModuleKind, ModuleTranslator};
 
 // TODO: consider making this a generic helper?
@@ -50,25 +53,55 @@ impl<'a> ChiselModule<'a> for Snip {
     }
 }
 
-impl From<failure::Error> for ModuleError {
-    fn from(error: failure::Error) -> Self {
-        ModuleError::Custom(error.to_string())
+impl WalrusTranslator for Snip {
+    fn translate_walrus(&self, module: &mut walrus::Module) -> Result<bool, ModuleError> {
+        wasm_snip::snip(module, self.0.clone())
+            .map_err(|e| ModuleError::External("wasm-snip", Arc::new(e.compat())))?;
+        Ok(true)
+    }
+
+    fn translate_walrus_with_changes(
+        &self,
+        module: &mut walrus::Module,
+    ) -> Result<Option<Changes>, ModuleError> {
+        let before: Vec<(walrus::FunctionId, Option<String>)> = module
+            .funcs
+            .iter()
+            .map(|func| (func.id(), func.name.clone()))
+            .collect();
+        self.translate_walrus(module)?;
+        let after: HashSet<walrus::FunctionId> =
+            module.funcs.iter().map(|func| func.id()).collect();
+
+        let mut changes = Changes::default();
+        for (id, name) in before.iter() {
+            if let (false, Some(name)) = (after.contains(id), name) {
+                changes.push(Change::FunctionRemoved(name.clone()));
+            }
+        }
+        if before.len() != after.len() {
+            changes.push(Change::FunctionCount(before.len(), after.len()));
+        }
+        Ok(Some(changes))
     }
 }
 
 impl ModuleTranslator for Snip {
-    fn translate_inplace(&self, _module: &mut Module) -> Result<bool, ModuleError> {
-        Err(ModuleError::NotSupported)
+    fn translate_inplace(&self, module: &mut Module) -> Result<bool, ModuleError> {
+        Ok(
+            translate_walrus(module, &[self as &dyn WalrusTranslator], false)
+                .remove(0)?
+                .is_some(),
+        )
     }
 
-    fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
-        let serialized = module.clone().to_bytes()?;
-
-        let mut input = walrus::Module::from_buffer(&serialized)?;
-        wasm_snip::snip(&mut input, self.0.clone())?;
-        let output = input.emit_wasm();
+    fn translate_with_changes(&self, module: &mut Module) -> Result<Option<Changes>, ModuleError> {
+        translate_walrus(module, &[self as &dyn WalrusTranslator], true).remove(0)
+    }
 
-        let output = Module::from_bytes(&output[..])?;
+    fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
+        let mut output = module.clone();
+        self.translate_inplace(&mut output)?;
         Ok(Some(output))
     }
 }
@@ -78,6 +111,7 @@ mod tests {
     use rustc_hex::FromHex;
 
     use super::*;
+    use crate::diff::diff;
 
     #[test]
     fn smoke_test() {
@@ -110,6 +144,33 @@ mod tests {
         let module = module
             .expect("translation to be succesful")
             .expect("new module to be returned");
-        assert!(module.to_bytes().unwrap().len() < wasm.len());
+        assert!(module.into_bytes().unwrap().len() < wasm.len());
+    }
+
+    #[test]
+    fn snipped_functions_listed() {
+        // (module
+        //   (func $main (export "main")
+        //     (call $core::fmt::write::h9f284ae8e8e9b94a))
+        //   (func $core::fmt::write::h9f284ae8e8e9b94a))
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d010000000104016000000303020000070801046d61696e00000a
+0902040010010b02000b0033046e616d65012c0200046d61696e0123636f
+72653a3a666d743a3a77726974653a3a6839663238346165386538653962
+393461",
+        )
+        .unwrap();
+        let before = Module::from_bytes(&wasm).unwrap().parse_names().unwrap();
+        let mut after = before.clone();
+        let changes = Snip::with_defaults()
+            .unwrap()
+            .translate_with_changes(&mut after)
+            .unwrap()
+            .expect("Should be mutated");
+        let removed = Change::FunctionRemoved("core::fmt::write::h9f284ae8e8e9b94a".to_string());
+        assert!(changes.changes().contains(&removed));
+        // The names of the converted module are parsed, so the diff finds the function too.
+        assert!(after.names_section().is_some());
+        assert!(diff(&before, &after).changes().contains(&removed));
     }
 }
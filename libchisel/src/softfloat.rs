@@ -0,0 +1,1244 @@
+//! Lowers floating point to deterministic software routines.
+//! Float values are replaced by their bit patterns everywhere in the module, f32 by i32 and f64 by
+//! i64: in signatures, locals, globals and block types. Float loads, stores and constants become
+//! their integer counterparts, and reinterpretations are dropped. Every other float instruction
+//! becomes a call to a routine implementing it, which is either bundled into the module or
+//! imported. The routines round to nearest, ties to even, and arithmetic produces canonical NaNs.
+//! Like the instructions they replace, abs, neg and copysign only change the sign bit, so they keep
+//! the payload of a NaN.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use parity_wasm::elements::{
+    BlockType, CodeSection, External, Func, FuncBody, FunctionSection, FunctionType, GlobalType,
+    ImportCountType, ImportEntry, ImportSection, Instruction, Internal, Local, Module, Section,
+    Type, TypeSection, ValueType,
+};
+
+use super::depgraph::{DepGraph, DepGraphBuilder};
+use super::{ChiselModule, ModuleError, ModuleKind, ModuleTranslator};
+
+/// The routines, compiled from res/softfloat.wat without the names section. Each is exported under
+/// the name of the instruction it implements, e.g. "f32_add".
+const RUNTIME: &[u8] = include_bytes!("../res/softfloat.wasm");
+
+/// Enum on which ModuleTranslator is implemented.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SoftFloat {
+    /// The routines used by the module are added to it as functions.
+    Bundle,
+    /// The routines used by the module are imported from the namespace, under the same names.
+    Import(String),
+}
+
+impl<'a> ChiselModule<'a> for SoftFloat {
+    type ObjectReference = &'a dyn ModuleTranslator;
+
+    fn id(&'a self) -> String {
+        "softfloat".to_string()
+    }
+
+    fn kind(&'a self) -> ModuleKind {
+        ModuleKind::Translator
+    }
+
+    fn as_abstract(&'a self) -> Self::ObjectReference {
+        self as Self::ObjectReference
+    }
+
+    fn with_defaults() -> Result<Self, ModuleError> {
+        Ok(SoftFloat::Bundle)
+    }
+
+    fn with_config(config: &HashMap<String, String>) -> Result<Self, ModuleError> {
+        let namespace = config.get("namespace");
+        match config.get("mode").map(|value| value.as_str()) {
+            None | Some("bundle") => match namespace {
+                None => Ok(SoftFloat::Bundle),
+                Some(_) => Err(ModuleError::InvalidConfig(
+                    "namespace".to_string(),
+                    "only used in import mode".to_string(),
+                )),
+            },
+            Some("import") => Ok(SoftFloat::Import(
+                namespace.map_or("softfloat".to_string(), |namespace| namespace.clone()),
+            )),
+            Some(value) => Err(ModuleError::InvalidConfig(
+                "mode".to_string(),
+                format!("expected bundle or import, found '{}'", value),
+            )),
+        }
+    }
+}
+
+impl ModuleTranslator for SoftFloat {
+    fn translate_inplace(&self, module: &mut Module) -> Result<bool, ModuleError> {
+        self.lower(module)
+    }
+
+    fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
+        let mut ret = module.clone();
+        if self.lower(&mut ret)? {
+            Ok(Some(ret))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// How a float instruction is lowered.
+enum Lowering {
+    /// Replaced by an integer instruction.
+    Replace(Instruction),
+    /// Dropped, as a float and its bit pattern are now the same value.
+    Drop,
+    /// Replaced by a call to the routine of the given name.
+    Call(&'static str),
+}
+
+impl SoftFloat {
+    fn lower(&self, module: &mut Module) -> Result<bool, ModuleError> {
+        check_imports(module)?;
+        check_exports(module)?;
+
+        let routines: BTreeSet<&'static str> =
+            module.code_section().map_or(BTreeSet::new(), |section| {
+                section
+                    .bodies()
+                    .iter()
+                    .flat_map(|body| body.code().elements())
+                    .filter_map(|instruction| match lowering(instruction) {
+                        Some(Lowering::Call(routine)) => Some(routine),
+                        _ => None,
+                    })
+                    .collect()
+            });
+
+        let mut changed = lower_types(module);
+        changed |= lower_globals(module);
+
+        let runtime = Module::from_bytes(RUNTIME).map_err(|e| ModuleError::Parse(Arc::new(e)))?;
+        let indices = match self {
+            SoftFloat::Bundle => bundle_routines(module, &runtime, &routines),
+            SoftFloat::Import(namespace) => import_routines(module, &runtime, &routines, namespace),
+        };
+        changed |= lower_code(module, &indices);
+
+        Ok(changed)
+    }
+}
+
+/// Float types of imports cannot be lowered, as they are defined by the host.
+fn check_imports(module: &Module) -> Result<(), ModuleError> {
+    let section = match module.import_section() {
+        Some(section) => section,
+        None => return Ok(()),
+    };
+    for entry in section.entries() {
+        let uses_floats = match entry.external() {
+            External::Function(idx) => match module
+                .type_section()
+                .and_then(|section| section.types().get(*idx as usize))
+            {
+                Some(Type::Function(ty)) => ty.params().iter().chain(ty.results()).any(is_float),
+                None => false,
+            },
+            External::Global(ty) => is_float(&ty.content_type()),
+            _ => false,
+        };
+        if uses_floats {
+            return Err(ModuleError::Custom(format!(
+                "Cannot lower the float type of import {}.{}",
+                entry.module(),
+                entry.field()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Float types of exports cannot be lowered either, as the host would see bit patterns instead.
+fn check_exports(module: &Module) -> Result<(), ModuleError> {
+    let section = match module.export_section() {
+        Some(section) => section,
+        None => return Ok(()),
+    };
+    // Imported functions and globals have been checked along with the imports.
+    let imported_functions = module.import_count(ImportCountType::Function) as u32;
+    let imported_globals = module.import_count(ImportCountType::Global) as u32;
+    for entry in section.entries() {
+        let uses_floats = match *entry.internal() {
+            Internal::Function(idx) if idx >= imported_functions => module
+                .function_section()
+                .and_then(|section| section.entries().get((idx - imported_functions) as usize))
+                .and_then(|func| {
+                    module
+                        .type_section()
+                        .and_then(|section| section.types().get(func.type_ref() as usize))
+                })
+                .is_some_and(|Type::Function(ty)| {
+                    ty.params().iter().chain(ty.results()).any(is_float)
+                }),
+            Internal::Global(idx) if idx >= imported_globals => module
+                .global_section()
+                .and_then(|section| section.entries().get((idx - imported_globals) as usize))
+                .is_some_and(|entry| is_float(&entry.global_type().content_type())),
+            _ => false,
+        };
+        if uses_floats {
+            return Err(ModuleError::Custom(format!(
+                "Cannot lower the float type of export {}",
+                entry.field()
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn is_float(ty: &ValueType) -> bool {
+    matches!(ty, ValueType::F32 | ValueType::F64)
+}
+
+/// Replaces a float type by the type of its bit pattern. Returns true if it was a float type.
+fn lower_value_type(ty: &mut ValueType) -> bool {
+    match ty {
+        ValueType::F32 => *ty = ValueType::I32,
+        ValueType::F64 => *ty = ValueType::I64,
+        _ => return false,
+    }
+    true
+}
+
+fn lower_types(module: &mut Module) -> bool {
+    let mut changed = false;
+    if let Some(section) = module.type_section_mut() {
+        for Type::Function(ty) in section.types_mut().iter_mut() {
+            for param in ty.params_mut().iter_mut() {
+                changed |= lower_value_type(param);
+            }
+            for result in ty.results_mut().iter_mut() {
+                changed |= lower_value_type(result);
+            }
+        }
+    }
+    changed
+}
+
+fn lower_globals(module: &mut Module) -> bool {
+    let mut changed = false;
+    if let Some(section) = module.global_section_mut() {
+        for entry in section.entries_mut().iter_mut() {
+            let mut ty = entry.global_type().content_type();
+            if lower_value_type(&mut ty) {
+                let is_mutable = entry.global_type().is_mutable();
+                *entry.global_type_mut() = GlobalType::new(ty, is_mutable);
+                changed = true;
+            }
+            for instruction in entry.init_expr_mut().code_mut().iter_mut() {
+                if let Some(Lowering::Replace(replacement)) = lowering(instruction) {
+                    *instruction = replacement;
+                }
+            }
+        }
+    }
+    changed
+}
+
+/// Lowers the locals, block types and instructions of every function body, calling the routines
+/// at the given function indices.
+fn lower_code(module: &mut Module, routines: &HashMap<&'static str, u32>) -> bool {
+    let mut changed = false;
+    let bodies = match module.code_section_mut() {
+        Some(section) => section.bodies_mut(),
+        None => return false,
+    };
+    for body in bodies.iter_mut() {
+        for local in body.locals_mut().iter_mut() {
+            let mut ty = local.value_type();
+            if lower_value_type(&mut ty) {
+                *local = Local::new(local.count(), ty);
+                changed = true;
+            }
+        }
+
+        let instructions = body.code_mut().elements_mut();
+        let mut lowered = Vec::with_capacity(instructions.len());
+        for mut instruction in instructions.drain(..) {
+            match &mut instruction {
+                Instruction::Block(BlockType::Value(ty))
+                | Instruction::Loop(BlockType::Value(ty))
+                | Instruction::If(BlockType::Value(ty)) => changed |= lower_value_type(ty),
+                _ => {}
+            }
+            match lowering(&instruction) {
+                Some(Lowering::Replace(replacement)) => lowered.push(replacement),
+                Some(Lowering::Drop) => {}
+                Some(Lowering::Call(routine)) => lowered.push(Instruction::Call(routines[routine])),
+                None => {
+                    lowered.push(instruction);
+                    continue;
+                }
+            }
+            changed = true;
+        }
+        *instructions = lowered;
+    }
+    changed
+}
+
+/// Returns the index of the function type, adding it to the type section if it is missing.
+pub(crate) fn type_index(module: &mut Module, ty: &FunctionType) -> u32 {
+    if module.type_section().is_none() {
+        module
+            .insert_section(Section::Type(TypeSection::with_types(vec![])))
+            .expect("the type section is missing");
+    }
+    let types = module
+        .type_section_mut()
+        .expect("the type section exists")
+        .types_mut();
+    if let Some(idx) = types.iter().position(|Type::Function(other)| other == ty) {
+        return idx as u32;
+    }
+    types.push(Type::Function(ty.clone()));
+    (types.len() - 1) as u32
+}
+
+/// Returns the function index of every routine exported by the runtime.
+fn runtime_exports(runtime: &Module) -> HashMap<&str, u32> {
+    runtime
+        .export_section()
+        .expect("the runtime has exports")
+        .entries()
+        .iter()
+        .filter_map(|entry| match entry.internal() {
+            Internal::Function(idx) => Some((entry.field(), *idx)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the signature of a function of the runtime, which has no imports.
+fn runtime_signature(runtime: &Module, idx: u32) -> FunctionType {
+    let type_ref = runtime
+        .function_section()
+        .expect("the runtime has functions")
+        .entries()[idx as usize]
+        .type_ref();
+    match &runtime
+        .type_section()
+        .expect("the runtime has types")
+        .types()[type_ref as usize]
+    {
+        Type::Function(ty) => ty.clone(),
+    }
+}
+
+pub(crate) fn function_count(module: &Module) -> u32 {
+    module.import_count(ImportCountType::Function) as u32
+        + module
+            .function_section()
+            .map_or(0, |section| section.entries().len() as u32)
+}
+
+/// Appends the routines, and the runtime functions they call, to the module. Returns their
+/// function indices.
+fn bundle_routines(
+    module: &mut Module,
+    runtime: &Module,
+    routines: &BTreeSet<&'static str>,
+) -> HashMap<&'static str, u32> {
+    let exports = runtime_exports(runtime);
+    let mut functions = BTreeSet::new();
+    for routine in routines {
+        let idx = exports[routine];
+        functions.insert(idx);
+        functions.extend(
+            DepGraph::build(runtime, idx)
+                .expect("the runtime has code")
+                .callees(),
+        );
+    }
+
+    // The functions keep their relative order, after those of the module.
+    let base = function_count(module);
+    let remapped: HashMap<u32, u32> = functions
+        .iter()
+        .enumerate()
+        .map(|(offset, idx)| (*idx, base + offset as u32))
+        .collect();
+
+    if module.function_section().is_none() {
+        module
+            .insert_section(Section::Function(FunctionSection::with_entries(vec![])))
+            .expect("the function section is missing");
+    }
+    if module.code_section().is_none() {
+        module
+            .insert_section(Section::Code(CodeSection::with_bodies(vec![])))
+            .expect("the code section is missing");
+    }
+    let bodies = runtime
+        .code_section()
+        .expect("the runtime has code")
+        .bodies();
+    for idx in functions.iter() {
+        let type_ref = type_index(module, &runtime_signature(runtime, *idx));
+        let mut body: FuncBody = bodies[*idx as usize].clone();
+        for instruction in body.code_mut().elements_mut().iter_mut() {
+            if let Instruction::Call(callee) = instruction {
+                *callee = remapped[callee];
+            }
+        }
+        module
+            .function_section_mut()
+            .expect("the function section exists")
+            .entries_mut()
+            .push(Func::new(type_ref));
+        module
+            .code_section_mut()
+            .expect("the code section exists")
+            .bodies_mut()
+            .push(body);
+    }
+
+    routines
+        .iter()
+        .map(|routine| (*routine, remapped[&exports[routine]]))
+        .collect()
+}
+
+/// Imports the routines from the namespace, after the existing imports. Returns their function
+/// indices.
+fn import_routines(
+    module: &mut Module,
+    runtime: &Module,
+    routines: &BTreeSet<&'static str>,
+    namespace: &str,
+) -> HashMap<&'static str, u32> {
+    let exports = runtime_exports(runtime);
+    let imported = module.import_count(ImportCountType::Function) as u32;
+    shift_functions(module, imported, routines.len() as u32);
+
+    if module.import_section().is_none() {
+        module
+            .insert_section(Section::Import(ImportSection::with_entries(vec![])))
+            .expect("the import section is missing");
+    }
+    let mut indices = HashMap::new();
+    for (offset, routine) in routines.iter().enumerate() {
+        let type_ref = type_index(module, &runtime_signature(runtime, exports[routine]));
+        module
+            .import_section_mut()
+            .expect("the import section exists")
+            .entries_mut()
+            .push(ImportEntry::new(
+                namespace.to_string(),
+                routine.to_string(),
+                External::Function(type_ref),
+            ));
+        indices.insert(*routine, imported + offset as u32);
+    }
+    indices
+}
+
+/// Shifts the indices of the functions defined by the module, to make room for new imports.
+fn shift_functions(module: &mut Module, imported: u32, count: u32) {
+    if count == 0 {
+        return;
+    }
+    let shift = |idx: u32| if idx >= imported { idx + count } else { idx };
+
+    if let Some(section) = module.code_section_mut() {
+        for body in section.bodies_mut().iter_mut() {
+            for instruction in body.code_mut().elements_mut().iter_mut() {
+                if let Instruction::Call(idx) = instruction {
+                    *idx = shift(*idx);
+                }
+            }
+        }
+    }
+    if let Some(section) = module.export_section_mut() {
+        for entry in section.entries_mut().iter_mut() {
+            if let Internal::Function(idx) = entry.internal_mut() {
+                *idx = shift(*idx);
+            }
+        }
+    }
+    if let Some(section) = module.elements_section_mut() {
+        for segment in section.entries_mut().iter_mut() {
+            for idx in segment.members_mut().iter_mut() {
+                *idx = shift(*idx);
+            }
+        }
+    }
+    if let Some(start) = module.start_section() {
+        module.set_start_section(shift(start));
+    }
+    if let Some(section) = module.names_section_mut() {
+        if let Some(functions) = section.functions_mut() {
+            let names = functions.names_mut();
+            *names = names
+                .iter()
+                .map(|(idx, name)| (shift(idx), name.clone()))
+                .collect();
+        }
+        if let Some(locals) = section.locals_mut() {
+            let names = locals.local_names_mut();
+            *names = names
+                .iter()
+                .map(|(idx, names)| (shift(idx), names.clone()))
+                .collect();
+        }
+    }
+}
+
+fn lowering(instruction: &Instruction) -> Option<Lowering> {
+    use Instruction::*;
+
+    let routine = match instruction {
+        F32Const(bits) => return Some(Lowering::Replace(I32Const(*bits as i32))),
+        F64Const(bits) => return Some(Lowering::Replace(I64Const(*bits as i64))),
+        F32Load(align, offset) => return Some(Lowering::Replace(I32Load(*align, *offset))),
+        F64Load(align, offset) => return Some(Lowering::Replace(I64Load(*align, *offset))),
+        F32Store(align, offset) => return Some(Lowering::Replace(I32Store(*align, *offset))),
+        F64Store(align, offset) => return Some(Lowering::Replace(I64Store(*align, *offset))),
+        I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => {
+            return Some(Lowering::Drop)
+        }
+        F32Eq => "f32_eq",
+        F32Ne => "f32_ne",
+        F32Lt => "f32_lt",
+        F32Gt => "f32_gt",
+        F32Le => "f32_le",
+        F32Ge => "f32_ge",
+        F64Eq => "f64_eq",
+        F64Ne => "f64_ne",
+        F64Lt => "f64_lt",
+        F64Gt => "f64_gt",
+        F64Le => "f64_le",
+        F64Ge => "f64_ge",
+        F32Abs => "f32_abs",
+        F32Neg => "f32_neg",
+        F32Ceil => "f32_ceil",
+        F32Floor => "f32_floor",
+        F32Trunc => "f32_trunc",
+        F32Nearest => "f32_nearest",
+        F32Sqrt => "f32_sqrt",
+        F32Add => "f32_add",
+        F32Sub => "f32_sub",
+        F32Mul => "f32_mul",
+        F32Div => "f32_div",
+        F32Min => "f32_min",
+        F32Max => "f32_max",
+        F32Copysign => "f32_copysign",
+        F64Abs => "f64_abs",
+        F64Neg => "f64_neg",
+        F64Ceil => "f64_ceil",
+        F64Floor => "f64_floor",
+        F64Trunc => "f64_trunc",
+        F64Nearest => "f64_nearest",
+        F64Sqrt => "f64_sqrt",
+        F64Add => "f64_add",
+        F64Sub => "f64_sub",
+        F64Mul => "f64_mul",
+        F64Div => "f64_div",
+        F64Min => "f64_min",
+        F64Max => "f64_max",
+        F64Copysign => "f64_copysign",
+        I32TruncSF32 => "i32_trunc_f32_s",
+        I32TruncUF32 => "i32_trunc_f32_u",
+        I32TruncSF64 => "i32_trunc_f64_s",
+        I32TruncUF64 => "i32_trunc_f64_u",
+        I64TruncSF32 => "i64_trunc_f32_s",
+        I64TruncUF32 => "i64_trunc_f32_u",
+        I64TruncSF64 => "i64_trunc_f64_s",
+        I64TruncUF64 => "i64_trunc_f64_u",
+        F32ConvertSI32 => "f32_convert_i32_s",
+        F32ConvertUI32 => "f32_convert_i32_u",
+        F32ConvertSI64 => "f32_convert_i64_s",
+        F32ConvertUI64 => "f32_convert_i64_u",
+        F32DemoteF64 => "f32_demote_f64",
+        F64ConvertSI32 => "f64_convert_i32_s",
+        F64ConvertUI32 => "f64_convert_i32_u",
+        F64ConvertSI64 => "f64_convert_i64_s",
+        F64ConvertUI64 => "f64_convert_i64_u",
+        F64PromoteF32 => "f64_promote_f32",
+        _ => return None,
+    };
+    Some(Lowering::Call(routine))
+}
+
+#[cfg(test)]
+mod tests {
+    use rustc_hex::FromHex;
+
+    use super::*;
+    use crate::checkfloat::CheckFloat;
+    use crate::dropsection::DropSection;
+    use crate::ModuleValidator;
+
+    #[test]
+    fn runtime_matches_source() {
+        // The names section is left out of the checked-in runtime.
+        let mut assembled =
+            Module::from_bytes(wat::parse_str(include_str!("../res/softfloat.wat")).unwrap())
+                .unwrap();
+        assert!(DropSection::NamesSection
+            .translate_inplace(&mut assembled)
+            .unwrap());
+        assert!(
+            assembled.into_bytes().unwrap() == RUNTIME,
+            "res/softfloat.wasm is out of date; reassemble it from res/softfloat.wat"
+        );
+    }
+
+    fn signature(module: &Module, idx: u32) -> &FunctionType {
+        let imported = module.import_count(ImportCountType::Function) as u32;
+        let type_ref =
+            module.function_section().unwrap().entries()[(idx - imported) as usize].type_ref();
+        match &module.type_section().unwrap().types()[type_ref as usize] {
+            Type::Function(ty) => ty,
+        }
+    }
+
+    #[test]
+    fn bundle() {
+        // (module
+        //   (global $g (mut f64) (f64.const 1.5))
+        //   (memory 1)
+        //   (func $main (param $x f32) (result f32)
+        //     (local $y f32)
+        //     (local.set $y (f32.add (local.get $x) (f32.const 2)))
+        //     (f32.store (i32.const 0) (local.get $y))
+        //     (global.set $g (f64.promote_f32 (f32.load (i32.const 0))))
+        //     (block (result f32)
+        //       (f32.reinterpret_i32 (i32.reinterpret_f32 (local.get $y))))))
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d0100000001060160017d017d030201000503010001060d017c0144000000000000f83f0b0a
+26012401017d200043000000409221014100200138020041002a0200bb2400027d2001bcbe0b0b001f046e61
+6d6501070100046d61696e0209010002000178010179070401000167",
+        )
+        .unwrap();
+        let module = Module::from_bytes(&wasm).unwrap();
+
+        let translator = SoftFloat::with_defaults().unwrap();
+        let module = translator.translate(&module).unwrap().unwrap();
+
+        let checkfloat = CheckFloat::with_defaults().unwrap();
+        assert_eq!(checkfloat.validate(&module), Ok(true));
+
+        assert_eq!(
+            signature(&module, 0),
+            &FunctionType::new(vec![ValueType::I32], vec![ValueType::I32])
+        );
+        let global = &module.global_section().unwrap().entries()[0];
+        assert_eq!(global.global_type().content_type(), ValueType::I64);
+        assert_eq!(
+            global.init_expr().code(),
+            &[
+                Instruction::I64Const(1.5f64.to_bits() as i64),
+                Instruction::End
+            ]
+        );
+
+        // f32_add and f64_promote_f32, and the helpers they call.
+        assert_eq!(module.function_section().unwrap().entries().len(), 6);
+        let code = module.code_section().unwrap().bodies()[0].code().elements();
+        assert_eq!(
+            &code[..6],
+            &[
+                Instruction::GetLocal(0),
+                Instruction::I32Const(2f32.to_bits() as i32),
+                Instruction::Call(4),
+                Instruction::SetLocal(1),
+                Instruction::I32Const(0),
+                Instruction::GetLocal(1),
+            ]
+        );
+        assert!(code.contains(&Instruction::I32Store(2, 0)));
+        assert!(code.contains(&Instruction::Block(BlockType::Value(ValueType::I32))));
+        assert!(!code.contains(&Instruction::I32ReinterpretF32));
+
+        let serialized = module.into_bytes().unwrap();
+        assert!(Module::from_bytes(&serialized).is_ok());
+    }
+
+    #[test]
+    fn saturating_conversion() {
+        let wasm = wat::parse_str(
+            r#"(module
+                 (func (export "main") (param i32) (result i32)
+                   local.get 0 f32.reinterpret_i32 i32.trunc_sat_f32_s))"#,
+        )
+        .unwrap();
+        let module = crate::proposals::from_bytes(&wasm).unwrap();
+
+        // The function standing in for the conversion is lowered like any other.
+        let translator = SoftFloat::with_defaults().unwrap();
+        let module = translator.translate(&module).unwrap().unwrap();
+        let checkfloat = CheckFloat::with_defaults().unwrap();
+        assert_eq!(checkfloat.validate(&module), Ok(true));
+
+        let serialized = crate::proposals::into_bytes(module).unwrap();
+        let engine = wasmi::Engine::default();
+        let module = wasmi::Module::new(&engine, &serialized[..]).unwrap();
+        let mut store = wasmi::Store::new(&engine, ());
+        let instance = wasmi::Linker::new(&engine)
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .unwrap();
+        let main = instance.get_func(&store, "main").unwrap();
+        for value in [f32::NAN, f32::INFINITY, -3e9, -1.5, 0.5, 3e9] {
+            let mut results = [wasmi::Value::I32(0)];
+            main.call(
+                &mut store,
+                &[wasmi::Value::I32(value.to_bits() as i32)],
+                &mut results,
+            )
+            .unwrap();
+            assert_eq!(results[0].i32(), Some(value as i32), "{}", value);
+        }
+    }
+
+    #[test]
+    fn import() {
+        // (module
+        //   (import "env" "log" (func $log (param i32)))
+        //   (table 1 funcref)
+        //   (elem (i32.const 0) $helper)
+        //   (start $init)
+        //   (func $main (export "main")
+        //     (call $helper (f64.const 2))
+        //     (call $log (i32.const 0)))
+        //   (func $helper (param f64)
+        //     (drop (f64.sqrt (local.get 0))))
+        //   (func $init))
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d01000000010c0360017f0060000060017c00020b0103656e76036c6f670000030403010201
+040401700001070801046d61696e00010801030907010041000b01020a1d0311004400000000000000401002
+410010000b060020009f1a0b02000b0021046e616d65011a0400036c6f6701046d61696e020668656c706572
+0304696e6974",
+        )
+        .unwrap();
+        let module = Module::from_bytes(&wasm).unwrap().parse_names().unwrap();
+
+        let translator = SoftFloat::Import("float".to_string());
+        let module = translator.translate(&module).unwrap().unwrap();
+
+        let checkfloat = CheckFloat::with_defaults().unwrap();
+        assert_eq!(checkfloat.validate(&module), Ok(true));
+
+        let imports: Vec<(&str, &str)> = module
+            .import_section()
+            .unwrap()
+            .entries()
+            .iter()
+            .map(|entry| (entry.module(), entry.field()))
+            .collect();
+        assert_eq!(imports, vec![("env", "log"), ("float", "f64_sqrt")]);
+        assert_eq!(
+            module.import_section().unwrap().entries()[1].external(),
+            &External::Function(3)
+        );
+        assert_eq!(
+            module.type_section().unwrap().types()[3],
+            Type::Function(FunctionType::new(
+                vec![ValueType::I64],
+                vec![ValueType::I64]
+            ))
+        );
+
+        // Every function defined by the module moved up by one.
+        assert_eq!(
+            module.export_section().unwrap().entries()[0].internal(),
+            &Internal::Function(2)
+        );
+        assert_eq!(
+            module.elements_section().unwrap().entries()[0].members(),
+            &[3]
+        );
+        assert_eq!(module.start_section(), Some(4));
+        let names = module.names_section().unwrap().functions().unwrap().names();
+        assert_eq!(names.get(2).map(|name| name.as_str()), Some("main"));
+        assert_eq!(names.get(4).map(|name| name.as_str()), Some("init"));
+
+        let bodies = module.code_section().unwrap().bodies();
+        assert_eq!(
+            bodies[0].code().elements(),
+            &[
+                Instruction::I64Const(2f64.to_bits() as i64),
+                Instruction::Call(3),
+                Instruction::I32Const(0),
+                Instruction::Call(0),
+                Instruction::End,
+            ]
+        );
+        assert_eq!(
+            bodies[1].code().elements(),
+            &[
+                Instruction::GetLocal(0),
+                Instruction::Call(1),
+                Instruction::Drop,
+                Instruction::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn float_import() {
+        // (module (import "env" "pow" (func $pow (param f64 f64) (result f64))))
+        let wasm: Vec<u8> =
+            FromHex::from_hex("0061736d0100000001070160027c7c017c020b0103656e7603706f770000")
+                .unwrap();
+        let module = Module::from_bytes(&wasm).unwrap();
+
+        let translator = SoftFloat::with_defaults().unwrap();
+        assert_eq!(
+            translator.translate(&module),
+            Err(ModuleError::Custom(
+                "Cannot lower the float type of import env.pow".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn float_export() {
+        let translator = SoftFloat::with_defaults().unwrap();
+
+        // (module (func (export "sqrt") (param f64) (result f64) (f64.sqrt (local.get 0))))
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d0100000001060160017c017c03020100070801047371727400000a0701050020009f0b",
+        )
+        .unwrap();
+        let module = Module::from_bytes(&wasm).unwrap();
+        assert_eq!(
+            translator.translate(&module),
+            Err(ModuleError::Custom(
+                "Cannot lower the float type of export sqrt".to_string()
+            ))
+        );
+
+        // (module (global (export "pi") f64 (f64.const 3.25)))
+        let wasm: Vec<u8> =
+            FromHex::from_hex("0061736d01000000060d017c00440000000000000a400b0706010270690300")
+                .unwrap();
+        let module = Module::from_bytes(&wasm).unwrap();
+        assert_eq!(
+            translator.translate(&module),
+            Err(ModuleError::Custom(
+                "Cannot lower the float type of export pi".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn no_floats() {
+        // (module (func (export "main") (drop (i32.const 1))))
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d0100000001040160000003020100070801046d61696e00000a0701050041011a0b",
+        )
+        .unwrap();
+        let module = Module::from_bytes(&wasm).unwrap();
+
+        let translator = SoftFloat::with_defaults().unwrap();
+        assert_eq!(translator.translate(&module), Ok(None));
+    }
+
+    #[test]
+    fn config() {
+        let mut config = HashMap::new();
+        assert_eq!(SoftFloat::with_config(&config), Ok(SoftFloat::Bundle));
+
+        config.insert("mode".to_string(), "import".to_string());
+        assert_eq!(
+            SoftFloat::with_config(&config),
+            Ok(SoftFloat::Import("softfloat".to_string()))
+        );
+        config.insert("namespace".to_string(), "env".to_string());
+        assert_eq!(
+            SoftFloat::with_config(&config),
+            Ok(SoftFloat::Import("env".to_string()))
+        );
+
+        config.insert("mode".to_string(), "bundle".to_string());
+        assert!(SoftFloat::with_config(&config).is_err());
+        config.insert("mode".to_string(), "inline".to_string());
+        assert!(SoftFloat::with_config(&config).is_err());
+    }
+
+    #[test]
+    fn runtime_is_float_free() {
+        let runtime = Module::from_bytes(RUNTIME).unwrap();
+        let checkfloat = CheckFloat::with_defaults().unwrap();
+        assert_eq!(checkfloat.validate(&runtime), Ok(true));
+    }
+
+    /// The runtime, instantiated in an interpreter, so that its routines can be compared with
+    /// native floats.
+    struct Runtime {
+        store: wasmi::Store<()>,
+        instance: wasmi::Instance,
+    }
+
+    impl Runtime {
+        fn new() -> Self {
+            let engine = wasmi::Engine::default();
+            let module = wasmi::Module::new(&engine, RUNTIME).unwrap();
+            let mut store = wasmi::Store::new(&engine, ());
+            let instance = wasmi::Linker::new(&engine)
+                .instantiate(&mut store, &module)
+                .and_then(|instance| instance.start(&mut store))
+                .unwrap();
+            Runtime { store, instance }
+        }
+
+        /// Calls the routine. Returns None if it trapped.
+        fn call<P: wasmi::WasmParams, R: wasmi::WasmResults>(
+            &mut self,
+            routine: &str,
+            params: P,
+        ) -> Option<R> {
+            self.instance
+                .get_typed_func::<P, R>(&self.store, routine)
+                .unwrap()
+                .call(&mut self.store, params)
+                .ok()
+        }
+    }
+
+    /// Routines by name, along with the native operations they implement.
+    type Unary<T> = (&'static str, fn(T) -> T);
+    type Binary<T> = (&'static str, fn(T, T) -> T);
+    type Compare<T> = (&'static str, fn(T, T) -> bool);
+
+    /// A xorshift generator, so that the samples are the same on every run.
+    struct Samples(u64);
+
+    impl Samples {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Special values and the boundaries of conversions, followed by arbitrary bit patterns
+        /// and ordinary fractions.
+        fn f32s(&mut self, count: usize) -> Vec<f32> {
+            let mut samples = vec![
+                0.0,
+                -0.0,
+                1.0,
+                -1.0,
+                0.1,
+                2.5,
+                -2.5,
+                16777217.0,
+                2147483648.0,
+                -2147483904.0,
+                4294967296.0,
+                f32::MAX,
+                f32::MIN,
+                f32::MIN_POSITIVE,
+                f32::from_bits(1),
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                f32::NAN,
+                f32::from_bits(0xffc0_0001),
+            ];
+            for _ in 0..count {
+                let bits = self.next();
+                samples.push(if bits & 1 == 0 {
+                    f32::from_bits((bits >> 32) as u32)
+                } else {
+                    (bits >> 8) as i32 as f32 / ((bits >> 40) as u16 as f32 + 1.0)
+                });
+            }
+            samples
+        }
+
+        fn f64s(&mut self, count: usize) -> Vec<f64> {
+            let mut samples = vec![
+                0.0,
+                -0.0,
+                1.0,
+                -1.0,
+                0.1,
+                2.5,
+                -2.5,
+                9007199254740993.0,
+                2147483648.0,
+                -2147483649.0,
+                9223372036854775808.0,
+                18446744073709551616.0,
+                f64::MAX,
+                f64::MIN,
+                f64::MIN_POSITIVE,
+                f64::from_bits(1),
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NAN,
+                f64::from_bits(0xfff8_0000_0000_0001),
+            ];
+            for _ in 0..count {
+                let bits = self.next();
+                samples.push(if bits & 1 == 0 {
+                    f64::from_bits(self.next())
+                } else {
+                    (bits >> 8) as i64 as f64 / ((bits >> 40) as u16 as f64 + 1.0)
+                });
+            }
+            samples
+        }
+    }
+
+    /// Arithmetic results must be bit-for-bit identical, except that NaNs must be canonical.
+    fn assert_f32(routine: &str, args: &[f32], actual: i32, expected: f32) {
+        let expected = if expected.is_nan() {
+            0x7fc0_0000
+        } else {
+            expected.to_bits()
+        };
+        assert_eq!(actual as u32, expected, "{} {:?}", routine, args);
+    }
+
+    fn assert_f64(routine: &str, args: &[f64], actual: i64, expected: f64) {
+        let expected = if expected.is_nan() {
+            0x7ff8_0000_0000_0000
+        } else {
+            expected.to_bits()
+        };
+        assert_eq!(actual as u64, expected, "{} {:?}", routine, args);
+    }
+
+    /// min and max as defined by WebAssembly: NaN if either operand is NaN, and -0 below +0.
+    fn min_f32(a: f32, b: f32) -> f32 {
+        if a == b {
+            f32::from_bits(a.to_bits() | b.to_bits())
+        } else if a.is_nan() || b.is_nan() {
+            f32::NAN
+        } else {
+            a.min(b)
+        }
+    }
+
+    fn max_f32(a: f32, b: f32) -> f32 {
+        if a == b {
+            f32::from_bits(a.to_bits() & b.to_bits())
+        } else if a.is_nan() || b.is_nan() {
+            f32::NAN
+        } else {
+            a.max(b)
+        }
+    }
+
+    fn min_f64(a: f64, b: f64) -> f64 {
+        if a == b {
+            f64::from_bits(a.to_bits() | b.to_bits())
+        } else if a.is_nan() || b.is_nan() {
+            f64::NAN
+        } else {
+            a.min(b)
+        }
+    }
+
+    fn max_f64(a: f64, b: f64) -> f64 {
+        if a == b {
+            f64::from_bits(a.to_bits() & b.to_bits())
+        } else if a.is_nan() || b.is_nan() {
+            f64::NAN
+        } else {
+            a.max(b)
+        }
+    }
+
+    #[test]
+    fn runtime_f32_matches_native() {
+        let mut runtime = Runtime::new();
+        let mut samples = Samples(0x2545_f491_4f6c_dd1d);
+        let lhs = samples.f32s(300);
+        let rhs = samples.f32s(30);
+
+        let binary: &[Binary<f32>] = &[
+            ("f32_add", |a, b| a + b),
+            ("f32_sub", |a, b| a - b),
+            ("f32_mul", |a, b| a * b),
+            ("f32_div", |a, b| a / b),
+            ("f32_min", min_f32),
+            ("f32_max", max_f32),
+        ];
+        let compare: &[Compare<f32>] = &[
+            ("f32_eq", |a, b| a == b),
+            ("f32_ne", |a, b| a != b),
+            ("f32_lt", |a, b| a < b),
+            ("f32_le", |a, b| a <= b),
+            ("f32_gt", |a, b| a > b),
+            ("f32_ge", |a, b| a >= b),
+        ];
+        for &a in &lhs {
+            for &b in rhs.iter().chain(&lhs[..19]) {
+                let args = (a.to_bits() as i32, b.to_bits() as i32);
+                for (routine, op) in binary {
+                    let actual = runtime.call(routine, args).unwrap();
+                    assert_f32(routine, &[a, b], actual, op(a, b));
+                }
+                for (routine, op) in compare {
+                    let actual: i32 = runtime.call(routine, args).unwrap();
+                    assert_eq!(actual != 0, op(a, b), "{} {:?}", routine, [a, b]);
+                }
+                // Sign operations keep the payload of NaNs.
+                let actual: i32 = runtime.call("f32_copysign", args).unwrap();
+                assert_eq!(actual as u32, a.copysign(b).to_bits(), "f32_copysign");
+            }
+        }
+
+        let unary: &[Unary<f32>] = &[
+            ("f32_sqrt", f32::sqrt),
+            ("f32_ceil", f32::ceil),
+            ("f32_floor", f32::floor),
+            ("f32_trunc", f32::trunc),
+            ("f32_nearest", f32::round_ties_even),
+        ];
+        for &a in &lhs {
+            let arg = a.to_bits() as i32;
+            for (routine, op) in unary {
+                let actual = runtime.call(routine, arg).unwrap();
+                assert_f32(routine, &[a], actual, op(a));
+            }
+            let actual: i32 = runtime.call("f32_abs", arg).unwrap();
+            assert_eq!(actual as u32, a.abs().to_bits(), "f32_abs {}", a);
+            let actual: i32 = runtime.call("f32_neg", arg).unwrap();
+            assert_eq!(actual as u32, (-a).to_bits(), "f32_neg {}", a);
+        }
+    }
+
+    #[test]
+    fn runtime_f64_matches_native() {
+        let mut runtime = Runtime::new();
+        let mut samples = Samples(0x9e37_79b9_7f4a_7c15);
+        let lhs = samples.f64s(300);
+        let rhs = samples.f64s(30);
+
+        let binary: &[Binary<f64>] = &[
+            ("f64_add", |a, b| a + b),
+            ("f64_sub", |a, b| a - b),
+            ("f64_mul", |a, b| a * b),
+            ("f64_div", |a, b| a / b),
+            ("f64_min", min_f64),
+            ("f64_max", max_f64),
+        ];
+        let compare: &[Compare<f64>] = &[
+            ("f64_eq", |a, b| a == b),
+            ("f64_ne", |a, b| a != b),
+            ("f64_lt", |a, b| a < b),
+            ("f64_le", |a, b| a <= b),
+            ("f64_gt", |a, b| a > b),
+            ("f64_ge", |a, b| a >= b),
+        ];
+        for &a in &lhs {
+            for &b in rhs.iter().chain(&lhs[..20]) {
+                let args = (a.to_bits() as i64, b.to_bits() as i64);
+                for (routine, op) in binary {
+                    let actual = runtime.call(routine, args).unwrap();
+                    assert_f64(routine, &[a, b], actual, op(a, b));
+                }
+                for (routine, op) in compare {
+                    let actual: i32 = runtime.call(routine, args).unwrap();
+                    assert_eq!(actual != 0, op(a, b), "{} {:?}", routine, [a, b]);
+                }
+                let actual: i64 = runtime.call("f64_copysign", args).unwrap();
+                assert_eq!(actual as u64, a.copysign(b).to_bits(), "f64_copysign");
+            }
+        }
+
+        let unary: &[Unary<f64>] = &[
+            ("f64_sqrt", f64::sqrt),
+            ("f64_ceil", f64::ceil),
+            ("f64_floor", f64::floor),
+            ("f64_trunc", f64::trunc),
+            ("f64_nearest", f64::round_ties_even),
+        ];
+        for &a in &lhs {
+            let arg = a.to_bits() as i64;
+            for (routine, op) in unary {
+                let actual = runtime.call(routine, arg).unwrap();
+                assert_f64(routine, &[a], actual, op(a));
+            }
+            let actual: i64 = runtime.call("f64_abs", arg).unwrap();
+            assert_eq!(actual as u64, a.abs().to_bits(), "f64_abs {}", a);
+            let actual: i64 = runtime.call("f64_neg", arg).unwrap();
+            assert_eq!(actual as u64, (-a).to_bits(), "f64_neg {}", a);
+        }
+    }
+
+    #[test]
+    fn runtime_conversions_match_native() {
+        let mut runtime = Runtime::new();
+        let mut samples = Samples(0xd1b5_4a32_d192_ed03);
+
+        // Truncations trap on NaN and out of range values, where Rust saturates.
+        for a in samples.f32s(500) {
+            let arg = a.to_bits() as i32;
+            let t = a.trunc();
+            let i32_range = (-2147483648.0..2147483648.0).contains(&t);
+            let u32_range = (0.0..4294967296.0).contains(&t);
+            let i64_range = (-9223372036854775808.0..9223372036854775808.0).contains(&t);
+            let u64_range = (0.0..18446744073709551616.0).contains(&t);
+            let actual: Option<i32> = runtime.call("i32_trunc_f32_s", arg);
+            assert_eq!(actual, Some(t as i32).filter(|_| i32_range), "{}", a);
+            let actual: Option<i32> = runtime.call("i32_trunc_f32_u", arg);
+            assert_eq!(actual, Some(t as u32 as i32).filter(|_| u32_range), "{}", a);
+            let actual: Option<i64> = runtime.call("i64_trunc_f32_s", arg);
+            assert_eq!(actual, Some(t as i64).filter(|_| i64_range), "{}", a);
+            let actual: Option<i64> = runtime.call("i64_trunc_f32_u", arg);
+            assert_eq!(actual, Some(t as u64 as i64).filter(|_| u64_range), "{}", a);
+
+            let actual = runtime.call("f64_promote_f32", arg).unwrap();
+            assert_f64("f64_promote_f32", &[a as f64], actual, a as f64);
+        }
+
+        for a in samples.f64s(500) {
+            let arg = a.to_bits() as i64;
+            let t = a.trunc();
+            let i32_range = (-2147483648.0..2147483648.0).contains(&t);
+            let u32_range = (0.0..4294967296.0).contains(&t);
+            let i64_range = (-9223372036854775808.0..9223372036854775808.0).contains(&t);
+            let u64_range = (0.0..18446744073709551616.0).contains(&t);
+            let actual: Option<i32> = runtime.call("i32_trunc_f64_s", arg);
+            assert_eq!(actual, Some(t as i32).filter(|_| i32_range), "{}", a);
+            let actual: Option<i32> = runtime.call("i32_trunc_f64_u", arg);
+            assert_eq!(actual, Some(t as u32 as i32).filter(|_| u32_range), "{}", a);
+            let actual: Option<i64> = runtime.call("i64_trunc_f64_s", arg);
+            assert_eq!(actual, Some(t as i64).filter(|_| i64_range), "{}", a);
+            let actual: Option<i64> = runtime.call("i64_trunc_f64_u", arg);
+            assert_eq!(actual, Some(t as u64 as i64).filter(|_| u64_range), "{}", a);
+
+            let actual = runtime.call("f32_demote_f64", arg).unwrap();
+            assert_f32("f32_demote_f64", &[a as f32], actual, a as f32);
+        }
+
+        // Integers of every magnitude, to exercise rounding of wide ones.
+        for _ in 0..2000 {
+            let bits = samples.next();
+            let x = (samples.next() >> (bits % 64)) as i64 * if bits & 64 == 0 { 1 } else { -1 };
+            let actual: i32 = runtime.call("f32_convert_i32_s", x as i32).unwrap();
+            assert_eq!(actual as u32, (x as i32 as f32).to_bits(), "{}", x);
+            let actual: i32 = runtime.call("f32_convert_i32_u", x as i32).unwrap();
+            assert_eq!(actual as u32, (x as u32 as f32).to_bits(), "{}", x);
+            let actual: i32 = runtime.call("f32_convert_i64_s", x).unwrap();
+            assert_eq!(actual as u32, (x as f32).to_bits(), "{}", x);
+            let actual: i32 = runtime.call("f32_convert_i64_u", x).unwrap();
+            assert_eq!(actual as u32, (x as u64 as f32).to_bits(), "{}", x);
+            let actual: i64 = runtime.call("f64_convert_i32_s", x as i32).unwrap();
+            assert_eq!(actual as u64, (x as i32 as f64).to_bits(), "{}", x);
+            let actual: i64 = runtime.call("f64_convert_i32_u", x as i32).unwrap();
+            assert_eq!(actual as u64, (x as u32 as f64).to_bits(), "{}", x);
+            let actual: i64 = runtime.call("f64_convert_i64_s", x).unwrap();
+            assert_eq!(actual as u64, (x as f64).to_bits(), "{}", x);
+            let actual: i64 = runtime.call("f64_convert_i64_u", x).unwrap();
+            assert_eq!(actual as u64, (x as u64 as f64).to_bits(), "{}", x);
+        }
+    }
+}
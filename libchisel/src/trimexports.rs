@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
-use parity_wasm::elements::{ExportEntry, ExportSection, Internal, Module};
+use parity_wasm::elements::{ExportEntry, Internal, Module};
 
+use super::diff::{encoded_size, Change, Changes};
 use super::{ChiselModule, ModuleError, ModuleKind, ModulePreset, ModuleTranslator};
 
 /// Struct containing a list of valid exports.
@@ -90,26 +91,17 @@ impl ExportWhitelist {
 
 impl TrimExports {
     /// Iterates over the export section, if there is one, and removes
-    /// unnecessary entries.
-    fn trim_exports(&self, module: &mut Module) -> bool {
+    /// unnecessary entries. Returns the removed entries.
+    fn trim_exports(&self, module: &mut Module) -> Vec<ExportEntry> {
         if let Some(section) = module.export_section_mut() {
-            let new_section = ExportSection::with_entries(
-                section
-                    .entries()
-                    .iter()
-                    .cloned()
-                    .filter(|entry| self.whitelist.lookup(entry))
-                    .collect(),
-            );
-
-            if new_section.entries().len() < section.entries().len() {
-                *section = new_section;
-                return true;
-            }
-
-            false
+            let (kept, removed): (Vec<ExportEntry>, Vec<ExportEntry>) =
+                std::mem::take(section.entries_mut())
+                    .into_iter()
+                    .partition(|entry| self.whitelist.lookup(entry));
+            *section.entries_mut() = kept;
+            removed
         } else {
-            false
+            Vec::new()
         }
     }
 }
@@ -132,12 +124,28 @@ impl ModulePreset for TrimExports {
 
 impl ModuleTranslator for TrimExports {
     fn translate_inplace(&self, module: &mut Module) -> Result<bool, ModuleError> {
-        Ok(self.trim_exports(module))
+        Ok(!self.trim_exports(module).is_empty())
+    }
+
+    fn translate_with_changes(&self, module: &mut Module) -> Result<Option<Changes>, ModuleError> {
+        let size_before = module.export_section().cloned().map_or(0, encoded_size);
+        let removed = self.trim_exports(module);
+        if removed.is_empty() {
+            return Ok(None);
+        }
+        let size_after = module.export_section().cloned().map_or(0, encoded_size);
+
+        let mut changes = Changes::default();
+        for entry in removed {
+            changes.push(Change::ExportRemoved(entry.field().to_string()));
+        }
+        changes.add_size_delta(size_after as isize - size_before as isize);
+        Ok(Some(changes))
     }
 
     fn translate(&self, module: &Module) -> Result<Option<Module>, ModuleError> {
         let mut ret = module.clone();
-        let modified = self.trim_exports(&mut ret);
+        let modified = !self.trim_exports(&mut ret).is_empty();
         if modified {
             return Ok(Some(ret));
         }
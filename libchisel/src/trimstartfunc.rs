@@ -86,7 +86,7 @@ mod tests {
         let trimmer = TrimStartFunc::with_preset("ewasm").unwrap();
         trimmer.translate_inplace(&mut module).unwrap();
 
-        let result = module.to_bytes().unwrap();
+        let result = module.into_bytes().unwrap();
         let expect: Vec<u8> = vec![
             0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00,
             0x03, 0x02, 0x01, 0x00, 0x07, 0x08, 0x01, 0x04, 0x6d, 0x61, 0x69, 0x6e, 0x00, 0x00,
@@ -109,7 +109,7 @@ mod tests {
         let trimmer = TrimStartFunc::with_preset("ewasm").unwrap();
         trimmer.translate_inplace(&mut module).unwrap();
 
-        let result = module.to_bytes().unwrap();
+        let result = module.into_bytes().unwrap();
 
         // result is equal to initial wasm (not changed)
         assert_eq!(result, wasm);
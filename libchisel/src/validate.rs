@@ -0,0 +1,290 @@
+//! Full validation of a module against the WebAssembly specification, using wasmparser.
+//! Unlike the other validators, it type-checks every function body, so it catches invalid modules
+//! produced by translators.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use parity_wasm::elements::Module;
+use wasmparser::{
+    BinaryReaderError, FuncValidatorAllocations, Parser, ValidPayload, Validator, WasmFeatures,
+};
+
+use super::checkfloat::Function;
+use super::proposals::{self, Proposal};
+use super::{ChiselModule, ModuleError, ModuleKind, ModuleValidator};
+
+/// The proposals which can be enabled. The others cannot be parsed.
+const PROPOSALS: &[(&str, Proposal)] = &[
+    ("sign_extension", Proposal::SignExtension),
+    ("bulk_memory", Proposal::BulkMemory),
+    ("multi_value", Proposal::MultiValue),
+    ("saturating_float_to_int", Proposal::NonTrappingFloatToInt),
+];
+
+/// Struct on which ModuleValidator is implemented.
+pub struct Validate {
+    /// The proposals a module may use, on top of the MVP.
+    proposals: Vec<Proposal>,
+}
+
+/// A validation error, by its offset in the serialized module, and the function it is in.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Invalid {
+    pub function: Option<Function>,
+    pub offset: usize,
+    pub message: String,
+}
+
+impl<'a> ChiselModule<'a> for Validate {
+    type ObjectReference = &'a dyn ModuleValidator;
+
+    fn id(&'a self) -> String {
+        "validate".to_string()
+    }
+
+    fn kind(&'a self) -> ModuleKind {
+        ModuleKind::Validator
+    }
+
+    fn as_abstract(&'a self) -> Self::ObjectReference {
+        self as Self::ObjectReference
+    }
+
+    /// Allows every proposal which can be parsed.
+    fn with_defaults() -> Result<Self, ModuleError> {
+        Ok(Validate::new(
+            PROPOSALS.iter().map(|(_, proposal)| *proposal).collect(),
+        ))
+    }
+
+    fn with_config(config: &HashMap<String, String>) -> Result<Self, ModuleError> {
+        let features = match config.get("features") {
+            Some(features) => features,
+            None => return Validate::with_defaults(),
+        };
+        let proposals = features
+            .split(',')
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                PROPOSALS
+                    .iter()
+                    .find(|(proposal_name, _)| *proposal_name == name)
+                    .map(|(_, proposal)| *proposal)
+                    .ok_or_else(|| {
+                        ModuleError::InvalidConfig(
+                            "features".to_string(),
+                            format!("unknown or unsupported feature '{}'", name),
+                        )
+                    })
+            })
+            .collect::<Result<Vec<Proposal>, ModuleError>>()?;
+        Ok(Validate::new(proposals))
+    }
+}
+
+impl Validate {
+    pub fn new(proposals: Vec<Proposal>) -> Self {
+        Validate { proposals }
+    }
+
+    fn features(&self) -> WasmFeatures {
+        let enabled = |proposal: Proposal| self.proposals.contains(&proposal);
+        WasmFeatures {
+            mutable_global: true,
+            saturating_float_to_int: enabled(Proposal::NonTrappingFloatToInt),
+            sign_extension: enabled(Proposal::SignExtension),
+            reference_types: false,
+            multi_value: enabled(Proposal::MultiValue),
+            bulk_memory: enabled(Proposal::BulkMemory),
+            simd: false,
+            relaxed_simd: false,
+            threads: false,
+            tail_call: false,
+            floats: true,
+            multi_memory: false,
+            exceptions: false,
+            memory64: false,
+            extended_const: false,
+            component_model: false,
+            function_references: false,
+            memory_control: false,
+            gc: false,
+            component_model_values: false,
+            component_model_nested_names: false,
+        }
+    }
+
+    /// Returns the first validation error of the module, if any.
+    pub fn find(&self, module: &Module) -> Result<Option<Invalid>, ModuleError> {
+        let bytes = proposals::into_bytes(module.clone())?;
+        let mut validator = Validator::new_with_features(self.features());
+        let mut allocations = FuncValidatorAllocations::default();
+        // The index of the function being validated, in the function index space.
+        let mut function = None;
+
+        let result: Result<(), BinaryReaderError> =
+            Parser::new(0).parse_all(&bytes).try_for_each(|payload| {
+                if let ValidPayload::Func(func, body) = validator.payload(&payload?)? {
+                    let mut func = func.into_validator(std::mem::take(&mut allocations));
+                    function = Some(func.index());
+                    func.validate(&body)?;
+                    allocations = func.into_allocations();
+                    function = None;
+                }
+                Ok(())
+            });
+
+        let error = match result {
+            Ok(()) => return Ok(None),
+            Err(error) => error,
+        };
+        let names = module
+            .names_section()
+            .and_then(|section| section.functions());
+        Ok(Some(Invalid {
+            function: function.map(|index| Function {
+                index,
+                name: names.and_then(|names| names.names().get(index).cloned()),
+            }),
+            offset: error.offset(),
+            message: error.message().to_string(),
+        }))
+    }
+}
+
+impl ModuleValidator for Validate {
+    fn validate(&self, module: &Module) -> Result<bool, ModuleError> {
+        Ok(self.find(module)?.is_none())
+    }
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(function) = &self.function {
+            write!(f, "{}, ", function)?;
+        }
+        write!(f, "offset {:#x}: {}", self.offset, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parity_wasm::builder;
+    use parity_wasm::elements::{Instruction, Instructions, SignExtInstruction};
+    use rustc_hex::FromHex;
+
+    use super::*;
+
+    //  (module
+    //    (func $main (export "main") (result i32)
+    //      (i32.extend8_s (i32.const 255))))
+    fn sample_module() -> Module {
+        let wasm: Vec<u8> = FromHex::from_hex(
+            "0061736d010000000105016000017f03020100070801046d61696e00000a0801060041ff01c00b000e046e61
+6d6501070100046d61696e",
+        )
+        .unwrap();
+        Module::from_bytes(&wasm).unwrap().parse_names().unwrap()
+    }
+
+    #[test]
+    fn valid() {
+        let validator = Validate::with_defaults().unwrap();
+        assert_eq!(validator.validate(&sample_module()), Ok(true));
+        assert_eq!(validator.validate(&builder::module().build()), Ok(true));
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let mut module = sample_module();
+        // Replaces the constant by one of the wrong type.
+        *module.code_section_mut().unwrap().bodies_mut()[0].code_mut() = Instructions::new(vec![
+            Instruction::I64Const(255),
+            Instruction::SignExt(SignExtInstruction::I32Extend8S),
+            Instruction::End,
+        ]);
+        let invalid = Validate::with_defaults()
+            .unwrap()
+            .find(&module)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            invalid.to_string(),
+            "function 0 ($main), offset 0x25: type mismatch: expected i32, found i64"
+        );
+    }
+
+    #[test]
+    fn disabled_proposal() {
+        let mut config = HashMap::new();
+        config.insert(
+            "features".to_string(),
+            "bulk_memory, multi_value".to_string(),
+        );
+        let invalid = Validate::with_config(&config)
+            .unwrap()
+            .find(&sample_module())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            invalid.to_string(),
+            "function 0 ($main), offset 0x25: sign extension operations support is not enabled"
+        );
+    }
+
+    #[test]
+    fn saturating_conversions() {
+        let wasm = wat::parse_str(
+            "(module (func $f (param f32) (result i32) local.get 0 i32.trunc_sat_f32_s))",
+        )
+        .unwrap();
+        let module = proposals::from_bytes(&wasm).unwrap().parse_names().unwrap();
+        let validator = Validate::with_defaults().unwrap();
+        assert_eq!(validator.validate(&module), Ok(true));
+
+        let mut config = HashMap::new();
+        config.insert("features".to_string(), "sign_extension".to_string());
+        let invalid = Validate::with_config(&config)
+            .unwrap()
+            .find(&module)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            invalid.function.as_ref().unwrap().name.as_deref(),
+            Some("f")
+        );
+        assert!(invalid.message.contains("saturating"), "{}", invalid);
+    }
+
+    #[test]
+    fn module_error() {
+        // A start function must not take parameters or return results.
+        let mut module = sample_module();
+        module.set_start_section(0);
+        let invalid = Validate::with_defaults()
+            .unwrap()
+            .find(&module)
+            .unwrap()
+            .unwrap();
+        assert_eq!(invalid.function, None);
+        assert_eq!(
+            invalid.to_string(),
+            "offset 0x1f: invalid start function type"
+        );
+    }
+
+    #[test]
+    fn invalid_config() {
+        let mut config = HashMap::new();
+        config.insert("features".to_string(), "simd".to_string());
+        assert_eq!(
+            Validate::with_config(&config).err(),
+            Some(ModuleError::InvalidConfig(
+                "features".to_string(),
+                "unknown or unsupported feature 'simd'".to_string()
+            ))
+        );
+    }
+}
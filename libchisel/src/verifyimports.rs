@@ -587,7 +587,7 @@ mod tests {
             list: ImportList::with_entries(vec![ImportType::Function(
                 "ethereum",
                 "storageStore",
-                FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
             )]),
             require_all: true,
             allow_unlisted: false,
@@ -622,7 +622,7 @@ mod tests {
             list: ImportList::with_entries(vec![ImportType::Function(
                 "ethereum",
                 "storageStore",
-                FunctionType::new(vec![ValueType::I32, ValueType::I32], None),
+                FunctionType::new(vec![ValueType::I32, ValueType::I32], vec![]),
             )]),
             allow_unlisted: true,
             require_all: true,